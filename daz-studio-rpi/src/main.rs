@@ -1,18 +1,20 @@
 use midir::{MidiInput, Ignore};
 
 const CHANNELS: i32 = 2;
+const INPUT_CHANNELS: i32 = 1;
 const SAMPLE_RATE: f64 = 48_000.0;
 const FRAMES_PER_BUFFER: u32 = 512;
+const DEFAULT_DATA_PATH: &str = "./data";
 
 mod ui;
 
 use sequencer;
-use sequencer::midimessage::MidiMessage;
+use sequencer::midimessage::{MidiMessage, pitch_bend_value, CC_SUSTAIN_PEDAL};
 use sequencer::Sequencer;
 use sequencer::sequencer_data::{SequencerData, DataBroadcaster, Message};
 
 use std::sync::mpsc;
-use std::sync::mpsc::{Sender};
+use std::sync::mpsc::{Sender, Receiver};
 use std::collections::HashMap;
 
 use sdl2::keyboard::Keycode;
@@ -33,40 +35,48 @@ fn main() {
     let (midi_event_sender, midi_event_receiver) = mpsc::channel::<sequencer::Message>();
 
     let portaudio = portaudio::PortAudio::new().unwrap();
-    let mut settings = portaudio.default_output_stream_settings(CHANNELS, SAMPLE_RATE, FRAMES_PER_BUFFER).unwrap();
-    
-    settings.flags = portaudio::stream_flags::CLIP_OFF;
 
     let (mut data_ui, ui_sender) = SequencerData::new();
 
-    let (mut sequencer, audio_sender) = Sequencer::new(SAMPLE_RATE as f32, FRAMES_PER_BUFFER as usize);
+    // Presets and samples root, can be moved with DAZ_STUDIO_DATA_PATH
+    let data_path = std::env::var("DAZ_STUDIO_DATA_PATH").unwrap_or(DEFAULT_DATA_PATH.to_string());
+
+    let (mut sequencer, audio_sender) = Sequencer::new(SAMPLE_RATE as f32, FRAMES_PER_BUFFER as usize, &data_path);
 
     // Just for testing purpose, need to synchronise this after
     data_ui.instruments = sequencer.data.instruments.clone();
     
     sequencer.audio_state_senders.push(ui_sender.clone());
 
-    let callback = move |portaudio::OutputStreamCallbackArgs { buffer, frames, .. }| {
-
-        for msg in midi_event_receiver.try_recv() {
-            match msg {
-                sequencer::Message::Midi(midi) => {
-                    if midi.first & 0xf0 == 0x90 {
-                        sequencer.note_on(midi.second, midi.third);
-                    } else if midi.first & 0xf0 == 0x80 {
-                        sequencer.note_off(midi.second);
-                    }
-                },
-            }
-        }
-
-        sequencer.process(buffer, frames, CHANNELS as usize);
-
-        portaudio::Continue
-    };
-
-    let mut stream = portaudio.open_non_blocking_stream(settings, callback).unwrap();
-    stream.start().unwrap();
+    // The input feeds the armed instrument, without an input device only the output is opened
+    let _duplex_stream;
+    let _output_stream;
+    match portaudio.default_duplex_stream_settings(INPUT_CHANNELS, CHANNELS, SAMPLE_RATE, FRAMES_PER_BUFFER) {
+        Ok(mut settings) => {
+            settings.flags = portaudio::stream_flags::CLIP_OFF;
+            let callback = move |portaudio::DuplexStreamCallbackArgs { in_buffer, out_buffer, frames, .. }| {
+                receive_midi_messages(&mut sequencer, &midi_event_receiver);
+                sequencer.process_input(in_buffer, frames, INPUT_CHANNELS as usize);
+                sequencer.process(out_buffer, frames, CHANNELS as usize);
+                portaudio::Continue
+            };
+            let mut stream = portaudio.open_non_blocking_stream(settings, callback).unwrap();
+            stream.start().unwrap();
+            _duplex_stream = stream;
+        },
+        Err(_) => {
+            let mut settings = portaudio.default_output_stream_settings(CHANNELS, SAMPLE_RATE, FRAMES_PER_BUFFER).unwrap();
+            settings.flags = portaudio::stream_flags::CLIP_OFF;
+            let callback = move |portaudio::OutputStreamCallbackArgs { buffer, frames, .. }| {
+                receive_midi_messages(&mut sequencer, &midi_event_receiver);
+                sequencer.process(buffer, frames, CHANNELS as usize);
+                portaudio::Continue
+            };
+            let mut stream = portaudio.open_non_blocking_stream(settings, callback).unwrap();
+            stream.start().unwrap();
+            _output_stream = stream;
+        },
+    }
 
     let _connexion_midi : midir::MidiInputConnection<()>;
 
@@ -79,7 +89,8 @@ fn main() {
         if in_ports.len() > 0 {
             let midi_event_sender = midi_event_sender.clone();
             let conn_in = midi_in.connect(&in_ports[0], "midir-read-input", move |_stamp, message, _| {
-                if message[0] & 0xf0 == 0x90 || message[0] & 0xf0 == 0x80 {
+                let status = message[0] & 0xf0;
+                if status == 0x90 || status == 0x80 || status == 0xb0 || status == 0xe0 {
                     midi_event_sender.send(sequencer::Message::Midi( MidiMessage {
                         first: message[0],
                         second: message[1],
@@ -102,6 +113,24 @@ fn main() {
     launch_ui(midi_event_sender, &mut data_ui, broadcast).unwrap();
 }
 
+fn receive_midi_messages(sequencer: &mut Sequencer, midi_event_receiver: &Receiver<sequencer::Message>) {
+    for msg in midi_event_receiver.try_recv() {
+        match msg {
+            sequencer::Message::Midi(midi) => {
+                if midi.first & 0xf0 == 0x90 {
+                    sequencer.note_on(midi.second, midi.third);
+                } else if midi.first & 0xf0 == 0x80 {
+                    sequencer.note_off(midi.second);
+                } else if midi.first & 0xf0 == 0xb0 {
+                    sequencer.control_change(midi.second, midi.third as f32 / 127.);
+                } else if midi.first & 0xf0 == 0xe0 {
+                    sequencer.pitch_bend(pitch_bend_value(midi.second, midi.third));
+                }
+            },
+        }
+    }
+}
+
 fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut SequencerData, broadcaster: DataBroadcaster) -> Result<(), Infallible> {
     let output_settings = OutputSettingsBuilder::new().scale(2).build();
     let mut window = Window::new("Emulator", &output_settings);
@@ -132,7 +161,12 @@ fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut Sequen
     ]);
 
     let mut main_ui = ui::MainUI  {
-        metronome_left: true
+        metronome_left: true,
+        settings_page: false,
+        parameter_selected_idx: 0,
+        slices_page: false,
+        slice_selected_idx: 0,
+        error_message: None,
     };
 
     'main_loop: loop {
@@ -145,6 +179,38 @@ fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut Sequen
         for event in window.events() {
             match event {
                 SimulatorEvent::Quit => break 'main_loop,
+                // Settings values follow the key repeat
+                SimulatorEvent::KeyDown {
+                    keycode,
+                    ..
+                } if main_ui.settings_page && (keycode == Keycode::Left || keycode == Keycode::Right) => {
+                    let idx = data_ui.instrument_selected_id;
+                    let instrument = &data_ui.instruments[idx];
+                    if let Some(parameter) = instrument.parameters.get(main_ui.parameter_selected_idx) {
+                        let mut direction = 1.;
+                        if keycode == Keycode::Left {
+                            direction = -1.;
+                        }
+                        let value = parameter.next_value(instrument.parameter_values[main_ui.parameter_selected_idx], direction);
+                        broadcaster.send(Message::SetParameter(idx, parameter.id, value));
+                    }
+                },
+                SimulatorEvent::KeyDown {
+                    keycode,
+                    ..
+                } if main_ui.slices_page && (keycode == Keycode::Left || keycode == Keycode::Right) => {
+                    let idx = data_ui.instrument_selected_id;
+                    let instrument = &data_ui.instruments[idx];
+                    if let Some(k) = main_ui.selected_slice_parameter(data_ui) {
+                        let mut direction = 1.;
+                        if keycode == Keycode::Left {
+                            direction = -1.;
+                        }
+                        let parameter = &instrument.parameters[k];
+                        let value = parameter.next_value(instrument.parameter_values[k], direction);
+                        broadcaster.send(Message::SetParameter(idx, parameter.id, value));
+                    }
+                },
                 SimulatorEvent::KeyDown {
                     keycode,
                     repeat: false,
@@ -157,6 +223,14 @@ fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut Sequen
                     if keycode == Keycode::Space {
                         broadcaster.send(Message::PlayStop);
                     } 
+
+                    if keycode == Keycode::LShift {
+                        midi_event_sender.send(sequencer::Message::Midi(MidiMessage {
+                            first: 0xbc,
+                            second: CC_SUSTAIN_PEDAL,
+                            third: 127
+                        })).unwrap();
+                    }
                     
                     let note = key_board_notes.get(&keycode); 
                     if note.is_some() {
@@ -175,11 +249,39 @@ fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut Sequen
                     match keycode {
                         Keycode::Escape => break 'main_loop,
                         Keycode::Backspace => broadcaster.send(Message::UndoLastSession),
+                        // Main page, settings, then the slices when the instrument plays a sliced loop
+                        Keycode::Tab => {
+                            if main_ui.settings_page {
+                                main_ui.settings_page = false;
+                                main_ui.slices_page = !main_ui.slice_parameters(data_ui).is_empty();
+                                main_ui.slice_selected_idx = 0;
+                            } else if main_ui.slices_page {
+                                main_ui.slices_page = false;
+                            } else {
+                                main_ui.settings_page = true;
+                            }
+                            main_ui.parameter_selected_idx = 0;
+                        },
+                        Keycode::Up if main_ui.settings_page => main_ui.previous_parameter(data_ui),
+                        Keycode::Down if main_ui.settings_page => main_ui.next_parameter(data_ui),
+                        Keycode::Left | Keycode::Right if main_ui.settings_page => {},
+                        Keycode::Return if main_ui.settings_page => broadcaster.send(Message::SaveCurrentPreset),
+                        Keycode::Up if main_ui.slices_page => main_ui.previous_slice(data_ui),
+                        Keycode::Down if main_ui.slices_page => main_ui.next_slice(data_ui),
+                        Keycode::Left | Keycode::Right if main_ui.slices_page => {},
                         Keycode::Up => broadcaster.send(Message::PreviousInstrument),
                         Keycode::Down => broadcaster.send(Message::NextInstrument),
                         Keycode::Left => broadcaster.send(Message::PreviousPreset),
                         Keycode::Right => broadcaster.send(Message::NextPreset),
                         Keycode::W => broadcaster.send(Message::SetIsRecording(!data_ui.is_recording)),
+                        Keycode::Comma => broadcaster.send(Message::SetIsArmed(!data_ui.is_armed)),
+                        // The loop of the instrument is rendered into the next sampler
+                        Keycode::Period => broadcaster.send(Message::BounceInstrument(data_ui.instrument_selected_id, false)),
+                        Keycode::Slash => broadcaster.send(Message::BounceInstrument(data_ui.instrument_selected_id, true)),
+                        Keycode::Semicolon => {
+                            let idx = data_ui.instrument_selected_id;
+                            broadcaster.send(Message::SetInstrumentFrozen(idx, !data_ui.instruments[idx].is_frozen));
+                        },
                         Keycode::X => broadcaster.send(Message::SetMetronomeActive(!data_ui.metronome_active)),
                         Keycode::C => {
                             broadcaster.send(Message::PreviousQuantize);
@@ -195,6 +297,13 @@ fn launch_ui(midi_event_sender: Sender<sequencer::Message>, data_ui: &mut Sequen
                             let new_tempo = data_ui.tempo + 1.0;
                             broadcaster.send(Message::SetTempo(new_tempo));
                         },
+                        Keycode::LShift => {
+                            midi_event_sender.send(sequencer::Message::Midi(MidiMessage {
+                                first: 0xbc,
+                                second: CC_SUSTAIN_PEDAL,
+                                third: 0
+                            })).unwrap();
+                        },
                         _ => if let Some(note) = key_board_notes.get(&keycode) {
                             midi_event_sender.send(sequencer::Message::Midi(MidiMessage {
                                 first: 0x8c,
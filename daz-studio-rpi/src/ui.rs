@@ -1,5 +1,5 @@
 
-use sequencer::{sequencer_data::{SequencerData, InstrumentData}, midimessage::NoteEvent};
+use sequencer::{sequencer_data::{SequencerData, InstrumentData}, midimessage::{NoteEvent, AutomationTarget}, parameter::ParameterUnit};
 
 use core::convert::Infallible;
 use embedded_graphics::{
@@ -20,6 +20,8 @@ use embedded_graphics_simulator::{
     SimulatorDisplay
 };
 
+use std::time::{Duration, Instant};
+
 pub const SCREEN_WIDTH: u32 = 320;
 pub const SCREEN_HEIGHT: u32 = 240;
 
@@ -27,6 +29,11 @@ pub const BACKGROUND_COLOR : Rgb888 = Rgb888::new(34, 51, 59);
 
 pub const INSTRUMENT_COLOR : Rgb888 = Rgb888::new(234, 224, 213);
 pub const WAVEFORM_COLOR : Rgb888 = Rgb888::new(34, 51, 59);
+pub const AUTOMATION_COLOR : Rgb888 = Rgb888::new(223, 146, 142);
+pub const ERROR_COLOR : Rgb888 = Rgb888::new(255, 51, 36);
+pub const FROZEN_COLOR : Rgb888 = Rgb888::new(120, 190, 230);
+
+const ERROR_MESSAGE_DURATION : Duration = Duration::from_secs(4);
 
 pub const LEFT_MARGIN : i32 = 10;
 pub const HEIGHT_RECT_INSTRU : i32 = 30;
@@ -34,6 +41,11 @@ pub const HEIGHT_RECT_INSTRU : i32 = 30;
 
 pub struct MainUI {
    pub metronome_left: bool,
+   pub settings_page: bool,
+   pub parameter_selected_idx: usize,
+   pub slices_page: bool,
+   pub slice_selected_idx: usize,
+   pub error_message: Option<(String, Instant)>,
 }
 
 impl MainUI {
@@ -144,6 +156,12 @@ impl MainUI {
                     .into_styled(PrimitiveStyle::with_stroke(record_color, 1))
                     .draw(display)?;
             }
+            // Ring of the audio input recording
+            if data_ui.is_armed {
+                Circle::with_center(circle_record.center(), h_triangle as u32 + 6)
+                    .into_styled(PrimitiveStyle::with_stroke(record_color, 1))
+                    .draw(display)?;
+            }
         }
     
         // Metronome
@@ -215,8 +233,14 @@ impl MainUI {
             text.draw(display)?;
         }
     
+        if self.settings_page {
+            self.draw_settings(data_ui, display, header_rectangle.bottom_right().unwrap().y)?;
+        }
+        else if self.slices_page {
+            self.draw_slices(data_ui, display, header_rectangle.bottom_right().unwrap().y)?;
+        }
         // Instruments
-        {
+        else {
             let margin_top_instrument = 10;
             let mut i : usize = 0;
             let mut y = header_rectangle.bottom_right().unwrap().y + margin_top_instrument;
@@ -277,10 +301,237 @@ impl MainUI {
         }
 
         self.draw_wave_form(data_ui, display, Rectangle::new(Point::new(SCREEN_WIDTH as i32 / 2 - 50 / 2, 0), Size::new(50, 30)))?;
+
+        self.draw_error_message(data_ui, display)?;
     
         Ok(())
     }
 
+    // Loading errors are shown one after the other at the bottom of the screen
+    pub fn draw_error_message(&mut self,
+        data_ui: &mut SequencerData,
+        display: &mut SimulatorDisplay<Rgb888>,
+    ) -> Result<(), Infallible> {
+
+        let is_expired = match &self.error_message {
+            Some((_, shown_at)) => shown_at.elapsed() > ERROR_MESSAGE_DURATION,
+            None => true,
+        };
+        if is_expired {
+            self.error_message = None;
+            if !data_ui.error_messages.is_empty() {
+                self.error_message = Some((data_ui.error_messages.remove(0), Instant::now()));
+            }
+        }
+
+        if let Some((error_message, _)) = &self.error_message {
+            let height_banner = 16;
+            let max_characters = (SCREEN_WIDTH as i32 - LEFT_MARGIN * 2) as usize / 6;
+            let text : String = error_message.chars().take(max_characters).collect();
+
+            Rectangle::new(
+                Point::new(0, SCREEN_HEIGHT as i32 - height_banner),
+                Size::new(SCREEN_WIDTH, height_banner as u32)
+            ).into_styled(PrimitiveStyle::with_fill(ERROR_COLOR))
+            .draw(display)?;
+
+            Text::new(
+                &text,
+                Point::new(LEFT_MARGIN, SCREEN_HEIGHT as i32 - height_banner / 2 + 3),
+                MonoTextStyle::new(&FONT_6X12, INSTRUMENT_COLOR)
+            ).draw(display)?;
+        }
+        Ok({})
+    }
+
+    pub fn next_parameter(&mut self, data_ui: &SequencerData) {
+        let nb_parameters = data_ui.instruments[data_ui.instrument_selected_id].parameters.len();
+        self.parameter_selected_idx += 1;
+        if self.parameter_selected_idx >= nb_parameters {
+            self.parameter_selected_idx = 0;
+        }
+    }
+
+    pub fn previous_parameter(&mut self, data_ui: &SequencerData) {
+        let nb_parameters = data_ui.instruments[data_ui.instrument_selected_id].parameters.len();
+        if self.parameter_selected_idx > 0 {
+            self.parameter_selected_idx -= 1;
+        } else if nb_parameters > 0 {
+            self.parameter_selected_idx = nb_parameters - 1;
+        }
+    }
+
+    // Indexes of the parameters moving the slices of the selected instrument
+    pub fn slice_parameters(&self, data_ui: &SequencerData) -> Vec<usize> {
+        let instrument = &data_ui.instruments[data_ui.instrument_selected_id];
+        return (0..instrument.parameters.len()).filter(|&k| instrument.parameters[k].unit == ParameterUnit::SlicePoint).collect();
+    }
+
+    pub fn selected_slice_parameter(&self, data_ui: &SequencerData) -> Option<usize> {
+        return self.slice_parameters(data_ui).get(self.slice_selected_idx).copied();
+    }
+
+    pub fn next_slice(&mut self, data_ui: &SequencerData) {
+        let nb_slices = self.slice_parameters(data_ui).len();
+        self.slice_selected_idx += 1;
+        if self.slice_selected_idx >= nb_slices {
+            self.slice_selected_idx = 0;
+        }
+    }
+
+    pub fn previous_slice(&mut self, data_ui: &SequencerData) {
+        let nb_slices = self.slice_parameters(data_ui).len();
+        if self.slice_selected_idx > 0 {
+            self.slice_selected_idx -= 1;
+        } else if nb_slices > 0 {
+            self.slice_selected_idx = nb_slices - 1;
+        }
+    }
+
+    pub fn draw_settings(&mut self,
+        data_ui: &SequencerData,
+        display: &mut SimulatorDisplay<Rgb888>,
+        y: i32,
+    ) -> Result<(), Infallible> {
+
+        let instrument = &data_ui.instruments[data_ui.instrument_selected_id];
+
+        let height_parameter = 16;
+        let width_value_bar = 60;
+        let x_value_bar = SCREEN_WIDTH as i32 / 2;
+
+        let text_style = MonoTextStyle::new(&FONT_6X12, INSTRUMENT_COLOR);
+        let text_style_selected = MonoTextStyle::new(&FONT_6X12, BACKGROUND_COLOR);
+
+        let stroke_rect = PrimitiveStyleBuilder::new()
+            .stroke_color(INSTRUMENT_COLOR)
+            .stroke_width(1)
+            .build();
+
+        let fill_rect = PrimitiveStyleBuilder::new()
+            .fill_color(INSTRUMENT_COLOR)
+            .build();
+
+        let fill_rect_selected = PrimitiveStyleBuilder::new()
+            .fill_color(BACKGROUND_COLOR)
+            .build();
+
+        let title = [instrument.name.as_str(), instrument.presets[instrument.current_preset_id].as_str()].join(" - ");
+        Text::new(&title, Point::new(LEFT_MARGIN, y + 14), text_style).draw(display)?;
+
+        let y_parameters = y + 20;
+        let max_parameter_per_page = ((SCREEN_HEIGHT as i32 - y_parameters) / height_parameter) as usize;
+
+        // Keep the selected parameter visible
+        let mut offset = 0;
+        if self.parameter_selected_idx >= max_parameter_per_page {
+            offset = self.parameter_selected_idx + 1 - max_parameter_per_page;
+        }
+
+        for i in offset..instrument.parameters.len().min(offset + max_parameter_per_page) {
+            let parameter = &instrument.parameters[i];
+            let value = instrument.parameter_values[i];
+            let y_parameter = y_parameters + (i - offset) as i32 * height_parameter;
+
+            let mut style = text_style;
+            let mut style_bar = fill_rect;
+            if i == self.parameter_selected_idx {
+                Rectangle::new(
+                    Point::new(LEFT_MARGIN, y_parameter),
+                    Size::new(SCREEN_WIDTH - LEFT_MARGIN as u32 * 2, height_parameter as u32 - 2)
+                ).into_styled(fill_rect)
+                .draw(display)?;
+                style = text_style_selected;
+                style_bar = fill_rect_selected;
+            }
+
+            Text::new(&parameter.name, Point::new(LEFT_MARGIN + 4, y_parameter + 10), style).draw(display)?;
+
+            let mut ratio = 0.;
+            if parameter.max > parameter.min {
+                ratio = (value - parameter.min) / (parameter.max - parameter.min);
+            }
+            let value_bar = Rectangle::new(
+                Point::new(x_value_bar, y_parameter + 3),
+                Size::new(width_value_bar, height_parameter as u32 - 8)
+            );
+            if i != self.parameter_selected_idx {
+                value_bar.into_styled(stroke_rect).draw(display)?;
+            }
+            Rectangle::new(
+                value_bar.top_left,
+                Size::new((width_value_bar as f32 * ratio) as u32, value_bar.size.height)
+            ).into_styled(style_bar)
+            .draw(display)?;
+
+            Text::new(
+                &parameter.format_value(value),
+                Point::new(x_value_bar + width_value_bar as i32 + 8, y_parameter + 10),
+                style
+            ).draw(display)?;
+        }
+
+        Ok({})
+    }
+
+    // The loop as a timeline with a marker at the start of each slice
+    pub fn draw_slices(&mut self,
+        data_ui: &SequencerData,
+        display: &mut SimulatorDisplay<Rgb888>,
+        y: i32,
+    ) -> Result<(), Infallible> {
+
+        let instrument = &data_ui.instruments[data_ui.instrument_selected_id];
+        let slice_parameters = self.slice_parameters(data_ui);
+        // Slicing again can remove the selected slice
+        if self.slice_selected_idx >= slice_parameters.len() {
+            self.slice_selected_idx = slice_parameters.len().saturating_sub(1);
+        }
+
+        let text_style = MonoTextStyle::new(&FONT_6X12, INSTRUMENT_COLOR);
+
+        let stroke_rect = PrimitiveStyleBuilder::new()
+            .stroke_color(INSTRUMENT_COLOR)
+            .stroke_width(1)
+            .build();
+
+        let marker_style = PrimitiveStyle::with_stroke(INSTRUMENT_COLOR, 1);
+        let marker_selected_style = PrimitiveStyle::with_stroke(AUTOMATION_COLOR, 3);
+
+        let title = [instrument.name.as_str(), instrument.presets[instrument.current_preset_id].as_str()].join(" - ");
+        Text::new(&title, Point::new(LEFT_MARGIN, y + 14), text_style).draw(display)?;
+
+        let timeline = Rectangle::new(
+            Point::new(LEFT_MARGIN, y + 30),
+            Size::new(SCREEN_WIDTH - LEFT_MARGIN as u32 * 2, 80)
+        );
+        timeline.into_styled(stroke_rect).draw(display)?;
+
+        for (i, &k) in slice_parameters.iter().enumerate() {
+            let parameter = &instrument.parameters[k];
+            let mut ratio = 0.;
+            if parameter.max > parameter.min {
+                ratio = (instrument.parameter_values[k] - parameter.min) / (parameter.max - parameter.min);
+            }
+            let x = timeline.top_left.x + (ratio * (timeline.size.width - 1) as f32) as i32;
+            let mut style = marker_style;
+            if i == self.slice_selected_idx {
+                style = marker_selected_style;
+            }
+            Line::new(Point::new(x, timeline.top_left.y), Point::new(x, timeline.top_left.y + timeline.size.height as i32 - 1))
+                .into_styled(style)
+                .draw(display)?;
+        }
+
+        if let Some(&k) = slice_parameters.get(self.slice_selected_idx) {
+            let parameter = &instrument.parameters[k];
+            let text = format!("Slice {}/{}  {}", self.slice_selected_idx + 1, slice_parameters.len(), parameter.format_value(instrument.parameter_values[k]));
+            Text::new(&text, Point::new(LEFT_MARGIN, timeline.top_left.y + timeline.size.height as i32 + 20), text_style).draw(display)?;
+        }
+
+        Ok({})
+    }
+
     pub fn draw_instument(&mut self,
         data_ui: &SequencerData,
         instrument: &InstrumentData,
@@ -377,6 +628,13 @@ impl MainUI {
                 .draw(display)?;
         }
 
+        // Frozen tracks play a render of their loop
+        if instrument.is_frozen {
+            Rectangle::new(Point::new(right_box_name - 8, y + 3), Size::new(5, 5))
+                .into_styled(PrimitiveStyle::with_fill(FROZEN_COLOR))
+                .draw(display)?;
+        }
+
         let x = rectangle_instrument_name.top_left.x + 10;
         Text::new(&instrument.name, Point::new(x, y + 6 + 4), text_style).draw(display)?;
         Text::new(&instrument.presets[instrument.current_preset_id], Point::new(x, y + (10 + 2) * 2), text_style).draw(display)?;
@@ -399,6 +657,13 @@ impl MainUI {
             data_ui
         )?;
 
+        self.draw_automation_points(
+            display, 
+            instrument, 
+            rectangle_instrument_notes, 
+            data_ui
+        )?;
+
         Ok({})
     }
 
@@ -452,4 +717,50 @@ impl MainUI {
         Ok({})    
     }
 
+    fn draw_automation_points(&mut self, display: &mut SimulatorDisplay<Rgb888>, instrument: &InstrumentData, box_draw: Rectangle, data_ui: & SequencerData) -> Result<(), Infallible> {
+
+        let automation_points = &instrument.automation_points;
+
+        let nb_ticks = data_ui.bars * 4 * data_ui.ticks_per_quarter_note;
+        let size_tick = box_draw.size.width as f32 * 1.0 / nb_ticks as f32;
+        let bottom = box_draw.bottom_right().unwrap().y;
+
+        let mut targets : Vec<AutomationTarget> = Vec::new();
+        for automation_point in automation_points.iter() {
+            if !targets.contains(&automation_point.target) {
+                targets.push(automation_point.target);
+            }
+        }
+
+        // One lane per target, drawn over the notes
+        for target in targets.iter() {
+            let mut previous_point : Option<Point> = None;
+            for automation_point in automation_points.iter().filter(|automation_point| automation_point.target == *target) {
+                let mut value = automation_point.value;
+                if *target == AutomationTarget::PitchBend {
+                    value = (value + 1.) / 2.;
+                } else if let AutomationTarget::Parameter(id) = *target {
+                    if let Some(parameter_idx) = instrument.parameter_index(id) {
+                        let parameter = &instrument.parameters[parameter_idx];
+                        value = (value - parameter.min) / (parameter.max - parameter.min);
+                    }
+                }
+                let point = Point::new(
+                    box_draw.top_left.x + (automation_point.tick as f32 * size_tick) as i32,
+                    bottom - (value * (box_draw.size.height - 1) as f32) as i32
+                );
+
+                if let Some(previous_point) = previous_point {
+                    Line::new(previous_point, point)
+                        .into_styled(PrimitiveStyle::with_stroke(AUTOMATION_COLOR, 1))
+                        .draw(display)?;
+                } else {
+                    Pixel(point, AUTOMATION_COLOR).draw(display)?;
+                }
+                previous_point = Some(point);
+            }
+        }
+        Ok({})
+    }
+
 }
\ No newline at end of file
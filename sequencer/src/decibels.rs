@@ -10,6 +10,13 @@ pub fn db_to_gain (decibels: f32) -> f32
     return 0.0;
 }
 
+pub fn gain_to_db (gain: f32) -> f32
+{
+    if gain > 0.0 {
+        return f32::max(20.0 * gain.log10(), MINUS_INFINITY_DB);
+    }
+    return MINUS_INFINITY_DB;
+}
 
 pub fn root_mean_square_stereo(outputs: &[f32], num_samples: usize) -> [f32; 2] {
     let mut rms_left = 0.;
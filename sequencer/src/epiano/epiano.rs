@@ -1,15 +1,25 @@
 use crate::epiano::epiano_data::WAVES;
 use crate::epiano::epiano_voice::EpianoVoice;
-use crate::epiano::epiano_preset_bank::get_epiano_presets;
-use crate::processor::Processor;
+use crate::processor::{Processor, ProcessorSnapshot};
 
-use crate::midimessage::NoteEvent;
-use crate::preset::{Preset};
+use crate::midimessage::{NoteEvent, AutomationPoint, CC_MODULATION_WHEEL, CC_SUSTAIN_PEDAL};
+use crate::sustain_pedal::SustainPedal;
+use crate::preset::{Preset, load_user_presets, PresetWriter, new_preset_name};
+use crate::load_error::LoadError;
+use crate::utils::{pitch_bend_ratio, DEFAULT_PITCH_BEND_RANGE};
 
-use super::epiano_preset::EpianoPreset;
+use crate::parameter::{Parameter, ParameterUnit};
+
+use super::epiano_preset::{EpianoPreset, NB_PARAMETERS, PARAMETER_NAMES};
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
 
 const SILENCE : f32 = 0.0001; // voice choking
 const MAX_VOICES: usize = 32;
+// The factory presets come first, their file names start with their position, then the user presets
+const PRESETS_DIRECTORY : &str = "epiano-presets";
 
 pub struct Epiano {
     preset_id: usize,
@@ -41,6 +51,8 @@ pub struct Epiano {
 	velsens: f32,
 	volume: f32,
 	modwhl: f32,
+	bend_ratio: f32,
+	sustain_pedal: SustainPedal,
 	voices: [EpianoVoice; MAX_VOICES],
 
 	i_fs: f32,
@@ -48,10 +60,13 @@ pub struct Epiano {
 	nb_actives_notes: usize,
 
 	presets: Vec<EpianoPreset>,
+	preset_writer: PresetWriter<EpianoPreset>,
+	errors: Vec<LoadError>,
 
 	pub note_events: Vec<NoteEvent>,
+	pub automation_points: Vec<AutomationPoint>,
 
-	pub waves: Vec<i16>,
+	pub waves: Arc<Vec<i16>>,
 }
 
 #[derive(Copy, Clone)]
@@ -76,10 +91,90 @@ impl KGRP {
 	}
 }
 
+pub struct EpianoSnapshot {
+	preset: EpianoPreset,
+	modwhl: f32,
+	bend_ratio: f32,
+	kgrp: [KGRP; 34],
+	i_fs: f32,
+	note_events: Vec<NoteEvent>,
+	automation_points: Vec<AutomationPoint>,
+	waves: Arc<Vec<i16>>,
+}
+
+impl ProcessorSnapshot for EpianoSnapshot {
+	fn into_processor(self: Box<Self>) -> Box<dyn Processor> {
+		let mut voices = [EpianoVoice::new(); MAX_VOICES];
+		for v in 0..MAX_VOICES {
+			voices[v].env = 0.0;
+			voices[v].dec = 0.99;
+		}
+
+		let mut epiano = Epiano {
+			preset_id: 0,
+			muffvel: 0.,
+			size: 0,
+			width: 0.,
+			poly: 0,
+
+			fine: 0.,
+			random: 0.,
+			stretch: 0.,
+			sizevel: 0.,
+			overdrive: 0.,
+
+			treb: 0.,
+			tfrq: 0.,
+			tl: 0.,
+			tr: 0.,
+
+			lfo0: 0.,
+			lfo1: 1.,
+			dlfo: 0.,
+			lmod: 0.,
+			rmod: 0.,
+
+			kgrp: self.kgrp,
+
+			muff: 160.,
+			velsens: 0.,
+			volume: 0.2,
+			modwhl: self.modwhl,
+			bend_ratio: self.bend_ratio,
+			sustain_pedal: SustainPedal::new(),
+			voices,
+
+			i_fs: self.i_fs,
+
+			nb_actives_notes: 0,
+
+			presets: vec![self.preset],
+			preset_writer: PresetWriter::detached(),
+			errors: Vec::new(),
+
+			note_events: self.note_events,
+			automation_points: self.automation_points,
+
+			waves: self.waves,
+		};
+		epiano.recalculate();
+		Box::new(epiano)
+	}
+}
+
 impl Epiano {
-	pub fn new(sample_rate: f32) -> Epiano {
+	pub fn new(sample_rate: f32, data_path: &str) -> Epiano {
 
-		let presets = get_epiano_presets();
+		let presets_path = Path::new(data_path).join(PRESETS_DIRECTORY).to_string_lossy().to_string();
+
+		let mut errors = Vec::new();
+		let mut presets = load_user_presets::<EpianoPreset>(&presets_path, &mut errors);
+		if presets.is_empty() {
+			presets.push(EpianoPreset::empty());
+		}
+		for (id, preset) in presets.iter_mut().enumerate() {
+			preset.id = id;
+		}
 
 		let default_preset = presets[0].clone();
 
@@ -102,6 +197,8 @@ impl Epiano {
 			dlfo: 0.,
 			lfo1: 1.0,
 			modwhl: 0.,
+			bend_ratio: 1.,
+			sustain_pedal: SustainPedal::new(),
 
 			lmod: 0.,
 			rmod: 0.,
@@ -121,8 +218,11 @@ impl Epiano {
 			i_fs: 1. / sample_rate,
 
 			note_events: Vec::new(),
+			automation_points: Vec::new(),
 			presets: presets,
-			waves: WAVES.to_vec(),
+			preset_writer: PresetWriter::new(&presets_path),
+			errors,
+			waves: Arc::new(WAVES.to_vec()),
 		};
 
 
@@ -174,6 +274,7 @@ impl Epiano {
 		epiano.kgrp[32].pos = 414487;  epiano.kgrp[32].end = 422408;  epiano.kgrp[32].loop_idx = 2169;  
 
 		//extra xfade looping...
+		let waves = Arc::get_mut(&mut epiano.waves).unwrap();
 		for k in 0..28 {
 			let mut p0 = epiano.kgrp[k].end as usize;
 			let mut p1 = (epiano.kgrp[k].end - epiano.kgrp[k].loop_idx) as usize;
@@ -182,7 +283,7 @@ impl Epiano {
 			let dxf = -0.02;
 
 			while xf > 0.0 {
-				epiano.waves[p0] = ((1.0 - xf) * epiano.waves[p0] as f32 + xf * epiano.waves[p1] as f32) as i16;
+				waves[p0] = ((1.0 - xf) * waves[p0] as f32 + xf * waves[p1] as f32) as i16;
 				p0 -= 1;
 				p1 -= 1;
 				xf += dxf;
@@ -207,6 +308,17 @@ impl Epiano {
 		epiano
 	}
 
+	fn release_note(&mut self, midi_note: u8) {
+		let preset = self.presets[self.preset_id].clone();
+
+		for v in 0..self.nb_actives_notes {
+			//any voices playing that note?
+			if self.voices[v].note_id == midi_note  {
+				self.voices[v].dec = (-self.i_fs * (6.0 + 0.01 * midi_note as f32 - 5.0 * preset.envelope_release).exp()).exp();
+			}
+		}
+	}
+
 	pub fn recalculate(&mut self) {
 		let preset = self.presets[self.preset_id].clone();
 
@@ -258,6 +370,7 @@ impl Processor for Epiano {
 	fn get_name(&self) -> String { "Elec. Piano".to_string() }
 
     fn note_on(&mut self, midi_note: u8, velocity_origin: f32) {
+		self.sustain_pedal.note_on(midi_note);
         
 		let preset = self.presets[self.preset_id].clone();
 
@@ -353,17 +466,13 @@ impl Processor for Epiano {
     }
    
     fn note_off(&mut self, midi_note: u8) {
-		let preset = self.presets[self.preset_id].clone();
-
-		for v in 0..self.nb_actives_notes {
-			//any voices playing that note?
-			if self.voices[v].note_id == midi_note  {
-				self.voices[v].dec = (-self.i_fs * (6.0 + 0.01 * midi_note as f32 - 5.0 * preset.envelope_release).exp()).exp();
-			}
+		if !self.sustain_pedal.hold_note_off(midi_note) {
+			self.release_note(midi_note);
 		}
     }
 
     fn all_note_off(&mut self) {
+		self.sustain_pedal.reset();
 		let preset = self.presets[self.preset_id].clone();
 
         for i in 0..self.voices.len() {
@@ -371,6 +480,22 @@ impl Processor for Epiano {
         }
     }
 
+    fn pitch_bend(&mut self, value: f32) {
+		self.bend_ratio = pitch_bend_ratio(value, DEFAULT_PITCH_BEND_RANGE);
+    }
+
+    fn control_change(&mut self, controller: u8, value: f32) {
+		if controller == CC_MODULATION_WHEEL {
+			self.modwhl = value;
+			self.recalculate();
+		} else if controller == CC_SUSTAIN_PEDAL {
+			self.sustain_pedal.set_value(value);
+			while let Some(midi_note) = self.sustain_pedal.next_released_note() {
+				self.release_note(midi_note);
+			}
+		}
+    }
+
     fn process(&mut self, outputs: &mut [f32], num_samples: usize, _nb_channels: usize) {
 
 		let mut x;
@@ -390,7 +515,7 @@ impl Processor for Epiano {
 				for k in 0..self.nb_actives_notes {
 
 					let mut voice = &mut self.voices[k];
-					voice.frac += voice.delta;  //integer-based linear interpolation
+					voice.frac += (voice.delta as f32 * self.bend_ratio) as i32;  //integer-based linear interpolation
 					voice.pos += voice.frac >> 16;
 					voice.frac &= 0xFFFF;
 					
@@ -449,6 +574,19 @@ impl Processor for Epiano {
         self.note_events.push(midi_message);
     }
 
+    fn get_errors(&mut self) -> &mut Vec<LoadError> {
+        self.preset_writer.receive_errors(&mut self.errors);
+        return &mut self.errors;
+    }
+
+    fn get_automation_points(&mut self) -> &mut Vec<AutomationPoint> {
+        return &mut self.automation_points;
+    }
+
+    fn add_automation_point(&mut self, automation_point: AutomationPoint) {
+        self.automation_points.push(automation_point);
+    }
+
     fn get_current_preset_id(&self) -> usize {
         self.preset_id
     }
@@ -468,5 +606,71 @@ impl Processor for Epiano {
         return presets;
     }
 
+    fn get_parameters(&self) -> Vec<Parameter> {
+        let default = EpianoPreset::empty();
+        let mut parameters = Vec::new();
+        for id in 0..NB_PARAMETERS {
+            parameters.push(Parameter::new(id, PARAMETER_NAMES[id], 0., 1., default.get_parameter(id), 0.01, ParameterUnit::Percent));
+        }
+        return parameters;
+    }
+
+    fn get_parameter(&self, id: usize) -> f32 {
+        self.presets[self.preset_id].get_parameter(id)
+    }
+
+    fn set_parameter(&mut self, id: usize, value: f32) {
+		self.presets[self.preset_id].set_parameter(id, value);
+		self.recalculate();
+    }
+
+    fn save_current_preset(&mut self) -> Result<(), Box<dyn Error>> {
+        let names = self.presets.iter().map(|preset| preset.name.clone()).collect();
+        let mut preset = self.presets[self.preset_id].clone();
+        preset.name = new_preset_name(&preset.name, &names);
+        preset.id = self.presets.len();
+
+        self.preset_writer.save(preset.clone());
+
+        self.presets.push(preset);
+        self.set_current_preset_id(self.presets.len() - 1);
+        Ok(())
+    }
+
+    fn have_parameters_changed(&mut self) -> bool {
+        return false;
+    }
+
+    fn add_audio(&mut self, _name: &str, _left_channel: &mut Vec<f32>, _right_channel: &mut Vec<f32>, _sliced: bool) -> Result<(), Box<dyn Error>> {
+        Err("The Elec. Piano doesn't play audio".into())
+    }
+
+    fn snapshot(&self) -> Box<dyn ProcessorSnapshot> {
+		Box::new(EpianoSnapshot {
+			preset: self.presets[self.preset_id].clone(),
+			modwhl: self.modwhl,
+			bend_ratio: self.bend_ratio,
+			kgrp: self.kgrp,
+			i_fs: self.i_fs,
+			note_events: self.note_events.clone(),
+			automation_points: self.automation_points.clone(),
+			waves: self.waves.clone(),
+		})
+    }
+
+    fn set_armed(&mut self, _armed: bool) {
+
+    }
+
+    fn reserve_take(&mut self, _reserved: bool) {
+
+    }
+
+    fn record_input(&mut self, _inputs: &[f32], _num_samples: usize, _nb_channels: usize) {
+
+    }
+
+    fn set_tempo(&mut self, _bpm: f32) {}
+
     fn prepare(&mut self, _sample_rate: f32, _num_samples: usize, _nb_channels: usize) {}
 }
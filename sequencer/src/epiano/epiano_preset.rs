@@ -1,7 +1,24 @@
 use crate::preset::Preset;
 
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+pub const NB_PARAMETERS : usize = 12;
+pub const PARAMETER_NAMES : [&str; NB_PARAMETERS] = [
+    "Env. decay",
+    "Env. release",
+    "Hardness",
+    "Treble boost",
+    "Modulation",
+    "LFO rate",
+    "Velocity sense",
+    "Stereo width",
+    "Polyphony",
+    "Fine tuning",
+    "Random tuning",
+    "Overdrive",
+];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct EpianoPreset {
     pub id: usize,
     pub name: String,
@@ -38,6 +55,42 @@ impl EpianoPreset {
             overdrive: 0.000,
         }
     }
+
+    pub fn get_parameter(&self, id: usize) -> f32 {
+        match id {
+            0 => self.envelope_decay,
+            1 => self.envelope_release,
+            2 => self.hardness,
+            3 => self.treble_boost,
+            4 => self.modulation,
+            5 => self.lfo_rate,
+            6 => self.velocity_sense,
+            7 => self.stereo_width,
+            8 => self.polyphony,
+            9 => self.fine_tuning,
+            10 => self.random_tuning,
+            11 => self.overdrive,
+            _ => 0.,
+        }
+    }
+
+    pub fn set_parameter(&mut self, id: usize, value: f32) {
+        match id {
+            0 => self.envelope_decay = value,
+            1 => self.envelope_release = value,
+            2 => self.hardness = value,
+            3 => self.treble_boost = value,
+            4 => self.modulation = value,
+            5 => self.lfo_rate = value,
+            6 => self.velocity_sense = value,
+            7 => self.stereo_width = value,
+            8 => self.polyphony = value,
+            9 => self.fine_tuning = value,
+            10 => self.random_tuning = value,
+            11 => self.overdrive = value,
+            _ => {}
+        }
+    }
 }
 
 impl Preset for EpianoPreset {
@@ -1,5 +1,4 @@
 pub mod epiano_data;
 pub mod epiano_voice;
 pub mod epiano_preset;
-pub mod epiano_preset_bank;
 pub mod epiano;
\ No newline at end of file
@@ -4,7 +4,9 @@ const NB_COMBS_FILTERS : usize = 8;
 const NB_ALL_PASS_FILTERS : usize = 4;
 const NB_CHANNELS : usize= 2;
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ReverbParameters {
     pub room_size: f32,
     pub damping: f32,
@@ -295,6 +297,10 @@ impl Reverb {
         self.wet_gain2.reset(sample_rate, smooth_time);
     }
 
+    pub fn get_parameters(&self) -> ReverbParameters {
+        return self.parameters.clone();
+    }
+
     pub fn set_parameters(&mut self, new_params: ReverbParameters) {
         let wet_scale_factor = 3.0;
         let dry_scale_factor = 2.0;
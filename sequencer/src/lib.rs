@@ -14,21 +14,32 @@ pub mod preset;
 pub mod fx;
 pub mod sequencer_data;
 pub mod epiano;
+pub mod sustain_pedal;
+pub mod parameter;
+pub mod load_error;
+pub mod track_render;
 
 use crate::processor::Processor;
 use crate::mood::mood::Mood;
 use crate::metronome::metronome::Metronome;
 use crate::sampler::sampler::Sampler;
-use crate::midimessage::NoteEvent;
+use crate::midimessage::{NoteEvent, AutomationPoint, AutomationTarget, AutomationPlayer, CC_VOLUME};
 use crate::sequencer_data::SequencerData;
 use crate::sequencer_data::InstrumentData;
 use crate::sequencer_data::Message as SequencerDataMessage;
 use crate::midimessage::MidiMessage;
 use crate::epiano::epiano::Epiano;
+use crate::synthesizer::synthesizer::Synthesizer;
 use crate::decibels::root_mean_square_stereo;
+use crate::track_render::{FrozenTrack, TrackRenderer, RenderTiming, RenderResult};
 
 use std::sync::mpsc::Sender;
 
+fn is_recorded_event_playable(data: &SequencerData, stamp: i32, record_session: i32, stamp_record: i32) -> bool {
+    let record_recently = (stamp - stamp_record) < data.nb_ticks() / 2;
+    !record_recently || data.record_session != record_session
+}
+
 pub enum Message {
     Midi(MidiMessage),
 }
@@ -45,11 +56,31 @@ pub struct Sequencer {
     processors_outputs: Vec<Vec<f32>>,
     pub audio_state_senders: Vec<Sender<sequencer_data::Message>>,
     has_new_notes: bool,
+    has_new_automation_points: bool,
     stamp: i32,
+    automation_player: AutomationPlayer,
+    // Parameter values last applied to each processor, compared with the data to find the edited ones
+    parameter_values: Vec<Vec<f32>>,
+    parameters_changed: Vec<usize>,
+    volumes_changed: Vec<usize>,
+    parameter_lists_changed: Vec<usize>,
+    presets_changed: Vec<usize>,
+    tempo: f32,
+    // Processor recording the audio input
+    armed_processor: Option<usize>,
+    take_processor: Option<usize>,
+    // Errors of the sequencer itself, shown in the UI with the ones of the processors
+    errors: Vec<String>,
+    frozen_tracks: Vec<Option<FrozenTrack>>,
+    // One render at a time for each frozen track, the edits made during a render are rendered together once it's done
+    is_freeze_rendering: Vec<bool>,
+    is_freeze_outdated: Vec<bool>,
+    was_frozen: Vec<bool>,
+    track_renderer: TrackRenderer,
 }
 
 impl Sequencer {
-    pub fn new(sample_rate: f32, buffer_size: usize) -> (Sequencer, Sender<sequencer_data::Message>) {
+    pub fn new(sample_rate: f32, buffer_size: usize, data_path: &str) -> (Sequencer, Sender<sequencer_data::Message>) {
         
         let metronome = Metronome::new(sample_rate);
 
@@ -67,20 +98,35 @@ impl Sequencer {
             data,
             audio_state_senders: Vec::new(),
             has_new_notes: false,
+            has_new_automation_points: false,
             stamp: 0,
+            automation_player: AutomationPlayer::new(),
+            parameter_values: Vec::new(),
+            parameters_changed: Vec::new(),
+            volumes_changed: Vec::new(),
+            parameter_lists_changed: Vec::new(),
+            presets_changed: Vec::new(),
+            tempo: 0.,
+            armed_processor: None,
+            take_processor: None,
+            errors: Vec::new(),
+            frozen_tracks: Vec::new(),
+            is_freeze_rendering: Vec::new(),
+            is_freeze_outdated: Vec::new(),
+            was_frozen: Vec::new(),
+            track_renderer: TrackRenderer::new(),
         };
 
         sequencer.compute_elapsed_time_each_render();
 
-        Epiano::new(sample_rate);
-
-        sequencer.add_processor(Box::new(Epiano::new(sample_rate)));
-        sequencer.add_processor(Box::new(Sampler::new(sample_rate, 1)));
-        sequencer.add_processor(Box::new(Sampler::new(sample_rate, 2)));
-        sequencer.add_processor(Box::new(Mood::new(sample_rate, 0)));
-        sequencer.add_processor(Box::new(Mood::new(sample_rate, 1)));
-        sequencer.add_processor(Box::new(Mood::new(sample_rate, 3)));
-        sequencer.add_processor(Box::new(Mood::new(sample_rate, 4)));
+        sequencer.add_processor(Box::new(Epiano::new(sample_rate, data_path)));
+        sequencer.add_processor(Box::new(Sampler::new(sample_rate, 1, data_path)));
+        sequencer.add_processor(Box::new(Sampler::new(sample_rate, 2, data_path)));
+        sequencer.add_processor(Box::new(Mood::new(sample_rate, 0, data_path)));
+        sequencer.add_processor(Box::new(Mood::new(sample_rate, 1, data_path)));
+        sequencer.add_processor(Box::new(Mood::new(sample_rate, 3, data_path)));
+        sequencer.add_processor(Box::new(Mood::new(sample_rate, 4, data_path)));
+        sequencer.add_processor(Box::new(Synthesizer::new(sample_rate, 0, 0, data_path)));
 
         return (sequencer, sender);
     }
@@ -100,15 +146,18 @@ impl Sequencer {
         return false;
     }
 
+    // Frozen tracks aren't processed, the notes would pile up in their voices until they are unfrozen
     pub fn play_recorded_note_events(&mut self) {    
         for i in 0..self.processors.len() {
+            if self.data.instruments[i].is_frozen {
+                continue;
+            }
             for k in 0..self.processors[i].get_notes_events().len() {
                 let note_event = self.processors[i].get_notes_events()[k];
                 
-                let record_recently = (self.stamp - note_event.stamp_record) < self.data.nb_ticks() / 2;
-                if note_event.tick_off != -1 && (!record_recently || self.data.record_session != note_event.record_session) {
+                if note_event.tick_off != -1 && is_recorded_event_playable(&self.data, self.stamp, note_event.record_session, note_event.stamp_record) {
                     if note_event.tick_on == self.data.tick {
-                        self.processors[i].note_on(note_event.note_id, 1.0);
+                        self.processors[i].note_on(note_event.note_id, note_event.velocity);
                     }
                     if note_event.tick_off == self.data.tick {
                         self.processors[i].note_off(note_event.note_id);
@@ -118,6 +167,177 @@ impl Sequencer {
         }
     }
 
+    pub fn play_recorded_automation_points(&mut self) {
+        let tick = self.data.tick;
+        for i in 0..self.processors.len() {
+            if self.data.instruments[i].is_frozen {
+                continue;
+            }
+            self.play_automation_points(i, tick);
+        }
+    }
+
+    // The points of the session being recorded are skipped like its notes
+    fn play_automation_points(&mut self, processor_idx: usize, tick: i32) {
+        let (data, stamp) = (&self.data, self.stamp);
+        self.automation_player.play(self.processors[processor_idx].get_automation_points(), tick, |automation_point| {
+            is_recorded_event_playable(data, stamp, automation_point.record_session, automation_point.stamp_record)
+        });
+
+        for k in 0..self.automation_player.values.len() {
+            let (target, value) = self.automation_player.values[k];
+            self.apply_automation(processor_idx, target, value);
+        }
+    }
+
+    fn apply_automation(&mut self, processor_idx: usize, target: AutomationTarget, value: f32) {
+        match target {
+            AutomationTarget::PitchBend => self.processors[processor_idx].pitch_bend(value),
+            AutomationTarget::ControlChange(CC_VOLUME) => {
+                self.data.instruments[processor_idx].volume = value;
+                if !self.volumes_changed.contains(&processor_idx) {
+                    self.volumes_changed.push(processor_idx);
+                }
+            },
+            AutomationTarget::ControlChange(controller) => self.processors[processor_idx].control_change(controller, value),
+            AutomationTarget::Parameter(id) => {
+                self.processors[processor_idx].set_parameter(id, value);
+                if let Some(parameter_idx) = self.data.instruments[processor_idx].parameter_index(id) {
+                    self.data.instruments[processor_idx].parameter_values[parameter_idx] = value;
+                    self.parameter_values[processor_idx][parameter_idx] = value;
+                }
+                if !self.parameters_changed.contains(&processor_idx) {
+                    self.parameters_changed.push(processor_idx);
+                }
+                self.refresh_parameter_list(processor_idx);
+            },
+        }
+    }
+
+    // Some parameters change others, like the slicing of a loop moving the slice points and changing the number of pads,
+    // the processor tells when its list changes so it isn't built again for every value
+    fn refresh_parameter_list(&mut self, processor_idx: usize) {
+        if self.processors[processor_idx].have_parameters_changed() {
+            self.read_parameter_values(processor_idx);
+            return;
+        }
+
+        for k in 0..self.data.instruments[processor_idx].parameters.len() {
+            let value = self.processors[processor_idx].get_parameter(self.data.instruments[processor_idx].parameters[k].id);
+            if value != self.parameter_values[processor_idx][k] {
+                self.data.instruments[processor_idx].parameter_values[k] = value;
+                self.parameter_values[processor_idx][k] = value;
+                if !self.parameters_changed.contains(&processor_idx) {
+                    self.parameters_changed.push(processor_idx);
+                }
+            }
+        }
+    }
+
+    fn read_parameter_values(&mut self, processor_idx: usize) {
+        // Some processors have parameters depending on the preset, like the pads of a sampler kit
+        let parameters = self.processors[processor_idx].get_parameters();
+        self.data.instruments[processor_idx].parameter_values = vec![0.; parameters.len()];
+        self.parameter_values[processor_idx] = vec![0.; parameters.len()];
+        self.data.instruments[processor_idx].parameters = parameters;
+        if !self.parameter_lists_changed.contains(&processor_idx) {
+            self.parameter_lists_changed.push(processor_idx);
+        }
+
+        for k in 0..self.data.instruments[processor_idx].parameters.len() {
+            let value = self.processors[processor_idx].get_parameter(self.data.instruments[processor_idx].parameters[k].id);
+            self.data.instruments[processor_idx].parameter_values[k] = value;
+            self.parameter_values[processor_idx][k] = value;
+        }
+        if !self.parameters_changed.contains(&processor_idx) {
+            self.parameters_changed.push(processor_idx);
+        }
+    }
+
+    fn apply_parameter_values(&mut self, processor_idx: usize) -> bool {
+        let mut has_changed = false;
+        for k in 0..self.data.instruments[processor_idx].parameters.len() {
+            let value = self.data.instruments[processor_idx].parameter_values[k];
+            if value != self.parameter_values[processor_idx][k] {
+                let id = self.data.instruments[processor_idx].parameters[k].id;
+                self.processors[processor_idx].set_parameter(id, value);
+                self.parameter_values[processor_idx][k] = value;
+                if processor_idx == self.data.instrument_selected_id {
+                    self.record_automation_point(AutomationTarget::Parameter(id), value);
+                }
+                has_changed = true;
+            }
+        }
+        if has_changed {
+            self.refresh_parameter_list(processor_idx);
+        }
+        return has_changed;
+    }
+
+    fn render_timing(&self) -> RenderTiming {
+        return RenderTiming {
+            nb_ticks: self.data.nb_ticks(),
+            tick_time: self.data.tick_time,
+            elapsed_time_each_render: self.elapsed_time_each_render,
+            sample_rate: self.sample_rate,
+            buffer_size: self.buffer_size,
+            nb_channels: self.nb_channels,
+        };
+    }
+
+    fn loop_size(&self) -> usize {
+        return self.render_timing().loop_size();
+    }
+
+    // Frame of the loop at the start of the block, matching the blocks of a render after the ticks played before them
+    fn loop_position(&self) -> usize {
+        let time = self.data.tick as f32 * self.data.tick_time + self.time_accumulated - self.elapsed_time_each_render;
+        return ((time * self.sample_rate).round() as i64).rem_euclid(self.loop_size().max(1) as i64) as usize;
+    }
+
+    // Until the first render is ready the track plays live
+    fn receive_frozen_track(&mut self, processor_idx: usize, frozen_track: FrozenTrack) {
+        self.is_freeze_rendering[processor_idx] = false;
+        let replaced_track = if self.data.instruments[processor_idx].is_frozen {
+            self.frozen_tracks[processor_idx].replace(frozen_track)
+        } else {
+            Some(frozen_track)
+        };
+        if let Some(replaced_track) = replaced_track {
+            self.track_renderer.drop_frozen_track(replaced_track);
+        }
+    }
+
+    // True while frozen tracks are being rendered again
+    pub fn is_rendering_frozen_tracks(&self) -> bool {
+        return self.is_freeze_rendering.contains(&true);
+    }
+
+    // A copy of the track is rendered by the renderer, the track keeps playing meanwhile
+    fn bounce_track(&mut self, processor_idx: usize, sliced: bool) {
+        let snapshot = self.processors[processor_idx].snapshot();
+        self.track_renderer.bounce(processor_idx, sliced, snapshot, self.render_timing());
+    }
+
+    // The render is given to the first next track taking audio, like a sampler, as a new pad or as a sliced loop
+    fn add_bounce(&mut self, processor_idx: usize, sliced: bool, mut left_channel: Vec<f32>, mut right_channel: Vec<f32>) {
+        let track_name: String = self.processors[processor_idx].get_name().chars().filter(|c| c.is_alphanumeric()).collect();
+        let name = format!("bounce-{}", track_name.to_lowercase());
+
+        for offset in 1..self.processors.len() {
+            let target_idx = (processor_idx + offset) % self.processors.len();
+            if self.processors[target_idx].add_audio(&name, &mut left_channel, &mut right_channel, sliced).is_ok() {
+                // A sliced loop is a new preset of the target
+                self.data.instruments[target_idx].presets = self.processors[target_idx].get_presets().iter().map(|preset| preset.get_name()).collect();
+                self.data.instruments[target_idx].current_preset_id = self.processors[target_idx].get_current_preset_id();
+                self.presets_changed.push(target_idx);
+                return;
+            }
+        }
+        self.errors.push(format!("Can't bounce {}, no track takes audio", self.processors[processor_idx].get_name()));
+        self.track_renderer.drop_audio(left_channel, right_channel);
+    }
+
     pub fn update(&mut self) {
         self.data.bpm_has_biped = false;
         self.time_accumulated += self.elapsed_time_each_render;
@@ -129,6 +349,7 @@ impl Sequencer {
             }
 
             self.play_recorded_note_events();
+            self.play_recorded_automation_points();
 
             self.data.tick += 1;
             self.stamp += 1;
@@ -155,15 +376,112 @@ impl Sequencer {
                 while note_events.iter()
                     .position(|&n| n.record_session == last_session)
                     .map(|e| note_events.remove(e)).is_some() {}
+
+                self.processors[self.data.instrument_selected_id].get_automation_points()
+                    .retain(|automation_point| automation_point.record_session != last_session);
                 
                 self.processors[self.data.instrument_selected_id].all_note_off();
             }
         }
 
-        let mut i : usize = 0;
-        for instrument in self.data.instruments.iter() {
-            self.processors[i].set_current_preset_id(instrument.current_preset_id);
-            i += 1;
+        if self.data.save_current_preset {
+            let idx = self.data.instrument_selected_id;
+            if idx < self.processors.len() {
+                match self.processors[idx].save_current_preset() {
+                    Ok(()) => {
+                        self.data.instruments[idx].presets = self.processors[idx].get_presets().iter().map(|preset| preset.get_name()).collect();
+                        self.data.instruments[idx].current_preset_id = self.processors[idx].get_current_preset_id();
+                        self.presets_changed.push(idx);
+                    },
+                    Err(error) => self.errors.push(format!("{}: can't save preset: {}", self.processors[idx].get_name(), error)),
+                }
+            }
+            self.data.save_current_preset = false;
+        }
+
+        // A take starts with the transport and ends when it stops, the instrument is disarmed or another one is selected
+        let armed_processor = if self.data.is_armed && self.data.is_playing && self.data.instrument_selected_id < self.processors.len() {
+            Some(self.data.instrument_selected_id)
+        } else {
+            None
+        };
+        if armed_processor != self.armed_processor {
+            if let Some(idx) = self.armed_processor {
+                self.processors[idx].set_armed(false);
+            }
+            if let Some(idx) = armed_processor {
+                self.processors[idx].set_armed(true);
+            }
+            self.armed_processor = armed_processor;
+        }
+        // The takes of the armed track get their memory from the loader before the transport starts
+        let take_processor = if self.data.is_armed && self.data.instrument_selected_id < self.processors.len() {
+            Some(self.data.instrument_selected_id)
+        } else {
+            None
+        };
+        if take_processor != self.take_processor {
+            if let Some(idx) = self.take_processor {
+                self.processors[idx].reserve_take(false);
+            }
+            if let Some(idx) = take_processor {
+                self.processors[idx].reserve_take(true);
+            }
+            self.take_processor = take_processor;
+        }
+
+        if let Some((idx, sliced)) = self.data.bounce_instrument.take() {
+            if idx < self.processors.len() {
+                self.bounce_track(idx, sliced);
+            }
+        }
+        while let Some(result) = self.track_renderer.try_receive() {
+            match result {
+                RenderResult::Frozen { processor_idx, frozen_track } => self.receive_frozen_track(processor_idx, frozen_track),
+                RenderResult::Bounce { processor_idx, sliced, left_channel, right_channel } => self.add_bounce(processor_idx, sliced, left_channel, right_channel),
+            }
+        }
+
+        let tempo_has_changed = self.tempo != self.data.tempo;
+        self.tempo = self.data.tempo;
+
+        for i in 0..self.processors.len() {
+            let mut has_changed = tempo_has_changed || (self.data.undo_last_session && i == self.data.instrument_selected_id);
+            if tempo_has_changed {
+                self.processors[i].set_tempo(self.tempo);
+                self.refresh_parameter_list(i);
+            }
+            if self.processors[i].get_current_preset_id() != self.data.instruments[i].current_preset_id {
+                self.processors[i].set_current_preset_id(self.data.instruments[i].current_preset_id);
+                self.read_parameter_values(i);
+                has_changed = true;
+            } else if self.processors[i].have_parameters_changed() {
+                self.read_parameter_values(i);
+                has_changed = true;
+            }
+            if self.apply_parameter_values(i) {
+                has_changed = true;
+            }
+
+            if self.data.instruments[i].is_frozen && !self.was_frozen[i] {
+                self.processors[i].all_note_off();
+            }
+            self.was_frozen[i] = self.data.instruments[i].is_frozen;
+
+            // The edits of a frozen track are heard once its loop is rendered again, the last render plays meanwhile
+            if !self.data.instruments[i].is_frozen {
+                if let Some(frozen_track) = self.frozen_tracks[i].take() {
+                    self.track_renderer.drop_frozen_track(frozen_track);
+                }
+                self.is_freeze_outdated[i] = true;
+            } else if has_changed {
+                self.is_freeze_outdated[i] = true;
+            }
+            if self.data.instruments[i].is_frozen && self.is_freeze_outdated[i] && !self.is_freeze_rendering[i] {
+                self.track_renderer.freeze(i, self.processors[i].snapshot(), self.render_timing());
+                self.is_freeze_rendering[i] = true;
+                self.is_freeze_outdated[i] = false;
+            }
         }
 
         let bpm_has_bipped = self.data.bpm_has_biped;
@@ -183,14 +501,19 @@ impl Sequencer {
         }
 
         self.metronome.process(outputs, num_samples, nb_channels);
-        
+
+        let loop_position = self.loop_position();
         for p_index in 0..self.processors.len() {
             let processor_outputs = &mut self.processors_outputs[p_index];
             for s in 0..processor_outputs.len() {
                 processor_outputs[s] = 0.;
             }
 
-            self.processors[p_index].process(processor_outputs, num_samples, nb_channels);
+            match &self.frozen_tracks[p_index] {
+                Some(frozen_track) if self.data.is_playing => frozen_track.play(processor_outputs, loop_position, num_samples, nb_channels),
+                Some(_) => {},
+                None => self.processors[p_index].process(processor_outputs, num_samples, nb_channels),
+            }
             let [rms_left, rms_right] = root_mean_square_stereo(processor_outputs, num_samples);
             self.data.instruments[p_index].rms_left = rms_left;
             self.data.instruments[p_index].rms_right = rms_right;
@@ -206,13 +529,20 @@ impl Sequencer {
         self.synchronise_data(bpm_has_bipped, outputs);
     }
 
+    // Called before process with the frames of the audio input
+    pub fn process_input(&mut self, inputs: &[f32], num_samples: usize, nb_channels: usize) {
+        if let Some(idx) = self.armed_processor {
+            self.processors[idx].record_input(inputs, num_samples, nb_channels);
+        }
+    }
+
     pub fn get_tick(&self) -> i32 {
         return self.data.tick;
     }
 
     pub fn note_on(&mut self, note_id: u8, velocity: u8) {
         let idx = self.data.instrument_selected_id;
-        if self.data.instrument_selected_id < self.processors.len() {
+        if self.data.instrument_selected_id < self.processors.len() && !self.data.instruments[idx].is_frozen {
             self.processors[idx].note_on(note_id, velocity as f32 / 127.);
             if self.data.is_recording && self.data.is_playing {
                 let quantize_tick = self.quantize_tick();
@@ -221,6 +551,7 @@ impl Sequencer {
                     tick_on: quantize_tick,
                     tick_off: -1,
                     note_id,
+                    velocity: velocity as f32 / 127.,
                     record_session: self.data.record_session,
                     stamp_record: self.stamp,
                 });
@@ -234,7 +565,7 @@ impl Sequencer {
 
     pub fn note_off(&mut self, note_id: u8) {
         let idx = self.data.instrument_selected_id;
-        if self.data.instrument_selected_id < self.processors.len() {
+        if self.data.instrument_selected_id < self.processors.len() && !self.data.instruments[idx].is_frozen {
             self.processors[idx].note_off(note_id);
                 if self.data.is_recording && self.data.is_playing {
                 
@@ -264,6 +595,54 @@ impl Sequencer {
         }
     }
 
+    pub fn pitch_bend(&mut self, value: f32) {
+        let idx = self.data.instrument_selected_id;
+        if self.data.instrument_selected_id < self.processors.len() && !self.data.instruments[idx].is_frozen {
+            self.processors[idx].pitch_bend(value);
+            self.record_automation_point(AutomationTarget::PitchBend, value);
+        }
+    }
+
+    pub fn control_change(&mut self, controller: u8, value: f32) {
+        let idx = self.data.instrument_selected_id;
+        if self.data.instrument_selected_id < self.processors.len() && !self.data.instruments[idx].is_frozen {
+            self.apply_automation(idx, AutomationTarget::ControlChange(controller), value);
+            self.record_automation_point(AutomationTarget::ControlChange(controller), value);
+        }
+    }
+
+    fn record_automation_point(&mut self, target: AutomationTarget, value: f32) {
+        if !self.data.is_recording || !self.data.is_playing {
+            return;
+        }
+
+        let idx = self.data.instrument_selected_id;
+        let tick = self.data.tick;
+        let record_session = self.data.record_session;
+        let automation_points = self.processors[idx].get_automation_points();
+
+        // Controllers send a lot of messages, keep only the last value for a given tick
+        let existing_point = automation_points.iter_mut().find(|automation_point| {
+            automation_point.target == target && automation_point.tick == tick && automation_point.record_session == record_session
+        });
+
+        if let Some(automation_point) = existing_point {
+            automation_point.value = value;
+            automation_point.stamp_record = self.stamp;
+        } else {
+            automation_points.push(AutomationPoint {
+                target,
+                tick,
+                value,
+                record_session,
+                stamp_record: self.stamp,
+            });
+            automation_points.sort_by(|a, b| a.tick.cmp(&b.tick));
+        }
+
+        self.has_new_automation_points = true;
+    }
+
     pub fn add_processor(&mut self, mut processor: Box<dyn Processor>) {
 
         let nb_samples = self.buffer_size * self.nb_channels;
@@ -274,6 +653,10 @@ impl Sequencer {
         }
 
         processor.prepare(self.sample_rate, self.buffer_size, 2);
+        processor.set_tempo(self.data.tempo);
+
+        let parameters = processor.get_parameters();
+        let parameter_values : Vec<f32> = parameters.iter().map(|parameter| processor.get_parameter(parameter.id)).collect();
         
         self.data.instruments.push(InstrumentData {
             name: processor.get_name(),
@@ -281,12 +664,21 @@ impl Sequencer {
             current_preset_id: processor.get_current_preset_id(),
             presets: processor.get_presets().iter().map(|preset| preset.get_name()).collect(),
             paired_notes: Vec::new(),
+            automation_points: Vec::new(),
+            parameters,
+            parameter_values: parameter_values.clone(),
             rms_left: 0.,
             rms_right: 0.,
+            is_frozen: false,
         });
 
         self.processors.push(processor);
         self.processors_outputs.push(processor_outputs);
+        self.frozen_tracks.push(None);
+        self.is_freeze_rendering.push(false);
+        self.is_freeze_outdated.push(true);
+        self.was_frozen.push(false);
+        self.parameter_values.push(parameter_values);
     }
 
     fn quantize_tick(&self) -> i32 {
@@ -311,21 +703,58 @@ impl Sequencer {
     }
 
     pub fn synchronise_data(&mut self, bpm_has_bipped: bool, _outputs: &mut [f32]) {
+        let mut error_messages: Vec<String> = self.errors.drain(..).collect();
+        for processor in self.processors.iter_mut() {
+            if !processor.get_errors().is_empty() {
+                let name = processor.get_name();
+                for error in processor.get_errors().drain(..) {
+                    error_messages.push(format!("{}: {}", name, error));
+                }
+            }
+        }
+
         for sender in self.audio_state_senders.iter() {
+            if !error_messages.is_empty() {
+                sender.send(SequencerDataMessage::AddErrorMessages(error_messages.clone())).unwrap();
+            }
+
             if self.data.is_playing {
                 sender.send(SequencerDataMessage::SetTick(self.data.tick)).unwrap();
                 if !bpm_has_bipped && self.data.bpm_has_biped {
                     sender.send(SequencerDataMessage::SetBpmHasBiped(self.data.bpm_has_biped)).unwrap();
                 }
             }
-            if self.has_new_notes || self.data.undo_last_session {
-                let idx = self.data.instrument_selected_id;
-                if self.data.instrument_selected_id < self.processors.len() {
-                    let note_events = self.processors[idx].get_notes_events().clone();
-                    sender.send(SequencerDataMessage::SetMidiMessagesInstrument(note_events)).unwrap();
-                }
-                self.has_new_notes = false;
-                self.data.undo_last_session = false;
+            let idx = self.data.instrument_selected_id;
+            if (self.has_new_notes || self.data.undo_last_session) && idx < self.processors.len() {
+                let note_events = self.processors[idx].get_notes_events().clone();
+                sender.send(SequencerDataMessage::SetMidiMessagesInstrument(note_events)).unwrap();
+            }
+            if (self.has_new_automation_points || self.data.undo_last_session) && idx < self.processors.len() {
+                let automation_points = self.processors[idx].get_automation_points().clone();
+                sender.send(SequencerDataMessage::SetAutomationPointsInstrument(automation_points)).unwrap();
+            }
+            self.has_new_notes = false;
+            self.has_new_automation_points = false;
+            self.data.undo_last_session = false;
+
+            for idx in self.presets_changed.iter() {
+                let presets = self.data.instruments[*idx].presets.clone();
+                let current_preset_id = self.data.instruments[*idx].current_preset_id;
+                sender.send(SequencerDataMessage::SetPresetsInstrument(*idx, presets, current_preset_id)).unwrap();
+            }
+
+            for idx in self.parameter_lists_changed.iter() {
+                let parameters = self.data.instruments[*idx].parameters.clone();
+                sender.send(SequencerDataMessage::SetParametersInstrument(*idx, parameters)).unwrap();
+            }
+
+            for idx in self.parameters_changed.iter() {
+                let parameter_values = self.data.instruments[*idx].parameter_values.clone();
+                sender.send(SequencerDataMessage::SetParameterValuesInstrument(*idx, parameter_values)).unwrap();
+            }
+
+            for idx in self.volumes_changed.iter() {
+                sender.send(SequencerDataMessage::SetVolumeInstrument(*idx, self.data.instruments[*idx].volume)).unwrap();
             }
 
             for i in 0..self.data.instruments.len() {
@@ -335,6 +764,10 @@ impl Sequencer {
             }
             sender.send(SequencerDataMessage::SetWaveFormData(_outputs.to_vec())).unwrap();
         }
+        self.parameters_changed.clear();
+        self.volumes_changed.clear();
+        self.parameter_lists_changed.clear();
+        self.presets_changed.clear();
     }
 
 }
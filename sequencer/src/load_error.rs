@@ -0,0 +1,46 @@
+use std::fmt;
+use std::error::Error;
+
+// Errors met while loading samples and presets from the data folder
+#[derive(Debug)]
+pub enum LoadError {
+    Io(String, std::io::Error),
+    Json(String, serde_json::Error),
+    Wav(String, String),
+    InvalidPath(String),
+    Sfz(String, String),
+    Sf2(String, String),
+    UnsupportedOpcodes(String, Vec<String>),
+    Write(String, std::io::Error),
+    SilentRecording,
+    // Duration of the take kept
+    TruncatedRecording(f32),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(path, error) => write!(f, "can't read {}: {}", path, error),
+            LoadError::Json(path, error) => write!(f, "invalid preset {}: {}", path, error),
+            LoadError::Wav(path, message) => write!(f, "invalid wav {}: {}", path, message),
+            LoadError::InvalidPath(path) => write!(f, "invalid path {}", path),
+            LoadError::Sfz(path, message) => write!(f, "invalid sfz {}: {}", path, message),
+            LoadError::Sf2(path, message) => write!(f, "invalid sf2 {}: {}", path, message),
+            LoadError::UnsupportedOpcodes(path, opcodes) => write!(f, "unsupported opcodes in {}: {}", path, opcodes.join(", ")),
+            LoadError::Write(path, error) => write!(f, "can't write {}: {}", path, error),
+            LoadError::SilentRecording => write!(f, "nothing recorded, the input stayed silent"),
+            LoadError::TruncatedRecording(duration) => write!(f, "the recording was cut after {} s", duration),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(_, error) => Some(error),
+            LoadError::Write(_, error) => Some(error),
+            LoadError::Json(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
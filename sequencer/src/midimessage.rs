@@ -1,5 +1,13 @@
 pub const NOTE_ON : u8 = 0x9c;
 pub const NOTE_OFF : u8 = 0x8c; 
+pub const CONTROL_CHANGE : u8 = 0xbc;
+pub const PITCH_BEND : u8 = 0xec;
+
+pub const CC_MODULATION_WHEEL : u8 = 1;
+pub const CC_VOLUME : u8 = 7;
+pub const CC_SUSTAIN_PEDAL : u8 = 64;
+pub const CC_FILTER_CUTOFF : u8 = 74;
+pub const CC_REVERB : u8 = 91;
 
 #[derive(Copy, Clone)]
 pub struct MidiMessage {
@@ -11,8 +19,93 @@ pub struct MidiMessage {
 #[derive(Copy, Clone)]
 pub struct NoteEvent {
     pub note_id: u8,
+    // In [0, 1] like the velocity given to Processor::note_on
+    pub velocity: f32,
     pub tick_on: i32,
     pub tick_off: i32,
     pub record_session: i32,
     pub stamp_record: i32,
-}
\ No newline at end of file
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum AutomationTarget {
+    PitchBend,
+    ControlChange(u8),
+    // Parameter id of the instrument, see Processor::get_parameters
+    Parameter(usize),
+}
+
+impl AutomationTarget {
+    // Continuous targets are interpolated between two points, the others only change on a point
+    pub fn is_continuous(&self) -> bool {
+        *self != AutomationTarget::ControlChange(CC_SUSTAIN_PEDAL)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct AutomationPoint {
+    pub target: AutomationTarget,
+    pub tick: i32,
+    pub value: f32,
+    pub record_session: i32,
+    pub stamp_record: i32,
+}
+
+// Pitch bend is sent as a 14 bits value centered on 0x2000, returns it in [-1, 1]
+pub fn pitch_bend_value(lsb: u8, msb: u8) -> f32 {
+    let value = ((msb as i32) << 7) | lsb as i32;
+    (value - 0x2000) as f32 / 0x2000 as f32
+}
+
+// Values the automation of a track takes at a tick, the points on the tick and the continuous targets ramping to their next point
+// The buffers are reserved once so playing the automation in the audio thread doesn't allocate
+pub struct AutomationPlayer {
+    // Last point before the current tick for each target, and if the target already got its value
+    previous_points: Vec<(AutomationPoint, bool)>,
+    pub values: Vec<(AutomationTarget, f32)>,
+}
+
+impl AutomationPlayer {
+    pub fn new() -> AutomationPlayer {
+        AutomationPlayer {
+            previous_points: Vec::with_capacity(16),
+            values: Vec::with_capacity(16),
+        }
+    }
+
+    // The points sorted by tick, the ones is_playable refuses are skipped
+    pub fn play<F: Fn(&AutomationPoint) -> bool>(&mut self, automation_points: &[AutomationPoint], tick: i32, is_playable: F) {
+        self.previous_points.clear();
+        self.values.clear();
+
+        for automation_point in automation_points.iter() {
+            if !is_playable(automation_point) {
+                continue;
+            }
+
+            let previous_idx = self.previous_points.iter()
+                .position(|(previous_point, _)| previous_point.target == automation_point.target);
+
+            if automation_point.tick == tick {
+                self.values.push((automation_point.target, automation_point.value));
+                match previous_idx {
+                    Some(idx) => self.previous_points[idx] = (*automation_point, true),
+                    None => self.previous_points.push((*automation_point, true)),
+                }
+            } else if automation_point.tick < tick {
+                match previous_idx {
+                    Some(idx) => self.previous_points[idx] = (*automation_point, false),
+                    None => self.previous_points.push((*automation_point, false)),
+                }
+            } else if let Some(idx) = previous_idx {
+                let (previous_point, done) = self.previous_points[idx];
+                if !done && automation_point.target.is_continuous() && previous_point.value != automation_point.value {
+                    let alpha = (tick - previous_point.tick) as f32 / (automation_point.tick - previous_point.tick) as f32;
+                    let value = previous_point.value + alpha * (automation_point.value - previous_point.value);
+                    self.values.push((automation_point.target, value));
+                }
+                self.previous_points[idx].1 = true;
+            }
+        }
+    }
+}
@@ -3,5 +3,4 @@ pub mod mood_wave;
 pub mod mood_wave_bank;
 pub mod mood_voice;
 pub mod mood_preset;
-pub mod mood_oscillator;
-pub mod mood_preset_bank;
\ No newline at end of file
+pub mod mood_oscillator;
\ No newline at end of file
@@ -1,33 +1,123 @@
 const MAX_NOTES : usize = 8;
+// The factory presets come first, their file names start with their position, then the user presets
+const PRESETS_DIRECTORY : &str = "mood-presets";
+
+// Each oscillator exposes the same block of parameters, the oscillator parameter id is
+// the oscillator index * NB_OSCILLATOR_PARAMETERS + the parameter index in the block
+const OSC_WAVE_FORM : usize = 0;
+const OSC_OCTAVE_RANGE : usize = 1;
+const OSC_SEMITONE_SHIFT : usize = 2;
+const OSC_VOLUME : usize = 3;
+const OSC_PHASE_OFFSET : usize = 4;
+const OSC_ATTACK : usize = 5;
+const OSC_DECAY : usize = 6;
+const OSC_SUSTAIN : usize = 7;
+const OSC_RELEASE : usize = 8;
+const NB_OSCILLATOR_PARAMETERS : usize = 9;
+
+const GLIDE : usize = NB_OSCILLATORS * NB_OSCILLATOR_PARAMETERS;
+const FILTER_TYPE : usize = GLIDE + 1;
+const FILTER_CUTOFF : usize = GLIDE + 2;
+const FILTER_Q : usize = GLIDE + 3;
+const MONO : usize = GLIDE + 4;
+const PITCH_BEND_RANGE : usize = GLIDE + 5;
+
+const OCTAVE_RANGES : [f32; 6] = [32., 16., 8., 4., 2., 1.];
+const OCTAVE_RANGE_NAMES : [&str; 6] = ["32'", "16'", "8'", "4'", "2'", "1'"];
 
 use crate::mood::mood_voice::MoodVoice;
 use crate::mood::mood_wave_bank::MoodWaveBank;
-use crate::mood::mood_preset::MoodPreset;
-use crate::mood::mood_preset_bank::get_mood_presets;
+use crate::mood::mood_preset::{MoodPreset, NB_OSCILLATORS};
+use crate::mood::mood_wave::WAVE_NAMES;
 
-use crate::processor::Processor;
-use crate::midimessage::NoteEvent;
-use crate::preset::Preset;
-use crate::fx::reverb::Reverb;
+use crate::processor::{Processor, ProcessorSnapshot};
+use crate::midimessage::{NoteEvent, AutomationPoint, CC_FILTER_CUTOFF, CC_REVERB, CC_SUSTAIN_PEDAL};
+use crate::sustain_pedal::SustainPedal;
+use crate::utils::pitch_bend_ratio;
+use crate::preset::{Preset, load_user_presets, PresetWriter, new_preset_name, MIN_FILTER_Q, MAX_FILTER_Q};
+use crate::load_error::LoadError;
+use crate::parameter::{Parameter, ParameterUnit, FILTER_TYPE_NAMES, filter_type_from_index, filter_type_index};
+use crate::fx::reverb::{Reverb, ReverbParameters};
 
-use std::rc::Rc;
+use biquad::ToHertz;
+
+use std::sync::Arc;
+use std::error::Error;
+use std::path::Path;
 
 pub struct Mood {
-    pub wave_bank: Rc<MoodWaveBank>,
+    pub wave_bank: Arc<MoodWaveBank>,
     pub note_events: Vec<NoteEvent>,
+    pub automation_points: Vec<AutomationPoint>,
     voices: Vec<MoodVoice>,
     nb_actives_notes: usize,
     presets: Vec<MoodPreset>,
     preset_id: usize,
-    reverb: Reverb
+    reverb: Reverb,
+    pitch_bend: f32,
+    filter_cutoff: f32,
+    sustain_pedal: SustainPedal,
+    preset_writer: PresetWriter<MoodPreset>,
+    errors: Vec<LoadError>,
+}
+
+pub struct MoodSnapshot {
+    sample_rate: f32,
+    wave_bank: Arc<MoodWaveBank>,
+    note_events: Vec<NoteEvent>,
+    automation_points: Vec<AutomationPoint>,
+    preset: MoodPreset,
+    reverb_parameters: ReverbParameters,
+    pitch_bend: f32,
+    filter_cutoff: f32,
+}
+
+impl ProcessorSnapshot for MoodSnapshot {
+    fn into_processor(self: Box<Self>) -> Box<dyn Processor> {
+        let mut voices : Vec<MoodVoice> = Vec::new();
+        for _i in 0..MAX_NOTES {
+            voices.push(MoodVoice::new(self.sample_rate, self.wave_bank.clone()))
+        }
+
+        let mut reverb = Reverb::new(self.sample_rate);
+        reverb.set_parameters(self.reverb_parameters);
+
+        Box::new(Mood {
+            wave_bank: self.wave_bank,
+            note_events: self.note_events,
+            automation_points: self.automation_points,
+            voices,
+            nb_actives_notes: 0,
+            presets: vec![self.preset],
+            preset_id: 0,
+            reverb,
+            pitch_bend: self.pitch_bend,
+            filter_cutoff: self.filter_cutoff,
+            sustain_pedal: SustainPedal::new(),
+            preset_writer: PresetWriter::detached(),
+            errors: Vec::new(),
+        })
+    }
 }
 
 impl Mood {
-    pub fn new(sample_rate: f32, id: usize) -> Mood {
+    pub fn new(sample_rate: f32, id: usize, data_path: &str) -> Mood {
 
         let mut voices : Vec<MoodVoice> = Vec::new();
 
-        let wave_bank = Rc::new(MoodWaveBank::new(sample_rate));
+        let wave_bank = Arc::new(MoodWaveBank::new(sample_rate));
+
+        let presets_path = Path::new(data_path).join(PRESETS_DIRECTORY).to_string_lossy().to_string();
+
+        let mut errors = Vec::new();
+        let mut presets = load_user_presets::<MoodPreset>(&presets_path, &mut errors);
+        if presets.is_empty() {
+            presets.push(MoodPreset::empty());
+        }
+        for (id, preset) in presets.iter_mut().enumerate() {
+            preset.id = id;
+        }
+        let preset_id = id.min(presets.len() - 1);
 
         for _i in 0..MAX_NOTES {
             voices.push(MoodVoice::new(sample_rate, wave_bank.clone()))
@@ -36,11 +126,42 @@ impl Mood {
         Mood {
             wave_bank,
             note_events: Vec::new(),
+            automation_points: Vec::new(),
             voices: voices,
             nb_actives_notes: 0,
-            presets: get_mood_presets(),
-            preset_id: id,
+            presets,
+            preset_id,
             reverb: Reverb::new(sample_rate),
+            pitch_bend: 0.,
+            filter_cutoff: 0.5,
+            sustain_pedal: SustainPedal::new(),
+            preset_writer: PresetWriter::new(&presets_path),
+            errors,
+        }
+    }
+
+    fn pitch_bend_ratio(&self) -> f32 {
+        pitch_bend_ratio(self.pitch_bend, self.presets[self.preset_id].pitch_bend_range)
+    }
+
+    // The cutoff controller moves the preset cutoff up to 4 octaves up or down
+    fn filter_cutoff_ratio(&self) -> f32 {
+        f32::powf(2., (self.filter_cutoff - 0.5) * 8.)
+    }
+
+    fn start_voice(&mut self, voice_idx: usize, midi_note: u8, velocity: f32) {
+        let bend_ratio = self.pitch_bend_ratio();
+        let filter_cutoff_ratio = self.filter_cutoff_ratio();
+        self.voices[voice_idx].filter_cutoff_ratio = filter_cutoff_ratio;
+        self.voices[voice_idx].start_note(midi_note, velocity, self.presets[self.preset_id].clone());
+        self.voices[voice_idx].set_pitch_bend_ratio(bend_ratio);
+    }
+
+    fn release_note(&mut self, midi_note: u8) {
+        for i in 0..self.voices.len() {
+            if self.voices[i].note_id == midi_note && self.voices[i].active {
+                self.voices[i].stop_note();
+            }
         }
     }
 }
@@ -50,35 +171,63 @@ impl Processor for Mood {
     fn get_name(&self) -> String { "Mood".to_string() }
 
     fn note_on(&mut self, midi_note: u8, velocity: f32) {
+        self.sustain_pedal.note_on(midi_note);
         if self.presets[self.preset_id].is_mono {
             if self.voices[0].active {
                 self.voices[0].set_target_note(midi_note);
             } else {
-                self.voices[self.nb_actives_notes].start_note(midi_note, velocity, self.presets[self.preset_id].clone());
+                self.start_voice(self.nb_actives_notes, midi_note, velocity);
                 self.nb_actives_notes += 1;
             }
         } else {
             if self.nb_actives_notes < MAX_NOTES - 1 {
-                self.voices[self.nb_actives_notes].start_note(midi_note, velocity, self.presets[self.preset_id].clone());
+                self.start_voice(self.nb_actives_notes, midi_note, velocity);
                 self.nb_actives_notes += 1;
             }
         }
     }
    
     fn note_off(&mut self, midi_note: u8) {
-        for i in 0..self.voices.len() {
-            if self.voices[i].note_id == midi_note && self.voices[i].active {
-                self.voices[i].stop_note();
-            }
+        if !self.sustain_pedal.hold_note_off(midi_note) {
+            self.release_note(midi_note);
         }
     }
 
     fn all_note_off(&mut self) {
+        self.sustain_pedal.reset();
         for i in 0..self.voices.len() {
             self.voices[i].stop_note();
         }
     }
 
+    fn pitch_bend(&mut self, value: f32) {
+        self.pitch_bend = value;
+        let bend_ratio = self.pitch_bend_ratio();
+        for i in 0..self.nb_actives_notes {
+            self.voices[i].set_pitch_bend_ratio(bend_ratio);
+        }
+    }
+
+    fn control_change(&mut self, controller: u8, value: f32) {
+        if controller == CC_FILTER_CUTOFF {
+            self.filter_cutoff = value;
+            let filter_cutoff_ratio = self.filter_cutoff_ratio();
+            for i in 0..self.nb_actives_notes {
+                self.voices[i].set_filter_cutoff_ratio(filter_cutoff_ratio);
+            }
+        } else if controller == CC_REVERB {
+            // Only the live reverb follows the controller, the preset keeps its own wet level
+            let mut reverb_params = self.presets[self.preset_id].reverb_params.clone();
+            reverb_params.wet_level = value;
+            self.reverb.set_parameters(reverb_params);
+        } else if controller == CC_SUSTAIN_PEDAL {
+            self.sustain_pedal.set_value(value);
+            while let Some(midi_note) = self.sustain_pedal.next_released_note() {
+                self.release_note(midi_note);
+            }
+        }
+    }
+
     fn process(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize) {
 
         for s in outputs.iter_mut() {
@@ -112,6 +261,19 @@ impl Processor for Mood {
         self.note_events.push(midi_message);
     }
 
+    fn get_errors(&mut self) -> &mut Vec<LoadError> {
+        self.preset_writer.receive_errors(&mut self.errors);
+        return &mut self.errors;
+    }
+
+    fn get_automation_points(&mut self) -> &mut Vec<AutomationPoint> {
+        return &mut self.automation_points;
+    }
+
+    fn add_automation_point(&mut self, automation_point: AutomationPoint) {
+        self.automation_points.push(automation_point);
+    }
+
     fn get_current_preset_id(&self) -> usize {
         self.preset_id
     }
@@ -132,5 +294,148 @@ impl Processor for Mood {
         return presets;
     }
 
+    fn get_parameters(&self) -> Vec<Parameter> {
+        let default = MoodPreset::empty();
+        let mut parameters = Vec::new();
+
+        for osc in 0..NB_OSCILLATORS {
+            let id = osc * NB_OSCILLATOR_PARAMETERS;
+            let name = |parameter_name: &str| format!("Osc {} {}", osc + 1, parameter_name);
+            let octave_range = OCTAVE_RANGES.iter().position(|range| *range == default.oscx_octave_range[osc]).unwrap_or(2);
+
+            parameters.push(Parameter::choice(id + OSC_WAVE_FORM, &name("wave"), &WAVE_NAMES, default.oscx_wave_form[osc] as usize));
+            parameters.push(Parameter::choice(id + OSC_OCTAVE_RANGE, &name("range"), &OCTAVE_RANGE_NAMES, octave_range));
+            parameters.push(Parameter::new(id + OSC_SEMITONE_SHIFT, &name("shift"), -12., 12., default.oscx_semitone_shift[osc], 0.01, ParameterUnit::Semitones));
+            parameters.push(Parameter::new(id + OSC_VOLUME, &name("volume"), 0., 1., default.oscx_volume[osc], 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + OSC_PHASE_OFFSET, &name("phase"), 0., 1., default.oscx_phase_offset[osc], 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + OSC_ATTACK, &name("attack"), 0., 10., default.oscx_adsr_attack[osc], 0.001, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + OSC_DECAY, &name("decay"), 0., 60., default.oscx_adsr_decay[osc], 0.01, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + OSC_SUSTAIN, &name("sustain"), 0., 1., default.oscx_adsr_sustain[osc], 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + OSC_RELEASE, &name("release"), 0., 10., default.oscx_adsr_release[osc], 0.001, ParameterUnit::Seconds));
+        }
+
+        parameters.push(Parameter::new(GLIDE, "Glide", 0., 1., default.glide, 0.001, ParameterUnit::Seconds));
+        parameters.push(Parameter::choice(FILTER_TYPE, "Filter type", &FILTER_TYPE_NAMES, filter_type_index(default.filter_type)));
+        parameters.push(Parameter::new(FILTER_CUTOFF, "Filter cutoff", 20., 20000., default.filter_f0.hz(), 10., ParameterUnit::Hertz));
+        parameters.push(Parameter::new(FILTER_Q, "Filter Q", MIN_FILTER_Q, MAX_FILTER_Q, default.filter_q_value, 0.01, ParameterUnit::None));
+        parameters.push(Parameter::choice(MONO, "Mono", &["Off", "On"], default.is_mono as usize));
+        parameters.push(Parameter::new(PITCH_BEND_RANGE, "Bend range", 0., 24., default.pitch_bend_range, 1., ParameterUnit::Semitones));
+
+        return parameters;
+    }
+
+    fn get_parameter(&self, id: usize) -> f32 {
+        let preset = &self.presets[self.preset_id];
+
+        if id < GLIDE {
+            let osc = id / NB_OSCILLATOR_PARAMETERS;
+            return match id % NB_OSCILLATOR_PARAMETERS {
+                OSC_WAVE_FORM => preset.oscx_wave_form[osc] as f32,
+                OSC_OCTAVE_RANGE => OCTAVE_RANGES.iter().position(|range| *range == preset.oscx_octave_range[osc]).unwrap_or(2) as f32,
+                OSC_SEMITONE_SHIFT => preset.oscx_semitone_shift[osc],
+                OSC_VOLUME => preset.oscx_volume[osc],
+                OSC_PHASE_OFFSET => preset.oscx_phase_offset[osc],
+                OSC_ATTACK => preset.oscx_adsr_attack[osc],
+                OSC_DECAY => preset.oscx_adsr_decay[osc],
+                OSC_SUSTAIN => preset.oscx_adsr_sustain[osc],
+                _ => preset.oscx_adsr_release[osc],
+            };
+        }
+
+        match id {
+            GLIDE => preset.glide,
+            FILTER_TYPE => filter_type_index(preset.filter_type) as f32,
+            FILTER_CUTOFF => preset.filter_f0.hz(),
+            FILTER_Q => preset.filter_q_value,
+            MONO => preset.is_mono as u8 as f32,
+            PITCH_BEND_RANGE => preset.pitch_bend_range,
+            _ => 0.,
+        }
+    }
+
+    // Oscillator settings are applied on the next note, filter settings are applied to the playing voices
+    fn set_parameter(&mut self, id: usize, value: f32) {
+        let preset = &mut self.presets[self.preset_id];
+
+        if id < GLIDE {
+            let osc = id / NB_OSCILLATOR_PARAMETERS;
+            match id % NB_OSCILLATOR_PARAMETERS {
+                OSC_WAVE_FORM => preset.oscx_wave_form[osc] = (value.round() as usize).min(WAVE_NAMES.len() - 1) as u8,
+                OSC_OCTAVE_RANGE => preset.oscx_octave_range[osc] = OCTAVE_RANGES[(value.round() as usize).min(OCTAVE_RANGES.len() - 1)],
+                OSC_SEMITONE_SHIFT => preset.oscx_semitone_shift[osc] = value,
+                OSC_VOLUME => preset.oscx_volume[osc] = value,
+                OSC_PHASE_OFFSET => preset.oscx_phase_offset[osc] = value,
+                OSC_ATTACK => preset.oscx_adsr_attack[osc] = value,
+                OSC_DECAY => preset.oscx_adsr_decay[osc] = value,
+                OSC_SUSTAIN => preset.oscx_adsr_sustain[osc] = value,
+                _ => preset.oscx_adsr_release[osc] = value,
+            }
+            return;
+        }
+
+        match id {
+            GLIDE => preset.glide = value,
+            FILTER_TYPE => preset.filter_type = filter_type_from_index(value.round() as usize),
+            FILTER_CUTOFF => preset.filter_f0 = value.hz(),
+            FILTER_Q => preset.filter_q_value = value,
+            MONO => preset.is_mono = value >= 0.5,
+            PITCH_BEND_RANGE => preset.pitch_bend_range = value,
+            _ => {}
+        }
+
+        let (filter_type, filter_f0, filter_q_value) = (preset.filter_type, preset.filter_f0, preset.filter_q_value);
+        for i in 0..self.nb_actives_notes {
+            self.voices[i].set_filter(filter_type, filter_f0, filter_q_value);
+        }
+    }
+
+    fn save_current_preset(&mut self) -> Result<(), Box<dyn Error>> {
+        let names = self.presets.iter().map(|preset| preset.name.clone()).collect();
+        let mut preset = self.presets[self.preset_id].clone();
+        preset.name = new_preset_name(&preset.name, &names);
+        preset.id = self.presets.len();
+
+        self.preset_writer.save(preset.clone());
+
+        self.presets.push(preset);
+        self.set_current_preset_id(self.presets.len() - 1);
+        Ok(())
+    }
+
+    fn have_parameters_changed(&mut self) -> bool {
+        return false;
+    }
+
+    fn add_audio(&mut self, _name: &str, _left_channel: &mut Vec<f32>, _right_channel: &mut Vec<f32>, _sliced: bool) -> Result<(), Box<dyn Error>> {
+        Err("The Mood doesn't play audio".into())
+    }
+
+    fn snapshot(&self) -> Box<dyn ProcessorSnapshot> {
+        Box::new(MoodSnapshot {
+            sample_rate: self.voices[0].sample_rate,
+            wave_bank: self.wave_bank.clone(),
+            note_events: self.note_events.clone(),
+            automation_points: self.automation_points.clone(),
+            preset: self.presets[self.preset_id].clone(),
+            reverb_parameters: self.reverb.get_parameters(),
+            pitch_bend: self.pitch_bend,
+            filter_cutoff: self.filter_cutoff,
+        })
+    }
+
+    fn set_armed(&mut self, _armed: bool) {
+
+    }
+
+    fn reserve_take(&mut self, _reserved: bool) {
+
+    }
+
+    fn record_input(&mut self, _inputs: &[f32], _num_samples: usize, _nb_channels: usize) {
+
+    }
+
+    fn set_tempo(&mut self, _bpm: f32) {}
+
     fn prepare(&mut self, _sample_rate: f32, _num_samples: usize, _nb_channels: usize) {}
 }
\ No newline at end of file
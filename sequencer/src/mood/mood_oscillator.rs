@@ -1,11 +1,11 @@
 use crate::adsr::ADSR;
 use crate::mood::mood_wave::MoodWave;
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct MoodOscillator {
-    pub mood_wave: Rc<MoodWave>,
+    pub mood_wave: Arc<MoodWave>,
     pub pitch_ratio: f32,
     pub pitch_ratio_target: f32,
     pub position: f32,
@@ -14,6 +14,7 @@ pub struct MoodOscillator {
     pub glide: f32,
     pub glide_end: f32,
     pub glide_idx: f32,
+    pub bend_ratio: f32,
     pub adsr: ADSR
 }
 
@@ -21,7 +22,7 @@ impl MoodOscillator {
     pub fn new(sample_rate: f32) -> MoodOscillator {
         MoodOscillator {
             sample_rate: sample_rate,
-            mood_wave: Rc::new(MoodWave::empty()),
+            mood_wave: Arc::new(MoodWave::empty()),
             pitch_ratio: 0.,
             pitch_ratio_target: 0.,
             position: 0.,
@@ -29,11 +30,12 @@ impl MoodOscillator {
             glide: 0.,
             glide_end: 0.,
             glide_idx: 0.,
+            bend_ratio: 1.,
             adsr: ADSR::new(0., 0., 1.0, 0., sample_rate)
         }
     }
 
-    pub fn init(&mut self, frequency: f32, volume: f32, mood_wave: Rc<MoodWave>, adsr: ADSR, glide: f32, phase_offset: f32) {
+    pub fn init(&mut self, frequency: f32, volume: f32, mood_wave: Arc<MoodWave>, adsr: ADSR, glide: f32, phase_offset: f32) {
         self.mood_wave = mood_wave;
         self.position = 0.;
         self.volume = volume;
@@ -83,7 +85,7 @@ impl MoodOscillator {
         let s2 = self.mood_wave.mood_wave_samples[interpol_pos];
         let s = s1 * inv_alpha + s2 * alpha;
 
-        self.position += pitch_ratio;
+        self.position += pitch_ratio * self.bend_ratio;
 
         return s * self.volume * self.adsr.tick();
     }
@@ -4,13 +4,15 @@ use biquad::*;
 use biquad::Type;
 use crate::fx::reverb::ReverbParameters;
 
-const NB_OSCILLATORS : usize = 3;
+use serde::{Deserialize, Serialize};
+
+pub const NB_OSCILLATORS : usize = 3;
 
 use crate::mood::mood_wave::{
     WAVE_NONE,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MoodPreset {
     pub id: usize,
     pub name: String,
@@ -24,8 +26,12 @@ pub struct MoodPreset {
     pub oscx_adsr_release: [f32; NB_OSCILLATORS],
     pub oscx_adsr_sustain: [f32; NB_OSCILLATORS],
     pub glide: f32,
+    pub pitch_bend_range: f32,
+    #[serde(with = "crate::preset::filter_type_serde")]
     pub filter_type: biquad::Type<f32>,
+    #[serde(with = "crate::preset::hertz_serde")]
     pub filter_f0: biquad::Hertz<f32>,
+    #[serde(deserialize_with = "crate::preset::filter_q_serde::deserialize")]
     pub filter_q_value: f32,
     pub is_mono: bool,
 
@@ -49,6 +55,7 @@ impl MoodPreset {
             oscx_adsr_sustain: [0., 0., 0.],
             
             glide: 0.,
+            pitch_bend_range: 2.,
             filter_type: Type::LowPass,
             filter_f0: 10.khz() as biquad::Hertz<f32>,
             filter_q_value: biquad::Q_BUTTERWORTH_F32,
@@ -5,7 +5,7 @@ use crate::mood::mood_preset::MoodPreset;
 use crate::mood::mood_oscillator::MoodOscillator;
 use crate::utils::midi_note_fo_hertz;
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 const NB_OSCILLATORS : usize = 3;
 
@@ -19,7 +19,7 @@ use biquad::DirectForm1;
 
 #[derive(Clone)]
 pub struct MoodVoice {
-    pub wave_bank: Rc<MoodWaveBank>,
+    pub wave_bank: Arc<MoodWaveBank>,
     pub active: bool,
     pub note_id: u8,
     pub sample_rate: f32,
@@ -28,12 +28,13 @@ pub struct MoodVoice {
     pub filter_type: biquad::Type<f32>,
     pub filter_f0: biquad::Hertz<f32>,
     pub filter_q_value: f32,
+    pub filter_cutoff_ratio: f32,
     pub mood_preset: MoodPreset,
 }
 
 impl MoodVoice {
 
-    pub fn new(sample_rate: f32, wave_bank: Rc<MoodWaveBank>) ->  MoodVoice {
+    pub fn new(sample_rate: f32, wave_bank: Arc<MoodWaveBank>) ->  MoodVoice {
 
         let f0 = 880.hz();
         let fs = 30.khz();
@@ -55,6 +56,7 @@ impl MoodVoice {
             filter_type: Type::LowPass,
             filter_f0: f0,
             filter_q_value: biquad::Q_BUTTERWORTH_F32,
+            filter_cutoff_ratio: 1.,
             mood_preset: MoodPreset::empty()
         }
     }
@@ -97,11 +99,40 @@ impl MoodVoice {
         self.filter_f0 = self.mood_preset.filter_f0;
         self.filter_q_value = self.mood_preset.filter_q_value;
 
-        let coeffs = Coefficients::<f32>::from_params(self.filter_type, self.sample_rate.hz(), self.filter_f0, self.filter_q_value).unwrap();
-        self.biquad_filter = DirectForm1::<f32>::new(coeffs);
+        self.update_filter();
         self.biquad_filter.reset_state();
     }
 
+    // The filter keeps its coefficients when the new ones can't be computed
+    fn update_filter(&mut self) {
+        let cutoff = (self.filter_f0.hz() * self.filter_cutoff_ratio).clamp(20., self.sample_rate * 0.45);
+        if let Ok(coefficients) = Coefficients::<f32>::from_params(self.filter_type, self.sample_rate.hz(), cutoff.hz(), self.filter_q_value) {
+            self.biquad_filter.update_coefficients(coefficients);
+        }
+    }
+
+    pub fn set_filter_cutoff_ratio(&mut self, filter_cutoff_ratio: f32) {
+        self.filter_cutoff_ratio = filter_cutoff_ratio;
+        if self.active {
+            self.update_filter();
+        }
+    }
+
+    pub fn set_filter(&mut self, filter_type: biquad::Type<f32>, filter_f0: biquad::Hertz<f32>, filter_q_value: f32) {
+        self.filter_type = filter_type;
+        self.filter_f0 = filter_f0;
+        self.filter_q_value = filter_q_value;
+        if self.active {
+            self.update_filter();
+        }
+    }
+
+    pub fn set_pitch_bend_ratio(&mut self, bend_ratio: f32) {
+        for i in 0..NB_OSCILLATORS {
+            self.oscx[i].bend_ratio = bend_ratio;
+        }
+    }
+
     pub fn set_target_note(&mut self, target_note: u8) {
 
         self.note_id = target_note;
@@ -7,6 +7,8 @@ pub const WAVE_SQUARE_DIGITAL: u8 = 5;
 pub const WAVE_SQUARE_ANALOGIC_256: u8 = 6;
 pub const WAVE_NONE: u8 = 7;
 
+pub const WAVE_NAMES: [&str; 8] = ["Sine", "Triangle", "Saw", "Saw 4", "Saw 256", "Square", "Square 256", "None"];
+
 #[derive(Clone)]
 pub struct MoodWave {
     pub mood_wave_samples: Vec<f32>,
@@ -11,10 +11,10 @@ use crate::mood::mood_wave::{
     WAVE_NONE,
 };
 
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct MoodWaveBank {
-    pub waves: Vec<Rc<MoodWave>>,
+    pub waves: Vec<Arc<MoodWave>>,
     pub footprint: usize
 }
 
@@ -33,7 +33,7 @@ impl MoodWaveBank {
 
         let mut footprint = 0;
 
-        let mut waves : Vec<Rc<MoodWave>> = Vec::new();
+        let mut waves : Vec<Arc<MoodWave>> = Vec::new();
 
         let twelve_root_of_two = f32::powf(2., 1./12.);
 
@@ -50,10 +50,10 @@ impl MoodWaveBank {
                 let mood_wave = MoodWave::new(start_frequency, root_frequency, end_frequency, sample_rate, wave_form_type.clone());
                 footprint += mood_wave.mood_wave_samples.len();
                 root_frequency *= 2.;
-                waves.push(Rc::new(mood_wave));
+                waves.push(Arc::new(mood_wave));
             }
 
-            waves.push(Rc::new(MoodWave::new(1., 1., last_frequency, sample_rate, WAVE_NONE)))
+            waves.push(Arc::new(MoodWave::new(1., 1., last_frequency, sample_rate, WAVE_NONE)))
         }        
         MoodWaveBank {
             waves,
@@ -61,7 +61,7 @@ impl MoodWaveBank {
         }
     }
 
-    pub fn find_mood_wave(&self, frequency: f32, mood_wave_form: u8) -> Result<Rc<MoodWave>, String> {
+    pub fn find_mood_wave(&self, frequency: f32, mood_wave_form: u8) -> Result<Arc<MoodWave>, String> {
         for wave in self.waves.iter() {
             if wave.from_frequency <= frequency && frequency < wave.to_frequency && wave.wave_form == mood_wave_form {
                 return Ok(wave.clone());
@@ -0,0 +1,104 @@
+use biquad::Type;
+
+#[derive(Clone, PartialEq)]
+pub enum ParameterUnit {
+    None,
+    Percent,
+    Seconds,
+    Hertz,
+    Decibels,
+    Semitones,
+    Cents,
+    // Start of a slice in seconds, shown on the slices page
+    SlicePoint,
+    // Discrete parameter, the value is the index of the choice
+    Choice(Vec<String>),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Parameter {
+    pub id: usize,
+    pub name: String,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub step: f32,
+    pub unit: ParameterUnit,
+}
+
+impl Parameter {
+    pub fn new(id: usize, name: &str, min: f32, max: f32, default: f32, step: f32, unit: ParameterUnit) -> Parameter {
+        Parameter {
+            id,
+            name: name.to_string(),
+            min,
+            max,
+            default,
+            step,
+            unit,
+        }
+    }
+
+    pub fn choice(id: usize, name: &str, choices: &[&str], default: usize) -> Parameter {
+        Parameter {
+            id,
+            name: name.to_string(),
+            min: 0.,
+            max: (choices.len() - 1) as f32,
+            default: default as f32,
+            step: 1.,
+            unit: ParameterUnit::Choice(choices.iter().map(|choice| choice.to_string()).collect()),
+        }
+    }
+
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        if let ParameterUnit::Choice(_) = self.unit {
+            return value.round();
+        }
+        value
+    }
+
+    // Frequencies move by semitones so the whole audible range stays reachable step by step
+    pub fn next_value(&self, value: f32, direction: f32) -> f32 {
+        if self.unit == ParameterUnit::Hertz {
+            return self.clamp(value * f32::powf(2., direction / 12.));
+        }
+        self.clamp(value + direction * self.step)
+    }
+
+    pub fn format_value(&self, value: f32) -> String {
+        match &self.unit {
+            ParameterUnit::None => format!("{:.2}", value),
+            ParameterUnit::Percent => format!("{:.0}%", value * 100.),
+            ParameterUnit::Seconds | ParameterUnit::SlicePoint => format!("{:.3}s", value),
+            ParameterUnit::Hertz => format!("{:.0}Hz", value),
+            ParameterUnit::Decibels => format!("{:.1}dB", value),
+            ParameterUnit::Semitones => format!("{:.2}st", value),
+            ParameterUnit::Cents => format!("{:.0}ct", value),
+            ParameterUnit::Choice(choices) => choices[(value.round() as usize).min(choices.len() - 1)].clone(),
+        }
+    }
+}
+
+pub const FILTER_TYPE_NAMES: [&str; 5] = ["Low pass", "High pass", "Band pass", "Notch", "All pass"];
+
+pub fn filter_type_from_index(index: usize) -> Type<f32> {
+    match index {
+        1 => Type::HighPass,
+        2 => Type::BandPass,
+        3 => Type::Notch,
+        4 => Type::AllPass,
+        _ => Type::LowPass,
+    }
+}
+
+pub fn filter_type_index(filter_type: Type<f32>) -> usize {
+    match filter_type {
+        Type::HighPass => 1,
+        Type::BandPass => 2,
+        Type::Notch => 3,
+        Type::AllPass => 4,
+        _ => 0,
+    }
+}
@@ -1,4 +1,223 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::sync::{Arc, OnceLock};
+use std::marker::PhantomData;
+use std::thread;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::load_error::LoadError;
+
+// Range of the filter Q of the presets and of their parameters
+pub const MIN_FILTER_Q : f32 = 0.1;
+pub const MAX_FILTER_Q : f32 = 10.;
+
 pub trait Preset {
     fn get_id(self) -> usize;
     fn get_name(&self) -> String;
-}
\ No newline at end of file
+}
+
+pub fn read_json_preset<T: DeserializeOwned>(filepath: &Path) -> Result<T, LoadError> {
+    let path = filepath.to_string_lossy().to_string();
+    let file = File::open(filepath).map_err(|error| LoadError::Io(path.clone(), error))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|error| LoadError::Json(path, error))
+}
+
+// Written next to the file then renamed, a reader never sees it half written
+pub fn write_atomically(filepath: &str, bytes: &[u8]) -> Result<(), LoadError> {
+    let temporary_filepath = format!("{}.tmp", filepath);
+    std::fs::write(&temporary_filepath, bytes).map_err(|error| LoadError::Write(filepath.to_string(), error))?;
+    return std::fs::rename(&temporary_filepath, filepath).map_err(|error| LoadError::Write(filepath.to_string(), error));
+}
+
+// Reads every json preset of a directory, sorted by file name, a missing directory means no presets
+pub fn load_user_presets<T: DeserializeOwned>(directory: &str, errors: &mut Vec<LoadError>) -> Vec<T> {
+    let mut presets = Vec::new();
+
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return presets,
+    };
+
+    let mut filepaths : Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "json"))
+        .collect();
+    filepaths.sort();
+
+    for filepath in filepaths {
+        match read_json_preset(&filepath) {
+            Ok(preset) => presets.push(preset),
+            Err(error) => errors.push(error),
+        }
+    }
+    return presets;
+}
+
+// Writes the preset in the directory with a file name made from the preset name, names like "Bass 1" and
+// "Bass-1" give the same file name so a number is added instead of replacing the file of another preset
+pub fn save_user_preset<T: Serialize>(directory: &str, name: &str, preset: &T) -> Result<(), LoadError> {
+    std::fs::create_dir_all(directory).map_err(|error| LoadError::Write(directory.to_string(), error))?;
+
+    let file_name : String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let mut filepath = Path::new(directory).join(format!("{}.json", file_name));
+    let mut i = 2;
+    let file = loop {
+        match OpenOptions::new().write(true).create_new(true).open(&filepath) {
+            Ok(file) => break file,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                filepath = Path::new(directory).join(format!("{}-{}.json", file_name, i));
+                i += 1;
+            },
+            Err(error) => return Err(LoadError::Write(filepath.to_string_lossy().to_string(), error)),
+        }
+    };
+
+    serde_json::to_writer_pretty(BufWriter::new(file), preset)
+        .map_err(|error| LoadError::Write(filepath.to_string_lossy().to_string(), error.into()))
+}
+
+type PresetSave = Box<dyn FnOnce() + Send>;
+
+// One thread saves the user presets of every instrument, it's started by the first writer
+fn preset_saves() -> Sender<PresetSave> {
+    static SAVES: OnceLock<Sender<PresetSave>> = OnceLock::new();
+    let saves = SAVES.get_or_init(|| {
+        let (saves, save_receiver) = mpsc::channel::<PresetSave>();
+        thread::spawn(move || {
+            for save in save_receiver.iter() {
+                save();
+            }
+        });
+        saves
+    });
+    return saves.clone();
+}
+
+// Saves the user presets of an instrument in the preset thread so the audio never waits for the disk
+pub struct PresetWriter<T> {
+    directory: Arc<str>,
+    saves: Sender<PresetSave>,
+    error_sender: Sender<LoadError>,
+    errors: Receiver<LoadError>,
+    preset_type: PhantomData<T>,
+}
+
+impl<T: Preset + Serialize + Send + 'static> PresetWriter<T> {
+    pub fn new(directory: &str) -> PresetWriter<T> {
+        let (error_sender, errors) = mpsc::channel::<LoadError>();
+        PresetWriter {
+            directory: Arc::from(directory),
+            saves: preset_saves(),
+            error_sender,
+            errors,
+            preset_type: PhantomData,
+        }
+    }
+
+    // Writer of a copy of the processor, it saves nothing
+    pub fn detached() -> PresetWriter<T> {
+        let (saves, _) = mpsc::channel::<PresetSave>();
+        let (error_sender, errors) = mpsc::channel::<LoadError>();
+        PresetWriter {
+            directory: Arc::from(""),
+            saves,
+            error_sender,
+            errors,
+            preset_type: PhantomData,
+        }
+    }
+
+    pub fn save(&self, preset: T) {
+        let directory = self.directory.clone();
+        let error_sender = self.error_sender.clone();
+        let _ = self.saves.send(Box::new(move || {
+            if let Err(error) = save_user_preset(&directory, &preset.get_name(), &preset) {
+                let _ = error_sender.send(error);
+            }
+        }));
+    }
+
+    // Moves the errors of the presets that couldn't be written
+    pub fn receive_errors(&self, errors: &mut Vec<LoadError>) {
+        while let Ok(error) = self.errors.try_recv() {
+            errors.push(error);
+        }
+    }
+}
+
+// Name not used by any of the presets, "G-Bass" becomes "G-Bass 2", "G-Bass 3" etc
+pub fn new_preset_name(name: &str, names: &Vec<String>) -> String {
+    let mut i = 2;
+    loop {
+        let new_name = format!("{} {}", name, i);
+        if !names.contains(&new_name) {
+            return new_name;
+        }
+        i += 1;
+    }
+}
+
+pub mod filter_type_serde {
+    use biquad::Type;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(filter_type: &Type<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = match filter_type {
+            Type::HighPass => "HighPass",
+            Type::BandPass => "BandPass",
+            Type::Notch => "Notch",
+            Type::AllPass => "AllPass",
+            _ => "LowPass",
+        };
+        serializer.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Type<f32>, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.as_str() {
+            "LowPass" => Ok(Type::LowPass),
+            "HighPass" => Ok(Type::HighPass),
+            "BandPass" => Ok(Type::BandPass),
+            "Notch" => Ok(Type::Notch),
+            "AllPass" => Ok(Type::AllPass),
+            _ => Err(D::Error::custom(format!("unknown filter type {}", name))),
+        }
+    }
+}
+
+pub mod filter_q_serde {
+    use crate::preset::{MIN_FILTER_Q, MAX_FILTER_Q};
+    use serde::{Deserialize, Deserializer};
+    use serde::de::Error;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        let filter_q = f32::deserialize(deserializer)?;
+        if !(MIN_FILTER_Q..=MAX_FILTER_Q).contains(&filter_q) {
+            return Err(D::Error::custom(format!("filter Q {} out of [{}, {}]", filter_q, MIN_FILTER_Q, MAX_FILTER_Q)));
+        }
+        return Ok(filter_q);
+    }
+}
+
+pub mod hertz_serde {
+    use biquad::Hertz;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(frequency: &Hertz<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(frequency.hz())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hertz<f32>, D::Error> {
+        let frequency = f32::deserialize(deserializer)?;
+        Hertz::<f32>::from_hz(frequency).map_err(|_| D::Error::custom(format!("invalid frequency {}", frequency)))
+    }
+}
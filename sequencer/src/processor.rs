@@ -1,19 +1,64 @@
-use crate::midimessage::NoteEvent;
+use crate::midimessage::{NoteEvent, AutomationPoint};
 use crate::preset::Preset;
+use crate::parameter::Parameter;
+use crate::load_error::LoadError;
 
-pub trait Processor {
+use std::error::Error;
+
+// State of a processor copied by the audio thread, the copy rendering the track is built from it in another thread
+// so the audio thread doesn't make voices or effects
+pub trait ProcessorSnapshot: Send {
+    fn into_processor(self: Box<Self>) -> Box<dyn Processor>;
+}
+
+pub trait Processor: Send {
     fn note_on(&mut self, midi_note: u8, velocity: f32);
     fn note_off(&mut self, midi_note: u8);
     fn all_note_off(&mut self);
+    // value is in [-1, 1], 0 meaning no bend
+    fn pitch_bend(&mut self, value: f32);
+    // value is in [0, 1]
+    fn control_change(&mut self, controller: u8, value: f32);
     fn process(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize);
     fn prepare(&mut self, sample_rate: f32, num_samples: usize, nb_channels: usize);
+    // Tempo of the project in beats per minute
+    fn set_tempo(&mut self, bpm: f32);
+    // An armed processor records the audio input, the take ends when it's disarmed
+    fn set_armed(&mut self, armed: bool);
+    // The selected track is armed, the memory of its takes is reserved until it's disarmed
+    fn reserve_take(&mut self, reserved: bool);
+    // Interleaved frames of the audio input
+    fn record_input(&mut self, inputs: &[f32], num_samples: usize, nb_channels: usize);
     
     fn get_notes_events(&mut self) -> &mut Vec<NoteEvent>;
     fn add_notes_event(&mut self, midi_message: NoteEvent);
 
+    fn get_automation_points(&mut self) -> &mut Vec<AutomationPoint>;
+    fn add_automation_point(&mut self, automation_point: AutomationPoint);
+
     fn get_name(&self) -> String;
 
+    // Errors met while loading presets and samples, drained by the sequencer to be shown in the UI
+    fn get_errors(&mut self) -> &mut Vec<LoadError>;
+
     fn set_current_preset_id(&mut self, id: usize);
     fn get_current_preset_id(&self) -> usize;
     fn get_presets(&self) -> Vec<Box<dyn Preset>>;
-}
\ No newline at end of file
+
+    fn get_parameters(&self) -> Vec<Parameter>;
+    fn get_parameter(&self, id: usize) -> f32;
+    fn set_parameter(&mut self, id: usize, value: f32);
+    // True once after the processor added or removed parameters by itself, like the pads of a kit read in the background
+    fn have_parameters_changed(&mut self) -> bool;
+
+    // Saves the current state of the preset as a new user preset and selects it
+    fn save_current_preset(&mut self) -> Result<(), Box<dyn Error>>;
+
+    // Takes audio rendered at the engine rate, like a bounced track, as a new sample or a loop to slice
+    // The channels are moved out when the audio is taken
+    fn add_audio(&mut self, name: &str, left_channel: &mut Vec<f32>, right_channel: &mut Vec<f32>, sliced: bool) -> Result<(), Box<dyn Error>>;
+
+    // Current preset, parameters, notes and automation, the copy made from it has silent voices
+    // It shares the samples and waves of the processor and doesn't load or save anything
+    fn snapshot(&self) -> Box<dyn ProcessorSnapshot>;
+}
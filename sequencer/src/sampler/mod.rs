@@ -1,4 +1,12 @@
 pub mod sample_voice;
 pub mod sample;
 pub mod sampler;
-pub mod sampler_preset;
\ No newline at end of file
+pub mod sampler_preset;
+pub mod resampler;
+pub mod sfz;
+pub mod sf2;
+pub mod slicer;
+pub mod time_stretch;
+pub mod wav;
+pub mod sample_loader;
+pub mod recorder;
@@ -0,0 +1,97 @@
+use crate::decibels::db_to_gain;
+
+// Longest take, the memory is reserved by the loader when the track is armed so the audio thread doesn't allocate
+pub const MAX_DURATION : f32 = 60.;
+// Frames under this level at the start and the end of a take are cut
+const SILENCE_DB : f32 = -50.;
+// Kept before the first loud frame so the attack isn't cut, and after the last one for the tail
+const PRE_ROLL_DURATION : f32 = 0.005;
+const TAIL_DURATION : f32 = 0.05;
+const NORMALIZED_PEAK_DB : f32 = -1.;
+
+// Audio input captured while the sampler is armed, the take is trimmed and saved by the loader
+// which frees the buffers once it's done with them
+pub struct Recorder {
+    left_channel: Vec<f32>,
+    right_channel: Vec<f32>,
+    pub is_recording: bool,
+    // The input went on after the take was full
+    pub is_truncated: bool,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            left_channel: Vec::new(),
+            right_channel: Vec::new(),
+            is_recording: false,
+            is_truncated: false,
+        }
+    }
+
+    pub fn has_buffers(&self) -> bool {
+        return self.left_channel.capacity() > 0;
+    }
+
+    // The input is lost until the loader gives the buffers
+    pub fn start(&mut self) {
+        self.left_channel.clear();
+        self.right_channel.clear();
+        self.is_recording = true;
+        self.is_truncated = false;
+    }
+
+    // Interleaved inputs, a mono input is recorded on both channels
+    pub fn push(&mut self, inputs: &[f32], num_samples: usize, nb_channels: usize) {
+        if !self.is_recording || nb_channels == 0 {
+            return;
+        }
+        let nb_free_frames = self.left_channel.capacity() - self.left_channel.len();
+        if self.has_buffers() && num_samples > nb_free_frames {
+            self.is_truncated = true;
+        }
+        let num_samples = num_samples.min(nb_free_frames);
+        for frame in 0..num_samples {
+            let left = inputs[frame * nb_channels];
+            let right = inputs[frame * nb_channels + (nb_channels > 1) as usize];
+            self.left_channel.push(left);
+            self.right_channel.push(right);
+        }
+    }
+
+    // The raw take, its buffers are moved out without copying them
+    pub fn finish(&mut self) -> (Vec<f32>, Vec<f32>) {
+        self.is_recording = false;
+        return (std::mem::take(&mut self.left_channel), std::mem::take(&mut self.right_channel));
+    }
+
+    pub fn set_buffers(&mut self, left_channel: Vec<f32>, right_channel: Vec<f32>) {
+        self.left_channel = left_channel;
+        self.right_channel = right_channel;
+    }
+}
+
+// Empty buffers of the longest take, made in the loader thread
+pub fn take_buffers(sample_rate: f32) -> (Vec<f32>, Vec<f32>) {
+    let max_frames = (MAX_DURATION * sample_rate) as usize;
+    return (Vec::with_capacity(max_frames), Vec::with_capacity(max_frames));
+}
+
+// The take without its silent start and end, normalized, None if it stayed silent
+pub fn trim_take(left_channel: &[f32], right_channel: &[f32], sample_rate: f32) -> Option<(Vec<f32>, Vec<f32>)> {
+    let threshold = db_to_gain(SILENCE_DB);
+    let is_loud = |frame: usize| left_channel[frame].abs() > threshold || right_channel[frame].abs() > threshold;
+    let first = (0..left_channel.len()).find(|&frame| is_loud(frame))?;
+    let last = (0..left_channel.len()).rev().find(|&frame| is_loud(frame))?;
+
+    let start = first.saturating_sub((PRE_ROLL_DURATION * sample_rate) as usize);
+    let end = (last + 1 + (TAIL_DURATION * sample_rate) as usize).min(left_channel.len());
+    let mut left_channel = left_channel[start..end].to_vec();
+    let mut right_channel = right_channel[start..end].to_vec();
+
+    let peak = left_channel.iter().chain(right_channel.iter()).fold(0., |peak: f32, value| peak.max(value.abs()));
+    let gain = db_to_gain(NORMALIZED_PEAK_DB) / peak;
+    left_channel.iter_mut().chain(right_channel.iter_mut()).for_each(|value| *value *= gain);
+
+    return Some((left_channel, right_channel));
+}
@@ -0,0 +1,88 @@
+// Band limited resampling with a Blackman windowed sinc
+// https://ccrma.stanford.edu/~jos/resample/
+
+const ZERO_CROSSINGS : usize = 32;
+const TABLE_STEPS_PER_CROSSING : usize = 512;
+
+pub struct Resampler {
+    // Right half of the windowed sinc, from 0 to ZERO_CROSSINGS
+    table: Vec<f64>,
+}
+
+impl Resampler {
+    pub fn new() -> Resampler {
+        let table_size = ZERO_CROSSINGS * TABLE_STEPS_PER_CROSSING + 1;
+        let mut table = Vec::with_capacity(table_size);
+
+        for i in 0..table_size {
+            let x = i as f64 / TABLE_STEPS_PER_CROSSING as f64;
+            let sinc = if i == 0 { 1. } else { (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x) };
+            let phase = std::f64::consts::PI * x / ZERO_CROSSINGS as f64;
+            let window = 0.42 + 0.5 * phase.cos() + 0.08 * (2. * phase).cos();
+            table.push(sinc * window);
+        }
+
+        Resampler {
+            table
+        }
+    }
+
+    fn windowed_sinc(&self, x: f64) -> f64 {
+        let position = x.abs() * TABLE_STEPS_PER_CROSSING as f64;
+        let idx = position as usize;
+        if idx + 1 >= self.table.len() {
+            return 0.;
+        }
+        let alpha = position - idx as f64;
+        self.table[idx] * (1. - alpha) + self.table[idx + 1] * alpha
+    }
+
+    // When the rate goes down the cutoff moves down with it to avoid aliasing
+    pub fn resample(&self, input: &[f32], source_rate: f32, target_rate: f32) -> Vec<f32> {
+        if input.is_empty() || source_rate == target_rate {
+            return input.to_vec();
+        }
+
+        let output_size = resampled_len(input.len(), source_rate, target_rate);
+        return self.resample_frames(input, 0, source_rate, target_rate, 0, output_size);
+    }
+
+    // Output frames first..first + count, the input holds the source frames from input_start and the ones read
+    // around them by the filter, so a long file is resampled a block at a time
+    pub fn resample_frames(&self, input: &[f32], input_start: usize, source_rate: f32, target_rate: f32, first: usize, count: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(count);
+        if input.is_empty() {
+            output.resize(count, 0.);
+            return output;
+        }
+
+        let ratio = target_rate as f64 / source_rate as f64;
+        let cutoff = ratio.min(1.);
+        let half_width = filter_half_width(source_rate, target_rate);
+        let input_end = input_start + input.len() - 1;
+
+        for n in first..first + count {
+            let position = n as f64 / ratio;
+            let first_frame = ((position - half_width).ceil().max(0.) as usize).max(input_start);
+            let last_frame = ((position + half_width).floor() as usize).min(input_end);
+
+            let mut sum = 0.;
+            for k in first_frame..=last_frame {
+                sum += input[k - input_start] as f64 * self.windowed_sinc((k as f64 - position) * cutoff);
+            }
+            output.push((sum * cutoff) as f32);
+        }
+
+        return output;
+    }
+}
+
+pub fn resampled_len(len: usize, source_rate: f32, target_rate: f32) -> usize {
+    return (len as f64 * target_rate as f64 / source_rate as f64).round() as usize;
+}
+
+// Source frames read on each side of an output frame
+pub fn filter_half_width(source_rate: f32, target_rate: f32) -> f64 {
+    let cutoff = (target_rate as f64 / source_rate as f64).min(1.);
+    return ZERO_CROSSINGS as f64 / cutoff;
+}
@@ -1,7 +1,11 @@
-use std::fs::File;
-// use std::path::Path;
-use crate::sampler::sampler_preset::SampleInfo;
-// use wav_io::{, header::*, tone};
+use crate::sampler::sampler_preset::{SampleInfo, SampleSettings, LoopMode};
+use crate::load_error::LoadError;
+use crate::sampler::resampler::Resampler;
+use crate::sampler::sf2;
+use crate::sampler::wav;
+use crate::sampler::sample_loader::SampleStream;
+
+use std::sync::Arc;
 
 pub struct Sample {
     pub sample_rate: f32,
@@ -11,7 +15,18 @@ pub struct Sample {
     pub root_midi_note: u8,
     pub note_midi_min: u8,
     pub note_midi_max: u8,
-    pub is_one_shot: bool
+    pub velocity_min: u8,
+    pub velocity_max: u8,
+    pub round_robin_group: Option<u8>,
+    pub choke_group: Option<u8>,
+    pub loop_mode: LoopMode,
+    pub loop_start: usize,
+    pub loop_end: usize,
+    pub loop_crossfade: usize,
+    pub original_bpm: Option<f32>,
+    pub is_one_shot: bool,
+    // Frames after the channels, read from the disk while the sample plays
+    pub stream: Option<Arc<SampleStream>>,
 }
 
 impl Sample {
@@ -25,44 +40,150 @@ impl Sample {
             size: 0,
             note_midi_min: 0,
             note_midi_max: 0,
-            is_one_shot: true
+            velocity_min: 0,
+            velocity_max: 127,
+            round_robin_group: None,
+            choke_group: None,
+            loop_mode: LoopMode::None,
+            loop_start: 0,
+            loop_end: 0,
+            loop_crossfade: 0,
+            original_bpm: None,
+            is_one_shot: true,
+            stream: None,
+        }
+    }
+
+    // Silent pad keeping the notes of a sample that can't be loaded
+    pub fn placeholder(sample_info: &SampleInfo, sample_rate: f32) -> Sample {
+        Sample {
+            sample_rate: sample_rate,
+            root_midi_note: sample_info.root_midi_note,
+            note_midi_min: sample_info.note_midi_min,
+            note_midi_max: sample_info.note_midi_max,
+            velocity_min: sample_info.velocity_min,
+            velocity_max: sample_info.velocity_max,
+            round_robin_group: sample_info.round_robin_group,
+            choke_group: sample_info.choke_group,
+            original_bpm: sample_info.original_bpm,
+            is_one_shot: sample_info.is_one_shot,
+            ..Sample::empty()
         }
     }
 
-    pub fn load_sample(sample_info: &SampleInfo, sample_rate: f32) -> Sample {
-        let file_in = File::open(sample_info.filepath.clone()).unwrap();
-        let (header, samples) = wav_io::read_from_file(file_in).unwrap();
-
-        let mut left_channel: Vec<f32> = Vec::with_capacity(samples.len());
-        let mut right_channel: Vec<f32> = Vec::with_capacity(samples.len());
-
-        if header.channels == 1 {
-            left_channel = samples.clone();
-            right_channel = samples.clone();
-        } else if header.channels == 2 {
-            let mut i = 0;
-            while i < samples.len() {
-                left_channel.push(samples[i]);
-                right_channel.push(samples[i+1]);
-                i += 2;
+    pub fn load_sample(sample_info: &SampleInfo, sample_rate: f32) -> Result<Sample, LoadError> {
+        let (mut left_channel, mut right_channel, source_sample_rate) = match &sample_info.sf2_sample {
+            Some(sf2_sample) => {
+                let (left_channel, right_channel) = sf2::read_sample(&sample_info.filepath, sf2_sample)?;
+                (left_channel, right_channel, sf2_sample.sample_rate as f32)
+            },
+            None => wav::read_wav(&sample_info.filepath)?,
+        };
+
+        // Samples are stored at the engine rate so they play at their pitch whatever the file rate
+        if source_sample_rate != sample_rate {
+            let resampler = Resampler::new();
+            left_channel = resampler.resample(&left_channel, source_sample_rate, sample_rate);
+            right_channel = resampler.resample(&right_channel, source_sample_rate, sample_rate);
+        }
+
+        let size = left_channel.len();
+        return Ok(Sample::new(sample_info, left_channel, right_channel, size, sample_rate / source_sample_rate, sample_rate));
+    }
+
+    // Frames already at the engine rate, like a recording
+    pub fn from_channels(sample_info: &SampleInfo, left_channel: Vec<f32>, right_channel: Vec<f32>, sample_rate: f32) -> Sample {
+        let size = left_channel.len();
+        return Sample::new(sample_info, left_channel, right_channel, size, 1., sample_rate);
+    }
+
+    // Head of a long wav file, the other frames come from the stream once they are read
+    pub fn streamed(sample_info: &SampleInfo, left_channel: Vec<f32>, right_channel: Vec<f32>, stream: Arc<SampleStream>, rate_ratio: f32, sample_rate: f32) -> Sample {
+        let size = left_channel.len() + stream.len();
+        let sample = Sample {
+            stream: Some(stream),
+            ..Sample::new(sample_info, left_channel, right_channel, size, rate_ratio, sample_rate)
+        };
+        if let Some(stream) = &sample.stream {
+            if sample.loop_mode != LoopMode::None {
+                stream.set_loop_start(sample.loop_start.saturating_sub(sample.left_channel.len()));
             }
         }
-    
-        return Sample {
+        sample.follow_settings(&sample_info.settings);
+        return sample;
+    }
+
+    fn new(sample_info: &SampleInfo, left_channel: Vec<f32>, right_channel: Vec<f32>, size: usize, rate_ratio: f32, sample_rate: f32) -> Sample {
+        let to_engine_frames = |frame: usize| (frame as f32 * rate_ratio).round() as usize;
+
+        // The frame at the loop end is read when the loop wraps, it has to exist
+        let loop_end = to_engine_frames(sample_info.loop_end.unwrap_or(size)).min(size.saturating_sub(1));
+        let loop_start = to_engine_frames(sample_info.loop_start).min(loop_end);
+        let loop_mode = if loop_end > loop_start { sample_info.loop_mode } else { LoopMode::None };
+        // The crossfade reads before the loop start
+        let loop_crossfade = to_engine_frames(sample_info.loop_crossfade).min(loop_start).min(loop_end - loop_start);
+
+        Sample {
             sample_rate: sample_rate,
-            size: left_channel.len(),
+            size: size,
             left_channel: left_channel,
             right_channel: right_channel,
             root_midi_note: sample_info.root_midi_note,
             note_midi_min: sample_info.note_midi_min,
             note_midi_max: sample_info.note_midi_max,
-            is_one_shot: sample_info.is_one_shot
+            velocity_min: sample_info.velocity_min,
+            velocity_max: sample_info.velocity_max,
+            round_robin_group: sample_info.round_robin_group,
+            choke_group: sample_info.choke_group,
+            loop_mode: loop_mode,
+            loop_start: loop_start,
+            loop_end: loop_end,
+            loop_crossfade: loop_crossfade,
+            original_bpm: sample_info.original_bpm,
+            is_one_shot: sample_info.is_one_shot,
+            stream: None,
+        }
+    }
+
+    // Played part of the sample with the settings of a pad
+    pub fn start_frame(&self, settings: &SampleSettings) -> f32 {
+        return (settings.start.clamp(0.0, 1.0) * self.size as f32).floor();
+    }
+
+    pub fn end_frame(&self, settings: &SampleSettings) -> f32 {
+        return (settings.end.clamp(0.0, 1.0) * self.size as f32).floor().max(self.start_frame(settings));
+    }
+
+    // A reversed pad starts from its end
+    pub fn first_played_frame(&self, settings: &SampleSettings) -> f32 {
+        if settings.reverse {
+            return (self.end_frame(settings) - 1.0).max(0.0);
+        }
+        return self.start_frame(settings);
+    }
+
+    // The stream keeps the frames the voices of the pad start from read
+    pub fn follow_settings(&self, settings: &SampleSettings) {
+        if let Some(stream) = &self.stream {
+            let first_frame = self.first_played_frame(settings) as usize;
+            stream.set_start(first_frame.saturating_sub(self.left_channel.len()));
+        }
+    }
+
+    // Frames of the stream not read yet are silent
+    pub fn frame(&self, idx: usize) -> (f32, f32) {
+        if idx < self.left_channel.len() {
+            return (self.left_channel[idx], self.right_channel[idx]);
+        }
+        match &self.stream {
+            Some(stream) => stream.frame(idx - self.left_channel.len()),
+            None => (0., 0.),
         }
     }
 
-    pub fn apply_to_note(&self, midi_note: u8) -> bool {
+    pub fn apply_to_note(&self, midi_note: u8, midi_velocity: u8) -> bool {
         if self.note_midi_min <= midi_note && midi_note <= self.note_midi_max  {
-            return true;
+            return self.velocity_min <= midi_velocity && midi_velocity <= self.velocity_max;
         }
         return false;
     } 
@@ -0,0 +1,469 @@
+use crate::load_error::LoadError;
+use crate::sampler::sample::Sample;
+use crate::sampler::sampler_preset::{self, SampleInfo};
+use crate::sampler::recorder::{trim_take, take_buffers};
+use crate::sampler::slicer::{Slicer, SliceSettings, SlicedLoop};
+use crate::sampler::wav::{self, WavReader};
+use crate::sampler::resampler::{Resampler, resampled_len, filter_half_width};
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender, Receiver, TryRecvError, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Wav files longer than this are streamed from the disk
+const STREAMING_MIN_DURATION : f32 = 10.;
+// Duration read before the sample can be played, the rest is read in the background
+const PRELOAD_DURATION : f32 = 2.;
+pub const STREAM_BLOCK_SIZE : usize = 32768;
+// Blocks kept read on each side of the ones the voices play or start from, the others are freed
+pub const STREAM_WINDOW : usize = 2;
+// A block stays played this long after a voice read it
+const STREAM_READ_TIMEOUT : Duration = Duration::from_millis(500);
+// How often the samples still played after being replaced are checked while there's nothing else to do
+const DROP_INTERVAL : Duration = Duration::from_millis(100);
+// How often the voices playing streams are followed once the blocks around them are read
+const STREAM_INTERVAL : Duration = Duration::from_millis(20);
+// No frame for the anchors of a stream
+const NO_FRAME : usize = usize::MAX;
+
+// Frames of a streamed sample after its head, at the engine rate
+// Only the blocks around the voices, the start of the pad and the loop start are kept, the audio thread never waits
+// for a block, the frames of the blocks being read or not read yet are silent
+pub struct SampleStream {
+    blocks: Vec<Mutex<Option<(Vec<f32>, Vec<f32>)>>>,
+    // Clock of the loader when a voice last read each block
+    reads: Vec<AtomicUsize>,
+    clock: AtomicUsize,
+    start: AtomicUsize,
+    loop_start: AtomicUsize,
+    size: usize,
+}
+
+impl SampleStream {
+    fn new(size: usize) -> SampleStream {
+        let nb_blocks = size.div_ceil(STREAM_BLOCK_SIZE);
+        SampleStream {
+            blocks: (0..nb_blocks).map(|_| Mutex::new(None)).collect(),
+            reads: (0..nb_blocks).map(|_| AtomicUsize::new(NO_FRAME)).collect(),
+            clock: AtomicUsize::new(0),
+            start: AtomicUsize::new(0),
+            loop_start: AtomicUsize::new(NO_FRAME),
+            size,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.size;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.size == 0;
+    }
+
+    // Frame the voices start from, set again when the pad settings change
+    pub fn set_start(&self, idx: usize) {
+        self.start.store(idx, Ordering::Relaxed);
+    }
+
+    pub fn set_loop_start(&self, idx: usize) {
+        self.loop_start.store(idx, Ordering::Relaxed);
+    }
+
+    // Reading a block that isn't there yet asks the loader for it
+    pub fn frame(&self, idx: usize) -> (f32, f32) {
+        let block_idx = idx / STREAM_BLOCK_SIZE;
+        if block_idx >= self.blocks.len() {
+            return (0., 0.);
+        }
+        self.reads[block_idx].store(self.clock.load(Ordering::Relaxed), Ordering::Relaxed);
+        // The loader only holds the lock while it sets or frees the block
+        match self.blocks[block_idx].try_lock() {
+            Ok(block) => match block.as_ref() {
+                Some((left_channel, right_channel)) if idx % STREAM_BLOCK_SIZE < left_channel.len() => {
+                    (left_channel[idx % STREAM_BLOCK_SIZE], right_channel[idx % STREAM_BLOCK_SIZE])
+                },
+                _ => (0., 0.),
+            },
+            Err(_) => (0., 0.),
+        }
+    }
+
+    // Blocks in memory
+    pub fn nb_blocks_read(&self) -> usize {
+        return self.blocks.iter().filter(|block| block.lock().map_or(false, |block| block.is_some())).count();
+    }
+
+    // Blocks played by the voices in the last STREAM_READ_TIMEOUT ms, then the start and the loop start
+    fn anchors(&self) -> Vec<usize> {
+        let clock = self.clock.load(Ordering::Relaxed);
+        let timeout = STREAM_READ_TIMEOUT.as_millis() as usize;
+        let mut anchors: Vec<usize> = (0..self.blocks.len())
+            .filter(|block_idx| {
+                let read = self.reads[*block_idx].load(Ordering::Relaxed);
+                read != NO_FRAME && clock.saturating_sub(read) <= timeout
+            })
+            .collect();
+        for idx in [self.start.load(Ordering::Relaxed), self.loop_start.load(Ordering::Relaxed)] {
+            if idx != NO_FRAME {
+                anchors.push((idx / STREAM_BLOCK_SIZE).min(self.blocks.len().saturating_sub(1)));
+            }
+        }
+        return anchors;
+    }
+
+}
+
+// Each request has a generation, the results of the requests replaced by a newer one are ignored
+pub enum LoadRequest {
+    Kit { generation: usize, samples: Vec<SampleInfo> },
+    // The loop is kept in the thread so it can be cut again
+    Loop { generation: usize, filepath: String, settings: SliceSettings },
+    Slice { generation: usize, settings: SliceSettings },
+    MoveSlicePoint { generation: usize, idx: usize, position: usize },
+    // Writes a loop, its folder is made if needed
+    Save { filepath: String, left_channel: Vec<f32>, right_channel: Vec<f32> },
+    // Buffers for the takes of an armed sampler
+    TakeBuffers,
+    // A recorded take is trimmed and normalized first, then the pad is saved and added to the preset file of its kit
+    Pad { preset_id: usize, preset_filepath: String, sample_info: SampleInfo, left_channel: Vec<f32>, right_channel: Vec<f32>, is_take: bool },
+    // Samples replaced in the sampler, freed here once no voice plays them so the audio thread never frees their frames
+    Drop(Vec<Arc<Sample>>),
+    // Take buffers the sampler doesn't need anymore
+    DropTake(Vec<f32>, Vec<f32>),
+}
+
+pub enum LoadResult {
+    // One sample for each sample info, silent placeholders for the ones that can't be loaded
+    Kit { generation: usize, samples: Vec<Arc<Sample>>, errors: Vec<LoadError> },
+    // One sample for each slice, the pads are made again when the loop is cut again rather than a point moved
+    Slices { generation: usize, sliced_loop: SlicedLoop, samples: Vec<Arc<Sample>>, is_resliced: bool },
+    TakeBuffers(Vec<f32>, Vec<f32>),
+    // No pad for a silent take
+    Pad { preset_id: usize, midi_note: u8, pad: Option<(SampleInfo, Arc<Sample>)> },
+    // A streamed sample can't be read anymore or a recording can't be saved
+    Error(LoadError),
+}
+
+// Reads the samples in a thread so the audio never waits for the disk
+pub struct SampleLoader {
+    requests: Sender<LoadRequest>,
+    results: Receiver<LoadResult>,
+}
+
+// Reads the blocks of a stream from its file, resampled to the engine rate
+// The blocks read are known here so the played ones aren't locked to find the missing ones
+struct PendingStream {
+    stream: Arc<SampleStream>,
+    reader: StreamReader,
+    // Frames of the head before the stream
+    offset: usize,
+    is_read: Vec<bool>,
+}
+
+impl PendingStream {
+    // Nearest block to an anchor that isn't read yet
+    fn next_missing_block(&self, anchors: &[usize]) -> Option<usize> {
+        for distance in 0..=STREAM_WINDOW {
+            for anchor in anchors {
+                for block_idx in [anchor.checked_add(distance), anchor.checked_sub(distance)].into_iter().flatten() {
+                    if block_idx < self.is_read.len() && !self.is_read[block_idx] {
+                        return Some(block_idx);
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    fn read_block(&mut self, block_idx: usize) -> Result<(), LoadError> {
+        let start = block_idx * STREAM_BLOCK_SIZE;
+        let frames = self.reader.read_frames(self.offset + start, STREAM_BLOCK_SIZE.min(self.stream.size - start))?;
+        if let Ok(mut block) = self.stream.blocks[block_idx].lock() {
+            *block = Some(frames);
+        }
+        self.is_read[block_idx] = true;
+        return Ok(());
+    }
+
+    // Every block around the anchors is read before the sample is played
+    fn read_window(&mut self) -> Result<(), LoadError> {
+        let anchors = self.stream.anchors();
+        while let Some(block_idx) = self.next_missing_block(&anchors) {
+            self.read_block(block_idx)?;
+        }
+        return Ok(());
+    }
+
+    // The blocks far from the anchors aren't played, they are freed here rather than in the audio thread
+    fn free_unwanted_blocks(&mut self, anchors: &[usize]) {
+        for block_idx in 0..self.is_read.len() {
+            if self.is_read[block_idx] && anchors.iter().all(|anchor| anchor.abs_diff(block_idx) > STREAM_WINDOW) {
+                let freed = self.stream.blocks[block_idx].lock().ok().and_then(|mut block| block.take());
+                drop(freed);
+                self.is_read[block_idx] = false;
+            }
+        }
+    }
+}
+
+// Frames of a wav file at the engine rate, the frames around a block are read for the resampling filter
+struct StreamReader {
+    reader: WavReader,
+    resampler: Option<Resampler>,
+    sample_rate: f32,
+    // Frames at the engine rate
+    nb_frames: usize,
+}
+
+impl StreamReader {
+    fn new(reader: WavReader, sample_rate: f32) -> StreamReader {
+        let source_rate = reader.sample_rate as f32;
+        let resampler = if source_rate != sample_rate { Some(Resampler::new()) } else { None };
+        let nb_frames = resampled_len(reader.nb_frames, source_rate, sample_rate);
+        StreamReader {
+            reader,
+            resampler,
+            sample_rate,
+            nb_frames,
+        }
+    }
+
+    fn rate_ratio(&self) -> f32 {
+        return self.sample_rate / self.reader.sample_rate as f32;
+    }
+
+    fn read_frames(&mut self, start: usize, count: usize) -> Result<(Vec<f32>, Vec<f32>), LoadError> {
+        let count = count.min(self.nb_frames.saturating_sub(start));
+        let resampler = match &self.resampler {
+            Some(resampler) => resampler,
+            None => return self.reader.read_frames(start, count),
+        };
+        let source_rate = self.reader.sample_rate as f32;
+        let ratio = self.rate_ratio() as f64;
+        let half_width = filter_half_width(source_rate, self.sample_rate);
+        let source_start = (start as f64 / ratio - half_width).floor().max(0.) as usize;
+        let source_end = ((start + count) as f64 / ratio + half_width).ceil() as usize + 1;
+        let (left_channel, right_channel) = self.reader.read_frames(source_start, source_end - source_start)?;
+        return Ok((
+            resampler.resample_frames(&left_channel, source_start, source_rate, self.sample_rate, start, count),
+            resampler.resample_frames(&right_channel, source_start, source_rate, self.sample_rate, start, count),
+        ));
+    }
+}
+
+impl SampleLoader {
+    pub fn new(sample_rate: f32) -> SampleLoader {
+        let (requests, request_receiver) = mpsc::channel::<LoadRequest>();
+        let (result_sender, results) = mpsc::channel::<LoadResult>();
+        thread::spawn(move || run(sample_rate, request_receiver, result_sender));
+        SampleLoader {
+            requests,
+            results,
+        }
+    }
+
+    // Loader of a copy of the sampler, it reads nothing and runs no thread
+    pub fn detached() -> SampleLoader {
+        let (requests, _) = mpsc::channel::<LoadRequest>();
+        let (_, results) = mpsc::channel::<LoadResult>();
+        SampleLoader {
+            requests,
+            results,
+        }
+    }
+
+    pub fn request(&self, request: LoadRequest) {
+        // The thread only stops when the loader is dropped
+        let _ = self.requests.send(request);
+    }
+
+    pub fn try_receive(&self) -> Option<LoadResult> {
+        return self.results.try_recv().ok();
+    }
+}
+
+fn run(sample_rate: f32, requests: Receiver<LoadRequest>, results: Sender<LoadResult>) {
+    let mut streams: Vec<PendingStream> = Vec::new();
+    // Loop of the last Loop request and its generation
+    let mut slicer: Option<(usize, Slicer)> = None;
+    let mut dropped: Vec<Arc<Sample>> = Vec::new();
+    let started = Instant::now();
+    let mut is_streaming = false;
+    loop {
+        // Requests come first, the streams are read while there are none
+        let request = if streams.is_empty() && dropped.is_empty() {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return,
+            }
+        } else if !is_streaming {
+            let interval = if streams.is_empty() { DROP_INTERVAL } else { STREAM_INTERVAL };
+            match requests.recv_timeout(interval) {
+                Ok(request) => Some(request),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        } else {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        };
+
+        if let Some(request) = request {
+            let result = match request {
+                LoadRequest::Kit { generation, samples } => {
+                    slicer = None;
+                    let mut errors = Vec::new();
+                    let samples = samples.iter().map(|sample_info| {
+                        Arc::new(load(sample_info, sample_rate, &mut streams).unwrap_or_else(|error| {
+                            errors.push(error);
+                            Sample::placeholder(sample_info, sample_rate)
+                        }))
+                    }).collect();
+                    LoadResult::Kit { generation, samples, errors }
+                },
+                LoadRequest::Loop { generation, filepath, settings } => match Slicer::load(&filepath, sample_rate) {
+                    Ok(mut loaded) => {
+                        loaded.slice(&settings);
+                        let result = slices(generation, &loaded, true);
+                        slicer = Some((generation, loaded));
+                        result
+                    },
+                    Err(error) => {
+                        slicer = None;
+                        LoadResult::Error(error)
+                    },
+                },
+                LoadRequest::Slice { generation, settings } => match slicer.as_mut() {
+                    Some((loop_generation, slicer)) if *loop_generation == generation => {
+                        slicer.slice(&settings);
+                        slices(generation, slicer, true)
+                    },
+                    _ => continue,
+                },
+                LoadRequest::MoveSlicePoint { generation, idx, position } => match slicer.as_mut() {
+                    Some((loop_generation, slicer)) if *loop_generation == generation => {
+                        slicer.set_slice_point(idx, position);
+                        slices(generation, slicer, false)
+                    },
+                    _ => continue,
+                },
+                LoadRequest::Save { filepath, left_channel, right_channel } => match save(&filepath, &left_channel, &right_channel, sample_rate) {
+                    Ok(()) => continue,
+                    Err(error) => LoadResult::Error(error),
+                },
+                LoadRequest::TakeBuffers => {
+                    let (left_channel, right_channel) = take_buffers(sample_rate);
+                    LoadResult::TakeBuffers(left_channel, right_channel)
+                },
+                LoadRequest::Pad { preset_id, preset_filepath, sample_info, left_channel, right_channel, is_take } => {
+                    let midi_note = sample_info.root_midi_note;
+                    let channels = if is_take {
+                        trim_take(&left_channel, &right_channel, sample_rate)
+                    } else {
+                        Some((left_channel, right_channel))
+                    };
+                    let pad = match channels {
+                        Some((left_channel, right_channel)) => {
+                            let saved = save(&sample_info.filepath, &left_channel, &right_channel, sample_rate)
+                                .and_then(|_| sampler_preset::save_pad(&preset_filepath, &sample_info));
+                            if let Err(error) = saved {
+                                let _ = results.send(LoadResult::Error(error));
+                            }
+                            let sample = Sample::from_channels(&sample_info, left_channel, right_channel, sample_rate);
+                            Some((sample_info, Arc::new(sample)))
+                        },
+                        None => None,
+                    };
+                    LoadResult::Pad { preset_id, midi_note, pad }
+                },
+                LoadRequest::Drop(samples) => {
+                    dropped.extend(samples);
+                    continue;
+                },
+                LoadRequest::DropTake(left_channel, right_channel) => {
+                    drop((left_channel, right_channel));
+                    continue;
+                },
+            };
+            if results.send(result).is_err() {
+                return;
+            }
+            continue;
+        }
+
+        dropped.retain(|sample| Arc::strong_count(sample) > 1);
+        // Nobody plays the streams of the kits that were replaced
+        streams.retain(|pending| Arc::strong_count(&pending.stream) > 1);
+        // One block of each stream in turn so they all keep ahead of the voices
+        let clock = started.elapsed().as_millis() as usize;
+        is_streaming = false;
+        let mut failed = Vec::new();
+        for (s, pending) in streams.iter_mut().enumerate() {
+            pending.stream.clock.store(clock, Ordering::Relaxed);
+            let anchors = pending.stream.anchors();
+            pending.free_unwanted_blocks(&anchors);
+            if let Some(block_idx) = pending.next_missing_block(&anchors) {
+                match pending.read_block(block_idx) {
+                    Ok(()) => is_streaming = true,
+                    Err(error) => {
+                        let _ = results.send(LoadResult::Error(error));
+                        failed.push(s);
+                    },
+                }
+            }
+        }
+        // A stream that can't be read anymore keeps its blocks and stays silent after them
+        for s in failed.into_iter().rev() {
+            streams.remove(s);
+        }
+    }
+}
+
+fn slices(generation: usize, slicer: &Slicer, is_resliced: bool) -> LoadResult {
+    return LoadResult::Slices { generation, sliced_loop: slicer.sliced_loop(), samples: slicer.slice_samples().into_iter().map(Arc::new).collect(), is_resliced };
+}
+
+// Long wav files are streamed, the other samples are read at once
+// The blocks where the pad starts are read with the head, at the end of the sample for a reversed pad
+fn load(sample_info: &SampleInfo, sample_rate: f32, streams: &mut Vec<PendingStream>) -> Result<Sample, LoadError> {
+    if sample_info.sf2_sample.is_some() {
+        return Sample::load_sample(sample_info, sample_rate);
+    }
+
+    let reader = WavReader::open(&sample_info.filepath)?;
+    let is_long = reader.nb_frames as f32 > STREAMING_MIN_DURATION * reader.sample_rate as f32;
+    if !is_long {
+        return Sample::load_sample(sample_info, sample_rate);
+    }
+
+    let mut reader = StreamReader::new(reader, sample_rate);
+    let preload_size = (PRELOAD_DURATION * sample_rate) as usize;
+    let (left_channel, right_channel) = reader.read_frames(0, preload_size)?;
+    let offset = left_channel.len();
+    let stream = Arc::new(SampleStream::new(reader.nb_frames - offset));
+    let rate_ratio = reader.rate_ratio();
+    let sample = Sample::streamed(sample_info, left_channel, right_channel, stream.clone(), rate_ratio, sample_rate);
+    let nb_blocks = stream.blocks.len();
+    let mut pending = PendingStream {
+        stream,
+        reader,
+        offset,
+        is_read: vec![false; nb_blocks],
+    };
+    pending.read_window()?;
+    streams.push(pending);
+    return Ok(sample);
+}
+
+fn save(filepath: &str, left_channel: &[f32], right_channel: &[f32], sample_rate: f32) -> Result<(), LoadError> {
+    if let Some(directory) = Path::new(filepath).parent() {
+        std::fs::create_dir_all(directory).map_err(|error| LoadError::Write(filepath.to_string(), error))?;
+    }
+    return wav::write_wav(filepath, left_channel, right_channel, sample_rate as u32);
+}
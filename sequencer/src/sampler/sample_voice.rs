@@ -1,13 +1,23 @@
 use crate::adsr::ADSR;
 use crate::sampler::sample::Sample;
-use std::rc::Rc;
+use crate::sampler::sampler_preset::{LoopMode, PlaybackMode, SampleSettings};
+use crate::sampler::time_stretch::GrainStretcher;
+use crate::decibels::db_to_gain;
+use std::sync::Arc;
+
+use biquad::*;
+
+const CHOKE_RELEASE_TIME: f32 = 0.005;
+// Frames between two updates of the filter cutoff following the envelope
+const FILTER_UPDATE_INTERVAL: usize = 32;
 
 #[derive(Clone)]
 pub struct SamplerVoice {
     pub active: bool,
-    pub sample: Rc<Sample>,
+    pub sample: Arc<Sample>,
     pub velocity: f32,
     pub pitch_ratio: f32,
+    pub bend_ratio: f32,
     pub sample_rate: f32,
     pub adsr: ADSR,
     pub root_midi_note: i8,
@@ -16,16 +26,30 @@ pub struct SamplerVoice {
     pub loop_crossfade_duration: usize,
     pub loop_length: usize,
     pub note_id: u8,
-    pub source_sample_position: f32
+    pub source_sample_position: f32,
+    pub is_playing_backward: bool,
+    pub is_released: bool,
+    pub settings: SampleSettings,
+    // Tempo of the project, followed by the samples having an original tempo
+    pub tempo: f32,
+    stretcher: GrainStretcher,
+    gain_left: f32,
+    gain_right: f32,
+    filter_left: DirectForm1<f32>,
+    filter_right: DirectForm1<f32>,
+    filter_update_countdown: usize
 }
 
 impl SamplerVoice {
     pub fn new(sample_rate: f32) -> SamplerVoice {
+        let coeffs = Coefficients::<f32>::from_params(Type::AllPass, sample_rate.hz(), 880.hz(), Q_BUTTERWORTH_F32).unwrap();
+
         SamplerVoice {
             active: true,
-            sample: Rc::new(Sample::empty()),
+            sample: Arc::new(Sample::empty()),
             velocity: 1.0,
             pitch_ratio: 0.0,
+            bend_ratio: 1.0,
             sample_rate: sample_rate,
             adsr: ADSR::new(0.0001, 0.0, 1.0, 0.4, sample_rate),
             loop_crossfade_duration: 0,
@@ -34,11 +58,21 @@ impl SamplerVoice {
             root_note_min: 60,
             root_note_max: 60,
             note_id: 0,
-            source_sample_position: 0.0
+            source_sample_position: 0.0,
+            is_playing_backward: false,
+            is_released: false,
+            settings: SampleSettings::default(),
+            tempo: 120.0,
+            stretcher: GrainStretcher::new(sample_rate),
+            gain_left: 1.0,
+            gain_right: 1.0,
+            filter_left: DirectForm1::<f32>::new(coeffs),
+            filter_right: DirectForm1::<f32>::new(coeffs),
+            filter_update_countdown: 0
         }
     }
 
-    pub fn start_note(&mut self, midi_note :u8, velocity: f32, sample: Rc<Sample>) {
+    pub fn start_note(&mut self, midi_note :u8, velocity: f32, sample: Arc<Sample>, settings: SampleSettings) {
         self.sample = sample;
         self.note_id = midi_note;
 
@@ -47,49 +81,191 @@ impl SamplerVoice {
         self.adsr.note_on();
         
 
-        self.adsr.set_sample_rate((*self.sample).sample_rate);
-        let midi_delta = ((midi_note - (*self.sample).root_midi_note)) as f32 / 12.0;
-        self.pitch_ratio = f32::powf(2.0, midi_delta as f32) * (*self.sample).sample_rate / self.sample_rate;
-        self.source_sample_position = 0.0;
+        self.adsr.set_sample_rate(self.sample_rate);
+        self.set_settings(settings);
+        self.is_playing_backward = settings.reverse;
+        self.source_sample_position = (*self.sample).first_played_frame(&settings);
+        self.is_released = false;
+        self.loop_length = (*self.sample).loop_end - (*self.sample).loop_start;
+        self.loop_crossfade_duration = (*self.sample).loop_crossfade;
+        self.stretcher.reset(self.source_sample_position, self.grain_step());
+
+        self.filter_left.reset_state();
+        self.filter_right.reset_state();
+        self.filter_update_countdown = 0;
 
         self.velocity = velocity;
     }
 
+    pub fn set_settings(&mut self, settings: SampleSettings) {
+        self.settings = settings;
+
+        let midi_delta = (self.note_id as i32 - (*self.sample).root_midi_note as i32) as f32 + settings.coarse + settings.fine / 100.0;
+        self.pitch_ratio = f32::powf(2.0, midi_delta / 12.0) * (*self.sample).sample_rate / self.sample_rate;
+
+        let gain = db_to_gain(settings.gain);
+        self.gain_left = gain * (1.0 - settings.pan).min(1.0);
+        self.gain_right = gain * (1.0 + settings.pan).min(1.0);
+    }
+
+    // Speed of the sample on the timeline to stay in sync with the project
+    fn tempo_ratio(&self) -> f32 {
+        match (*self.sample).original_bpm {
+            Some(original_bpm) if original_bpm > 0.0 => self.tempo / original_bpm,
+            _ => 1.0,
+        }
+    }
+
+    fn is_stretched(&self) -> bool {
+        return self.settings.playback_mode == PlaybackMode::Stretch && (*self.sample).original_bpm.is_some();
+    }
+
+    // Grains are read at the pitch of the note, in the direction the sample is played
+    fn grain_step(&self) -> f32 {
+        let step = self.pitch_ratio * self.bend_ratio;
+        if self.is_playing_backward {
+            return -step;
+        }
+        return step;
+    }
+
+    fn update_filter(&mut self, envelope_value: f32) {
+        let cutoff = self.settings.filter_cutoff * f32::powf(2.0, self.settings.filter_envelope_amount * envelope_value / 12.0);
+        let cutoff = cutoff.clamp(20.0, self.sample_rate * 0.45);
+        if let Ok(coeffs) = Coefficients::<f32>::from_params(self.settings.filter_type, self.sample_rate.hz(), cutoff.hz(), self.settings.filter_q.max(0.1)) {
+            self.filter_left.update_coefficients(coeffs);
+            self.filter_right.update_coefficients(coeffs);
+        }
+    }
+
+    fn start_frame(&self) -> f32 {
+        return (*self.sample).start_frame(&self.settings);
+    }
+
+    fn end_frame(&self) -> f32 {
+        return (*self.sample).end_frame(&self.settings);
+    }
+
     pub fn stop_note(&mut self) {
+        // The sample plays to its end once released
+        self.is_released = true;
         if !self.sample.is_one_shot {
             self.adsr.note_off();
         }
     }
 
+    // Quick fade out, even for one shot samples
+    pub fn choke(&mut self) {
+        self.adsr.release = CHOKE_RELEASE_TIME;
+        self.adsr.recalculate_rates();
+        self.adsr.note_off();
+    }
+
+    fn is_looping(&self) -> bool {
+        return match (*self.sample).loop_mode {
+            LoopMode::None => false,
+            LoopMode::Continuous => !self.settings.reverse,
+            _ => !self.is_released && !self.settings.reverse,
+        };
+    }
+
+    fn read_frame(&self, position: f32) -> (f32, f32) {
+        let pos = position as usize;
+        let alpha = position - (pos as f32);
+        let inv_alpha = 1.0 - alpha;
+        let mut interpol_pos = pos + 1;
+        if interpol_pos >= (*self.sample).size {
+            interpol_pos = pos;
+        }
+        let (left, right) = (*self.sample).frame(pos);
+        let (next_left, next_right) = (*self.sample).frame(interpol_pos);
+        return (left * inv_alpha + next_left * alpha, right * inv_alpha + next_right * alpha);
+    }
+
+    fn advance_position(&mut self) {
+        let mut step = self.pitch_ratio * self.bend_ratio * self.tempo_ratio();
+        if self.is_stretched() {
+            step = (*self.sample).sample_rate / self.sample_rate * self.tempo_ratio();
+        }
+        let loop_start = (*self.sample).loop_start as f32;
+        let loop_end = (*self.sample).loop_end as f32;
+
+        if self.is_playing_backward {
+            self.source_sample_position -= step;
+            if !self.settings.reverse && self.source_sample_position <= loop_start {
+                self.source_sample_position = 2.0 * loop_start - self.source_sample_position;
+                self.is_playing_backward = false;
+            }
+            return;
+        }
+
+        self.source_sample_position += step;
+        if self.is_looping() && self.source_sample_position >= loop_end {
+            if (*self.sample).loop_mode == LoopMode::PingPong {
+                self.source_sample_position = 2.0 * loop_end - self.source_sample_position;
+                self.is_playing_backward = true;
+            } else {
+                self.source_sample_position -= self.loop_length as f32;
+            }
+        }
+    }
+
+    fn is_in_played_range(&self, position: f32) -> bool {
+        if position < 0.0 || position >= (*self.sample).size as f32 {
+            return false;
+        }
+        // A held loop keeps playing past the end offset
+        if self.settings.reverse {
+            return position >= self.start_frame();
+        }
+        return self.is_looping() || position < self.end_frame();
+    }
+
     pub fn render_next_block(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize) {
         
         let mut idx = 0;
         while idx < nb_channels * num_samples {
             let envelope_value = self.adsr.tick();
 
-            let pos = self.source_sample_position as usize;
-            let alpha = self.source_sample_position - (pos as f32);
-            let inv_alpha = 1.0 - alpha;
-
             if envelope_value == 0.0 {
                 self.adsr.reset();
                 self.active = false;
                 break;
             }
 
-            if pos < (*self.sample).size {
+            let position = self.source_sample_position;
+            if self.is_in_played_range(position) {
                 let volume = envelope_value * self.velocity;
-                let mut interpol_pos = pos + 1;
-                if interpol_pos >= (*self.sample).size {
-                    interpol_pos = pos;
+                let (mut left, mut right) = if self.is_stretched() {
+                    let step = self.grain_step();
+                    self.stretcher.next_frame(&self.sample, position, step)
+                } else {
+                    self.read_frame(position)
+                };
+
+                // Fade the loop end into the frames before the loop start so the jump doesn't click
+                let fade_start = ((*self.sample).loop_end - self.loop_crossfade_duration) as f32;
+                if self.is_looping() && !self.is_stretched() && (*self.sample).loop_mode != LoopMode::PingPong && self.loop_crossfade_duration > 0 && position >= fade_start {
+                    let fade = (position - fade_start) / self.loop_crossfade_duration as f32;
+                    let (loop_left, loop_right) = self.read_frame(position - self.loop_length as f32);
+                    left = left * (1.0 - fade) + loop_left * fade;
+                    right = right * (1.0 - fade) + loop_right * fade;
+                }
+
+                if self.settings.filter_enabled {
+                    if self.filter_update_countdown == 0 {
+                        self.update_filter(envelope_value);
+                        self.filter_update_countdown = FILTER_UPDATE_INTERVAL;
+                    }
+                    self.filter_update_countdown -= 1;
+                    left = self.filter_left.run(left);
+                    right = self.filter_right.run(right);
                 }
-                let left = (*self.sample).left_channel[pos] * inv_alpha + (*self.sample).left_channel[interpol_pos] * alpha;
-                let right = (*self.sample).right_channel[pos] * inv_alpha + (*self.sample).right_channel[interpol_pos] * alpha;
 
-                outputs[idx] += left * volume;
-                outputs[idx + 1] += right * volume;
+                outputs[idx] += left * volume * self.gain_left;
+                outputs[idx + 1] += right * volume * self.gain_right;
 
-                self.source_sample_position += self.pitch_ratio;
+                self.advance_position();
             } else {
                 self.active = false;
                 self.adsr.reset();
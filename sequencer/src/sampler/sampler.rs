@@ -1,15 +1,67 @@
-use crate::processor::Processor;
-use crate::midimessage::NoteEvent;
+use crate::processor::{Processor, ProcessorSnapshot};
+use crate::midimessage::{NoteEvent, AutomationPoint, CC_SUSTAIN_PEDAL};
+use crate::sustain_pedal::SustainPedal;
+use crate::utils::{pitch_bend_ratio, DEFAULT_PITCH_BEND_RANGE};
 use crate::sampler::sample::Sample;
 use crate::sampler::sample_voice::SamplerVoice;
-use crate::sampler::sampler_preset::SamplerPreset;
-use crate::preset::Preset;
-use std::rc::Rc;
+use crate::sampler::sampler_preset::{SamplerPreset, SampleInfo, SampleSettings, SampleEnvelope, PlaybackMode, PRESET_FILE_NAME, RECORDINGS_NAME};
+use crate::sampler::sample_loader::{SampleLoader, LoadRequest, LoadResult};
+use crate::sampler::recorder::{Recorder, MAX_DURATION};
+use crate::sampler::slicer::{self, SliceSettings, SlicedLoop, SLICE_MODE_NAMES, SLICE_MODE_TRANSIENTS, SLICE_MODE_GRID, GRID_NAMES, MAX_SLICES, FIRST_SLICE_NOTE};
+use crate::preset::{Preset, MIN_FILTER_Q, MAX_FILTER_Q};
+use crate::load_error::LoadError;
+use crate::parameter::{Parameter, ParameterUnit, FILTER_TYPE_NAMES, filter_type_from_index, filter_type_index};
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const MAX_NOTES : usize = 32;
+const PRESETS_DIRECTORY : &str = "sampler-presets";
+const LOOPS_DIRECTORY : &str = "sampler-loops";
+// Note of the first pad recorded in an empty kit
+const FIRST_RECORDING_NOTE : u8 = 60;
+
+const ATTACK : usize = 0;
+const DECAY : usize = 1;
+const SUSTAIN : usize = 2;
+const RELEASE : usize = 3;
+
+// Parameters of the presets slicing a loop
+const SLICER_PARAMETERS : usize = 4;
+const SLICE_MODE : usize = SLICER_PARAMETERS;
+const SLICE_SENSITIVITY : usize = SLICER_PARAMETERS + 1;
+const SLICE_GRID : usize = SLICER_PARAMETERS + 2;
+const SLICE_POINTS : usize = SLICER_PARAMETERS + 3;
+
+// SAMPLES_PARAMETERS + the pad id * NB_SAMPLE_PARAMETERS + the parameter index in the block,
+// so the automation of a pad keeps its target when pads are added or removed
+const SAMPLES_PARAMETERS : usize = SLICE_POINTS + MAX_SLICES;
+const SAMPLE_GAIN : usize = 0;
+const SAMPLE_PAN : usize = 1;
+const SAMPLE_COARSE : usize = 2;
+const SAMPLE_FINE : usize = 3;
+const SAMPLE_START : usize = 4;
+const SAMPLE_END : usize = 5;
+const SAMPLE_REVERSE : usize = 6;
+const SAMPLE_ATTACK : usize = 7;
+const SAMPLE_DECAY : usize = 8;
+const SAMPLE_SUSTAIN : usize = 9;
+const SAMPLE_RELEASE : usize = 10;
+const SAMPLE_FILTER : usize = 11;
+const SAMPLE_FILTER_TYPE : usize = 12;
+const SAMPLE_FILTER_CUTOFF : usize = 13;
+const SAMPLE_FILTER_Q : usize = 14;
+const SAMPLE_FILTER_ENVELOPE : usize = 15;
+const SAMPLE_PLAYBACK_MODE : usize = 16;
+const NB_SAMPLE_PARAMETERS : usize = 17;
+
+const OFF_ON_NAMES : [&str; 2] = ["Off", "On"];
+const PLAYBACK_MODE_NAMES : [&str; 2] = ["Repitch", "Stretch"];
 
 pub struct Sampler {
-    pub samples: Vec<Rc<Sample>>,
+    pub samples: Vec<Arc<Sample>>,
     voices: Vec<SamplerVoice>,
     nb_actives_notes: usize,
     pub attack: f32,
@@ -18,14 +70,102 @@ pub struct Sampler {
     pub release: f32,
     pub id: usize,
     pub note_events: Vec<NoteEvent>,
+    pub automation_points: Vec<AutomationPoint>,
     pub im_armed: bool,
     presets: Vec<SamplerPreset>,
     preset_id: usize,
+    bend_ratio: f32,
+    sustain_pedal: SustainPedal,
+    errors: Vec<LoadError>,
+    round_robin_positions: HashMap<u8, usize>,
+    // Set once the loop of a sliced preset is cut by the loader
+    sliced_loop: Option<SlicedLoop>,
+    slice_mode: usize,
+    slice_sensitivity: f32,
+    slice_grid: usize,
+    tempo: f32,
+    loader: SampleLoader,
+    // Generation of the last load request, the older results are dropped
+    loading_generation: usize,
+    parameters_have_changed: bool,
+    recorder: Recorder,
+    // Set while the track is armed, the loader is asked for the buffers of the takes
+    is_take_reserved: bool,
+    is_waiting_for_take_buffers: bool,
+    data_path: String,
+    next_pad_id: usize,
+    // Preset id and note of the pads sent to the loader, the next pads go after them
+    pending_pads: Vec<(usize, u8)>,
+}
+
+pub struct SamplerSnapshot {
     sample_rate: f32,
+    samples: Vec<Arc<Sample>>,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    id: usize,
+    note_events: Vec<NoteEvent>,
+    automation_points: Vec<AutomationPoint>,
+    preset: SamplerPreset,
+    bend_ratio: f32,
+    round_robin_positions: HashMap<u8, usize>,
+    sliced_loop: Option<SlicedLoop>,
+    slice_mode: usize,
+    slice_sensitivity: f32,
+    slice_grid: usize,
+    tempo: f32,
+    next_pad_id: usize,
+}
+
+impl ProcessorSnapshot for SamplerSnapshot {
+    fn into_processor(self: Box<Self>) -> Box<dyn Processor> {
+        let mut voices : Vec<SamplerVoice> = Vec::new();
+        for _i in 0..MAX_NOTES {
+            let mut voice = SamplerVoice::new(self.sample_rate);
+            voice.tempo = self.tempo;
+            voices.push(voice);
+        }
+
+        Box::new(Sampler {
+            samples: self.samples,
+            voices,
+            nb_actives_notes: 0,
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+            id: self.id,
+            note_events: self.note_events,
+            automation_points: self.automation_points,
+            im_armed: false,
+            presets: vec![self.preset],
+            preset_id: 0,
+            bend_ratio: self.bend_ratio,
+            sustain_pedal: SustainPedal::new(),
+            errors: Vec::new(),
+            round_robin_positions: self.round_robin_positions,
+            sliced_loop: self.sliced_loop,
+            slice_mode: self.slice_mode,
+            slice_sensitivity: self.slice_sensitivity,
+            slice_grid: self.slice_grid,
+            tempo: self.tempo,
+            loader: SampleLoader::detached(),
+            loading_generation: 0,
+            parameters_have_changed: false,
+            recorder: Recorder::new(),
+            is_take_reserved: false,
+            is_waiting_for_take_buffers: false,
+            data_path: String::new(),
+            next_pad_id: self.next_pad_id,
+            pending_pads: Vec::new(),
+        })
+    }
 }
 
 impl Sampler {
-    pub fn new(sample_rate: f32, preset_id: usize) -> Sampler {
+    pub fn new(sample_rate: f32, preset_id: usize, data_path: &str) -> Sampler {
 
         let mut voices : Vec<SamplerVoice> = Vec::new();
 
@@ -34,7 +174,6 @@ impl Sampler {
         }
 
         let mut sampler = Sampler {
-            sample_rate: sample_rate,
             voices: voices,
             nb_actives_notes: 0,
             samples: Vec::new(),
@@ -44,21 +183,43 @@ impl Sampler {
             release: 0.0,
             id: 0,
             note_events: Vec::with_capacity(100),
+            automation_points: Vec::new(),
             im_armed: false,
             presets: Vec::new(),
             preset_id: preset_id,
+            bend_ratio: 1.,
+            sustain_pedal: SustainPedal::new(),
+            errors: Vec::new(),
+            round_robin_positions: HashMap::new(),
+            sliced_loop: None,
+            slice_mode: SLICE_MODE_TRANSIENTS,
+            slice_sensitivity: 0.5,
+            slice_grid: 2,
+            tempo: 120.,
+            loader: SampleLoader::new(sample_rate),
+            loading_generation: 0,
+            parameters_have_changed: false,
+            recorder: Recorder::new(),
+            is_take_reserved: false,
+            is_waiting_for_take_buffers: false,
+            data_path: data_path.to_string(),
+            next_pad_id: 0,
+            pending_pads: Vec::with_capacity(8),
         };
 
-        let presets = [
-            "./data/sampler-presets/Daz-Funk/preset.json",
-            "./data/sampler-presets/Daz-Kit/preset.json",
-            "./data/sampler-presets/Daz-Special/preset.json",
-        ];
-
-        for preset in presets {
-            let sampler_preset = SamplerPreset::new(preset.to_string());
-            sampler.presets.push(sampler_preset.unwrap());
+        sampler.presets = SamplerPreset::scan(&Path::new(data_path).join(PRESETS_DIRECTORY).to_string_lossy(), &mut sampler.errors);
+        sampler.presets.extend(slicer::scan_loops(&Path::new(data_path).join(LOOPS_DIRECTORY).to_string_lossy()));
+        if sampler.presets.is_empty() {
+            sampler.presets.push(SamplerPreset::empty());
+        }
+        for id in 0..sampler.presets.len() {
+            sampler.presets[id].id = id;
+            for sample_info in sampler.presets[id].samples.iter_mut() {
+                sample_info.pad_id = sampler.next_pad_id;
+                sampler.next_pad_id += 1;
+            }
         }
+        sampler.preset_id = preset_id.min(sampler.presets.len() - 1);
 
         sampler.load_samples();
 
@@ -67,13 +228,293 @@ impl Sampler {
 
     fn load_samples(&mut self) {
         self.all_note_off();
-        self.samples.clear();
-        for sample_info in self.presets[self.preset_id].samples.iter() {
-            let sample = Sample::load_sample(sample_info, self.sample_rate);
-            self.samples.push(Rc::new(sample));
+        self.loader.request(LoadRequest::Drop(std::mem::take(&mut self.samples)));
+        self.round_robin_positions.clear();
+
+        let preset = &self.presets[self.preset_id];
+        self.attack = preset.attack;
+        self.decay = preset.decay;
+        self.sustain = preset.sustain;
+        self.release = preset.release;
+
+        // The pads stay silent until the samples are read
+        self.sliced_loop = None;
+        self.loading_generation += 1;
+        let generation = self.loading_generation;
+        let settings = self.slice_settings();
+        let preset = &mut self.presets[self.preset_id];
+        match preset.loop_filepath.clone() {
+            Some(filepath) => {
+                preset.samples.clear();
+                self.loader.request(LoadRequest::Loop { generation, filepath, settings });
+            },
+            None => self.loader.request(LoadRequest::Kit { generation, samples: preset.samples.clone() }),
+        }
+    }
+
+    fn receive_samples(&mut self) {
+        while let Some(result) = self.loader.try_receive() {
+            match result {
+                LoadResult::Kit { generation, mut samples, errors } => {
+                    self.errors.extend(errors);
+                    if generation == self.loading_generation {
+                        // The pads added while the kit was loading come after it, like in the preset
+                        samples.append(&mut self.samples);
+                        self.samples = samples;
+                        // The positions of the round robin groups are added once, not when a note is played
+                        for s in 0..self.samples.len() {
+                            if let Some(group) = self.samples[s].round_robin_group {
+                                self.round_robin_positions.entry(group).or_insert(0);
+                            }
+                        }
+                        self.parameters_have_changed = true;
+                    } else {
+                        self.loader.request(LoadRequest::Drop(samples));
+                    }
+                },
+                LoadResult::Slices { generation, sliced_loop, samples, is_resliced } => {
+                    if generation == self.loading_generation {
+                        self.receive_slices(sliced_loop, samples, is_resliced);
+                        self.parameters_have_changed = true;
+                    } else {
+                        self.loader.request(LoadRequest::Drop(samples));
+                    }
+                },
+                LoadResult::TakeBuffers(left_channel, right_channel) => {
+                    self.is_waiting_for_take_buffers = false;
+                    if self.is_take_reserved && !self.recorder.has_buffers() {
+                        self.recorder.set_buffers(left_channel, right_channel);
+                    } else {
+                        self.loader.request(LoadRequest::DropTake(left_channel, right_channel));
+                    }
+                },
+                LoadResult::Pad { preset_id, midi_note, pad } => {
+                    self.pending_pads.retain(|pending_pad| *pending_pad != (preset_id, midi_note));
+                    match pad {
+                        Some((sample_info, sample)) => self.receive_pad(preset_id, sample_info, sample),
+                        None => self.errors.push(LoadError::SilentRecording),
+                    }
+                },
+                LoadResult::Error(error) => self.errors.push(error),
+            }
+        }
+    }
+
+    fn add_recording(&mut self) {
+        let is_truncated = self.recorder.is_truncated;
+        let (left_channel, right_channel) = self.recorder.finish();
+        // The buffers go with the take, the next one needs new ones
+        self.request_take_buffers();
+        if is_truncated {
+            self.errors.push(LoadError::TruncatedRecording(MAX_DURATION));
+        }
+        self.add_pad("recording", left_channel, right_channel, true);
+    }
+
+    fn request_take_buffers(&mut self) {
+        if self.is_take_reserved && !self.recorder.has_buffers() && !self.is_waiting_for_take_buffers {
+            self.is_waiting_for_take_buffers = true;
+            self.loader.request(LoadRequest::TakeBuffers);
+        }
+    }
+
+    // True once the loader gave the buffers of the next take
+    pub fn is_take_ready(&self) -> bool {
+        return self.recorder.has_buffers();
+    }
+
+    // New pad on the note after the highest one of the kit, the loader saves its wav next to the preset.json of the kit
+    // and adds it there, the kits without one keep their pads in the recordings kit
+    fn add_pad(&mut self, name: &str, left_channel: Vec<f32>, right_channel: Vec<f32>, is_take: bool) {
+        let preset = &self.presets[self.preset_id];
+        let recordings_filepath = Path::new(&self.data_path).join(PRESETS_DIRECTORY).join(RECORDINGS_NAME).join(PRESET_FILE_NAME);
+        let preset_filepath = preset.filepath.clone().unwrap_or(recordings_filepath.to_string_lossy().to_string());
+        let directory = Path::new(&preset_filepath).parent().map(|parent| parent.to_string_lossy().to_string()).unwrap_or_default();
+        let pending_notes = self.pending_pads.iter().filter(|(preset_id, _)| *preset_id == self.preset_id).map(|(_, midi_note)| *midi_note);
+        let midi_note = match preset.samples.iter().map(|sample_info| sample_info.note_midi_max).chain(pending_notes).max() {
+            Some(note) => note.saturating_add(1).min(127),
+            None => FIRST_RECORDING_NOTE,
+        };
+        // Named after the time and the pad, the file is written later by the loader
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis());
+        let filepath = format!("{}/{}-{}-{}.wav", directory, name, timestamp, midi_note);
+
+        self.pending_pads.push((self.preset_id, midi_note));
+        self.loader.request(LoadRequest::Pad {
+            preset_id: self.preset_id,
+            preset_filepath,
+            sample_info: SampleInfo::new(filepath, midi_note),
+            left_channel,
+            right_channel,
+            is_take,
+        });
+    }
+
+    // A pad of another kit is played once that kit is selected, it's read from its file like the others
+    fn receive_pad(&mut self, preset_id: usize, mut sample_info: SampleInfo, sample: Arc<Sample>) {
+        sample_info.pad_id = self.next_pad_id;
+        self.next_pad_id += 1;
+        self.presets[preset_id].samples.push(sample_info);
+        if preset_id == self.preset_id {
+            self.samples.push(sample);
+            self.parameters_have_changed = true;
+        } else {
+            self.loader.request(LoadRequest::Drop(vec![sample]));
+        }
+    }
+
+    // New loop preset, selected once its wav is saved in the loops directory since it's read from there like the others
+    fn add_loop(&mut self, name: &str, left_channel: Vec<f32>, right_channel: Vec<f32>) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis());
+        let stem = format!("{}-{}", name, timestamp);
+        let filepath = Path::new(&self.data_path).join(LOOPS_DIRECTORY).join(format!("{}.wav", stem)).to_string_lossy().to_string();
+        self.loader.request(LoadRequest::Save { filepath: filepath.clone(), left_channel, right_channel });
+
+        self.presets.push(SamplerPreset {
+            id: self.presets.len(),
+            name: format!("Slices - {}", stem),
+            loop_filepath: Some(filepath),
+            ..SamplerPreset::empty()
+        });
+        self.preset_id = self.presets.len() - 1;
+        self.load_samples();
+    }
+
+    fn slice_settings(&self) -> SliceSettings {
+        SliceSettings {
+            mode: self.slice_mode,
+            sensitivity: self.slice_sensitivity,
+            grid: self.slice_grid,
+            tempo: self.tempo,
+        }
+    }
+
+    // The loader cuts the loop again, the pads are made from the new slices when they come back
+    fn slice_loop(&mut self) {
+        if self.sliced_loop.is_some() {
+            self.loader.request(LoadRequest::Slice { generation: self.loading_generation, settings: self.slice_settings() });
+        }
+    }
+
+    // The point moves at once so it reads back as set, the loader cuts the slices around it
+    fn set_slice_point(&mut self, idx: usize, seconds: f32) {
+        let sliced_loop = match self.sliced_loop.as_mut() {
+            Some(sliced_loop) => sliced_loop,
+            None => return,
+        };
+        let position = (seconds.max(0.) * sliced_loop.sample_rate) as usize;
+        sliced_loop.set_slice_point(idx, position);
+        self.loader.request(LoadRequest::MoveSlicePoint { generation: self.loading_generation, idx, position });
+    }
+
+    // The slices are the first pads, the ones added to the preset come after them and are kept
+    fn receive_slices(&mut self, sliced_loop: SlicedLoop, mut samples: Vec<Arc<Sample>>, is_resliced: bool) {
+        let nb_slices = self.sliced_loop.as_ref().map_or(0, |sliced_loop| sliced_loop.slice_points.len());
+        if is_resliced {
+            self.all_note_off();
+            let preset = &mut self.presets[self.preset_id];
+            let filepath = preset.loop_filepath.clone().unwrap_or_default();
+            let pads = preset.samples.split_off(nb_slices.min(preset.samples.len()));
+            preset.samples.clear();
+            for idx in 0..samples.len() {
+                let mut sample_info = SampleInfo::new(filepath.clone(), FIRST_SLICE_NOTE.saturating_add(idx as u8));
+                sample_info.pad_id = self.next_pad_id;
+                self.next_pad_id += 1;
+                preset.samples.push(sample_info);
+            }
+            preset.samples.extend(pads);
+        }
+        samples.extend(self.samples.drain(nb_slices.min(self.samples.len())..));
+        self.loader.request(LoadRequest::Drop(std::mem::replace(&mut self.samples, samples)));
+        self.sliced_loop = Some(sliced_loop);
+    }
+
+    // First sample matching the note and the velocity, or the next one of its round robin group
+    fn find_sample(&mut self, midi_note: u8, velocity: f32) -> Option<usize> {
+        let midi_velocity = (velocity * 127.).round() as u8;
+        let first_match = self.samples.iter().position(|sample| sample.apply_to_note(midi_note, midi_velocity))?;
+
+        let group = match self.samples[first_match].round_robin_group {
+            Some(group) => group,
+            None => return Some(first_match),
+        };
+
+        let is_candidate = |sample: &Arc<Sample>| sample.round_robin_group == Some(group) && sample.apply_to_note(midi_note, midi_velocity);
+        let nb_candidates = self.samples.iter().filter(|sample| is_candidate(sample)).count();
+
+        let position = match self.round_robin_positions.get_mut(&group) {
+            Some(position) => position,
+            None => return Some(first_match),
+        };
+        let candidate_idx = *position % nb_candidates;
+        *position = candidate_idx + 1;
+
+        return self.samples.iter()
+            .enumerate()
+            .filter(|(_, sample)| is_candidate(sample))
+            .nth(candidate_idx)
+            .map(|(sample_idx, _)| sample_idx);
+    }
+
+    // Index of the pad in the kit and the parameter index in its block
+    fn pad_parameter(&self, id: usize) -> Option<(usize, usize)> {
+        let pad_id = (id - SAMPLES_PARAMETERS) / NB_SAMPLE_PARAMETERS;
+        let sample_idx = self.presets[self.preset_id].samples.iter().position(|sample_info| sample_info.pad_id == pad_id)?;
+        return Some((sample_idx, (id - SAMPLES_PARAMETERS) % NB_SAMPLE_PARAMETERS));
+    }
+
+    fn kit_envelope(&self) -> SampleEnvelope {
+        SampleEnvelope {
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
         }
+    }
 
+    fn set_sample_parameter(&mut self, sample_idx: usize, parameter: usize, value: f32) {
+        let settings = &mut self.presets[self.preset_id].samples[sample_idx].settings;
+        match parameter {
+            SAMPLE_GAIN => settings.gain = value,
+            SAMPLE_PAN => settings.pan = value,
+            SAMPLE_COARSE => settings.coarse = value,
+            SAMPLE_FINE => settings.fine = value,
+            SAMPLE_START => settings.start = value,
+            SAMPLE_END => settings.end = value,
+            SAMPLE_REVERSE => settings.reverse = value >= 0.5,
+            SAMPLE_ATTACK => settings.envelope.attack = Some(value),
+            SAMPLE_DECAY => settings.envelope.decay = Some(value),
+            SAMPLE_SUSTAIN => settings.envelope.sustain = Some(value),
+            SAMPLE_RELEASE => settings.envelope.release = Some(value),
+            SAMPLE_FILTER => settings.filter_enabled = value >= 0.5,
+            SAMPLE_FILTER_TYPE => settings.filter_type = filter_type_from_index(value as usize),
+            SAMPLE_FILTER_CUTOFF => settings.filter_cutoff = value,
+            SAMPLE_FILTER_Q => settings.filter_q = value,
+            SAMPLE_FILTER_ENVELOPE => settings.filter_envelope_amount = value,
+            SAMPLE_PLAYBACK_MODE => settings.playback_mode = if value >= 0.5 { PlaybackMode::Stretch } else { PlaybackMode::Repitch },
+            _ => {}
+        }
 
+        // Voices already playing the sample follow the change
+        let settings = *settings;
+        let sample = match self.samples.get(sample_idx) {
+            Some(sample) => sample,
+            None => return,
+        };
+        sample.follow_settings(&settings);
+        for i in 0..self.nb_actives_notes {
+            if Arc::ptr_eq(&self.voices[i].sample, sample) {
+                self.voices[i].set_settings(settings);
+            }
+        }
+    }
+
+    fn release_note(&mut self, midi_note: u8) {
+        for i in 0..self.voices.len() {
+            if self.voices[i].note_id == midi_note && self.voices[i].active {
+                self.voices[i].stop_note();
+            }
+        }
     }
 }
 
@@ -82,40 +523,65 @@ impl Processor for Sampler {
     fn get_name(&self) -> String { "Sampler".to_string() }
 
     fn note_on(&mut self, midi_note: u8, velocity: f32) {
-        for sample_idx in 0..self.samples.len() {
-            if self.samples[sample_idx].apply_to_note(midi_note) {
-                if self.nb_actives_notes < MAX_NOTES - 1 {
-                    let note_to_active = self.nb_actives_notes as usize;
-                    self.voices[note_to_active].adsr.attack = self.attack;
-                    self.voices[note_to_active].adsr.decay = self.decay;
-                    self.voices[note_to_active].adsr.sustain = self.sustain;
-                    self.voices[note_to_active].adsr.release = self.release;
-                    self.voices[note_to_active].adsr.reset();
-                    self.voices[note_to_active].adsr.recalculate_rates();
-
-                    self.voices[note_to_active].start_note(midi_note, velocity, self.samples[sample_idx].clone());
-                    self.nb_actives_notes += 1;
-                    break;
+        self.sustain_pedal.note_on(midi_note);
+        if let Some(sample_idx) = self.find_sample(midi_note, velocity) {
+            if let Some(choke_group) = self.samples[sample_idx].choke_group {
+                for i in 0..self.nb_actives_notes {
+                    if self.voices[i].sample.choke_group == Some(choke_group) {
+                        self.voices[i].choke();
+                    }
                 }
             }
+            if self.nb_actives_notes < MAX_NOTES - 1 {
+                let note_to_active = self.nb_actives_notes as usize;
+                let settings = self.presets[self.preset_id].samples[sample_idx].settings;
+                let envelope = settings.envelope.apply(self.kit_envelope());
+                self.voices[note_to_active].adsr.attack = envelope.attack;
+                self.voices[note_to_active].adsr.decay = envelope.decay;
+                self.voices[note_to_active].adsr.sustain = envelope.sustain;
+                self.voices[note_to_active].adsr.release = envelope.release;
+                self.voices[note_to_active].adsr.reset();
+                self.voices[note_to_active].adsr.recalculate_rates();
+
+                self.voices[note_to_active].bend_ratio = self.bend_ratio;
+                self.voices[note_to_active].tempo = self.tempo;
+                self.voices[note_to_active].start_note(midi_note, velocity, self.samples[sample_idx].clone(), settings);
+                self.nb_actives_notes += 1;
+            }
         }
     }
    
     fn note_off(&mut self, midi_note: u8) {
-        for i in 0..self.voices.len() {
-            if self.voices[i].note_id == midi_note && self.voices[i].active {
-                self.voices[i].stop_note();
-            }
+        if !self.sustain_pedal.hold_note_off(midi_note) {
+            self.release_note(midi_note);
         }
     }
 
     fn all_note_off(&mut self) {
+        self.sustain_pedal.reset();
         for i in 0..self.voices.len() {
             self.voices[i].stop_note();
         }
     }
 
+    fn pitch_bend(&mut self, value: f32) {
+        self.bend_ratio = pitch_bend_ratio(value, DEFAULT_PITCH_BEND_RANGE);
+        for i in 0..self.nb_actives_notes {
+            self.voices[i].bend_ratio = self.bend_ratio;
+        }
+    }
+
+    fn control_change(&mut self, controller: u8, value: f32) {
+        if controller == CC_SUSTAIN_PEDAL {
+            self.sustain_pedal.set_value(value);
+            while let Some(midi_note) = self.sustain_pedal.next_released_note() {
+                self.release_note(midi_note);
+            }
+        }
+    }
+
     fn process(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize) {
+        self.receive_samples();
         for i in 0..self.nb_actives_notes {
             let i: usize = i as usize;
             if !self.voices[i].is_ended() {
@@ -140,6 +606,18 @@ impl Processor for Sampler {
         self.note_events.push(midi_message);
     }
 
+    fn get_errors(&mut self) -> &mut Vec<LoadError> {
+        return &mut self.errors;
+    }
+
+    fn get_automation_points(&mut self) -> &mut Vec<AutomationPoint> {
+        return &mut self.automation_points;
+    }
+
+    fn add_automation_point(&mut self, automation_point: AutomationPoint) {
+        self.automation_points.push(automation_point);
+    }
+
     fn get_current_preset_id(&self) -> usize {
         self.preset_id
     }
@@ -159,6 +637,216 @@ impl Processor for Sampler {
         return presets;
     }
 
+    fn get_parameters(&self) -> Vec<Parameter> {
+        let mut parameters = vec![
+            Parameter::new(ATTACK, "Attack", 0., 10., 0., 0.001, ParameterUnit::Seconds),
+            Parameter::new(DECAY, "Decay", 0., 10., 0., 0.001, ParameterUnit::Seconds),
+            Parameter::new(SUSTAIN, "Sustain", 0., 1., 1., 0.01, ParameterUnit::Percent),
+            Parameter::new(RELEASE, "Release", 0., 10., 0., 0.001, ParameterUnit::Seconds),
+        ];
+
+        if let Some(sliced_loop) = &self.sliced_loop {
+            parameters.push(Parameter::choice(SLICE_MODE, "Slice mode", &SLICE_MODE_NAMES, SLICE_MODE_TRANSIENTS));
+            parameters.push(Parameter::new(SLICE_SENSITIVITY, "Sensitivity", 0., 1., 0.5, 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::choice(SLICE_GRID, "Grid", &GRID_NAMES, 2));
+            for idx in 0..sliced_loop.slice_points.len() {
+                let name = format!("Slice {} start", idx + 1);
+                parameters.push(Parameter::new(SLICE_POINTS + idx, &name, 0., sliced_loop.duration(), 0., 0.001, ParameterUnit::SlicePoint));
+            }
+        }
+
+        let default = SampleSettings::default();
+        let kit_envelope = self.kit_envelope();
+        for (sample_idx, sample_info) in self.presets[self.preset_id].samples.iter().enumerate() {
+            let id = SAMPLES_PARAMETERS + sample_info.pad_id * NB_SAMPLE_PARAMETERS;
+            let sample_name = match self.sliced_loop {
+                Some(_) => format!("Slice {}", sample_idx + 1),
+                None => Path::new(&sample_info.filepath).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            };
+            let name = |parameter_name: &str| format!("{} {}", sample_name, parameter_name);
+
+            parameters.push(Parameter::new(id + SAMPLE_GAIN, &name("gain"), -48., 12., default.gain, 0.5, ParameterUnit::Decibels));
+            parameters.push(Parameter::new(id + SAMPLE_PAN, &name("pan"), -1., 1., default.pan, 0.05, ParameterUnit::None));
+            parameters.push(Parameter::new(id + SAMPLE_COARSE, &name("coarse"), -24., 24., default.coarse, 1., ParameterUnit::Semitones));
+            parameters.push(Parameter::new(id + SAMPLE_FINE, &name("fine"), -100., 100., default.fine, 1., ParameterUnit::Cents));
+            parameters.push(Parameter::new(id + SAMPLE_START, &name("start"), 0., 1., default.start, 0.005, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + SAMPLE_END, &name("end"), 0., 1., default.end, 0.005, ParameterUnit::Percent));
+            parameters.push(Parameter::choice(id + SAMPLE_REVERSE, &name("reverse"), &OFF_ON_NAMES, default.reverse as usize));
+            parameters.push(Parameter::new(id + SAMPLE_ATTACK, &name("attack"), 0., 10., kit_envelope.attack, 0.001, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + SAMPLE_DECAY, &name("decay"), 0., 10., kit_envelope.decay, 0.001, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + SAMPLE_SUSTAIN, &name("sustain"), 0., 1., kit_envelope.sustain, 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + SAMPLE_RELEASE, &name("release"), 0., 10., kit_envelope.release, 0.001, ParameterUnit::Seconds));
+            parameters.push(Parameter::choice(id + SAMPLE_FILTER, &name("filter"), &OFF_ON_NAMES, default.filter_enabled as usize));
+            parameters.push(Parameter::choice(id + SAMPLE_FILTER_TYPE, &name("filter type"), &FILTER_TYPE_NAMES, filter_type_index(default.filter_type)));
+            parameters.push(Parameter::new(id + SAMPLE_FILTER_CUTOFF, &name("filter cutoff"), 20., 20000., default.filter_cutoff, 10., ParameterUnit::Hertz));
+            parameters.push(Parameter::new(id + SAMPLE_FILTER_Q, &name("filter Q"), MIN_FILTER_Q, MAX_FILTER_Q, default.filter_q, 0.01, ParameterUnit::None));
+            parameters.push(Parameter::new(id + SAMPLE_FILTER_ENVELOPE, &name("filter env"), -96., 96., default.filter_envelope_amount, 1., ParameterUnit::Semitones));
+            parameters.push(Parameter::choice(id + SAMPLE_PLAYBACK_MODE, &name("tempo sync"), &PLAYBACK_MODE_NAMES, (default.playback_mode == PlaybackMode::Stretch) as usize));
+        }
+
+        return parameters;
+    }
+
+    fn get_parameter(&self, id: usize) -> f32 {
+        if id >= SAMPLES_PARAMETERS {
+            let (sample_idx, parameter) = match self.pad_parameter(id) {
+                Some(pad_parameter) => pad_parameter,
+                None => return 0.,
+            };
+            let settings = self.presets[self.preset_id].samples[sample_idx].settings;
+            let envelope = settings.envelope.apply(self.kit_envelope());
+            return match parameter {
+                SAMPLE_GAIN => settings.gain,
+                SAMPLE_PAN => settings.pan,
+                SAMPLE_COARSE => settings.coarse,
+                SAMPLE_FINE => settings.fine,
+                SAMPLE_START => settings.start,
+                SAMPLE_END => settings.end,
+                SAMPLE_REVERSE => settings.reverse as usize as f32,
+                SAMPLE_ATTACK => envelope.attack,
+                SAMPLE_DECAY => envelope.decay,
+                SAMPLE_SUSTAIN => envelope.sustain,
+                SAMPLE_RELEASE => envelope.release,
+                SAMPLE_FILTER => settings.filter_enabled as usize as f32,
+                SAMPLE_FILTER_TYPE => filter_type_index(settings.filter_type) as f32,
+                SAMPLE_FILTER_CUTOFF => settings.filter_cutoff,
+                SAMPLE_FILTER_Q => settings.filter_q,
+                SAMPLE_FILTER_ENVELOPE => settings.filter_envelope_amount,
+                SAMPLE_PLAYBACK_MODE => (settings.playback_mode == PlaybackMode::Stretch) as usize as f32,
+                _ => 0.,
+            };
+        }
+
+        if id >= SLICER_PARAMETERS {
+            return match id {
+                SLICE_MODE => self.slice_mode as f32,
+                SLICE_SENSITIVITY => self.slice_sensitivity,
+                SLICE_GRID => self.slice_grid as f32,
+                _ => match &self.sliced_loop {
+                    Some(sliced_loop) => sliced_loop.slice_points.get(id - SLICE_POINTS).map_or(0., |point| *point as f32 / sliced_loop.sample_rate),
+                    None => 0.,
+                },
+            };
+        }
+
+        match id {
+            ATTACK => self.attack,
+            DECAY => self.decay,
+            SUSTAIN => self.sustain,
+            RELEASE => self.release,
+            _ => 0.,
+        }
+    }
+
+    fn set_parameter(&mut self, id: usize, value: f32) {
+        if id >= SAMPLES_PARAMETERS {
+            if let Some((sample_idx, parameter)) = self.pad_parameter(id) {
+                self.set_sample_parameter(sample_idx, parameter, value);
+            }
+            return;
+        }
+
+        if id >= SLICER_PARAMETERS {
+            match id {
+                SLICE_MODE => { self.slice_mode = value as usize; self.slice_loop() },
+                SLICE_SENSITIVITY => { self.slice_sensitivity = value; self.slice_loop() },
+                SLICE_GRID => { self.slice_grid = value as usize; self.slice_loop() },
+                _ => self.set_slice_point(id - SLICE_POINTS, value),
+            }
+            return;
+        }
+
+        let preset = &mut self.presets[self.preset_id];
+        match id {
+            ATTACK => { self.attack = value; preset.attack = value },
+            DECAY => { self.decay = value; preset.decay = value },
+            SUSTAIN => { self.sustain = value; preset.sustain = value },
+            RELEASE => { self.release = value; preset.release = value },
+            _ => {}
+        }
+    }
+
+    fn save_current_preset(&mut self) -> Result<(), Box<dyn Error>> {
+        Err("Sampler presets are saved from their preset.json".into())
+    }
+
+    fn have_parameters_changed(&mut self) -> bool {
+        // Polled even when the sampler isn't processed, like when its track is frozen
+        self.receive_samples();
+        let have_changed = self.parameters_have_changed;
+        self.parameters_have_changed = false;
+        return have_changed;
+    }
+
+    fn add_audio(&mut self, name: &str, left_channel: &mut Vec<f32>, right_channel: &mut Vec<f32>, sliced: bool) -> Result<(), Box<dyn Error>> {
+        if sliced {
+            self.add_loop(name, std::mem::take(left_channel), std::mem::take(right_channel));
+        } else {
+            self.add_pad(name, std::mem::take(left_channel), std::mem::take(right_channel), false);
+        }
+        return Ok(());
+    }
+
+    fn snapshot(&self) -> Box<dyn ProcessorSnapshot> {
+        Box::new(SamplerSnapshot {
+            sample_rate: self.voices[0].sample_rate,
+            samples: self.samples.clone(),
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+            id: self.id,
+            note_events: self.note_events.clone(),
+            automation_points: self.automation_points.clone(),
+            preset: self.presets[self.preset_id].clone(),
+            bend_ratio: self.bend_ratio,
+            round_robin_positions: self.round_robin_positions.clone(),
+            sliced_loop: self.sliced_loop.clone(),
+            slice_mode: self.slice_mode,
+            slice_sensitivity: self.slice_sensitivity,
+            slice_grid: self.slice_grid,
+            tempo: self.tempo,
+            next_pad_id: self.next_pad_id,
+        })
+    }
+
+    fn set_armed(&mut self, armed: bool) {
+        if armed && !self.im_armed {
+            self.recorder.start();
+        } else if !armed && self.im_armed {
+            self.add_recording();
+        }
+        self.im_armed = armed;
+    }
+
+    fn reserve_take(&mut self, reserved: bool) {
+        self.is_take_reserved = reserved;
+        if reserved {
+            self.request_take_buffers();
+        } else if !self.recorder.is_recording && self.recorder.has_buffers() {
+            let (left_channel, right_channel) = self.recorder.finish();
+            self.loader.request(LoadRequest::DropTake(left_channel, right_channel));
+        }
+    }
+
+    fn record_input(&mut self, inputs: &[f32], num_samples: usize, nb_channels: usize) {
+        if self.im_armed {
+            self.recorder.push(inputs, num_samples, nb_channels);
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        if self.tempo != bpm {
+            self.tempo = bpm;
+            for i in 0..self.voices.len() {
+                self.voices[i].tempo = bpm;
+            }
+            if self.slice_mode == SLICE_MODE_GRID {
+                self.slice_loop();
+            }
+        }
+    }
+
     fn prepare(&mut self, _sample_rate: f32, _num_samples: usize, _nb_channels: usize) {
         
     }
@@ -1,10 +1,12 @@
-use crate::preset::Preset;
+use crate::preset::{Preset, read_json_preset, write_atomically};
+use crate::load_error::LoadError;
+use crate::sampler::sfz;
+use crate::sampler::sf2::{self, Sf2Sample};
 
-use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize};
+use biquad::Type;
 
 #[derive(Clone, Deserialize)]
 pub struct SampleInfo {
@@ -13,6 +15,173 @@ pub struct SampleInfo {
     pub note_midi_max: u8,
     pub filepath: String,
     pub is_one_shot: bool,
+    // Velocity layers, from 0 to 127
+    #[serde(default)]
+    pub velocity_min: u8,
+    #[serde(default = "default_velocity_max")]
+    pub velocity_max: u8,
+    // Samples of the same group matching a note are played one after the other
+    #[serde(default)]
+    pub round_robin_group: Option<u8>,
+    // Triggering a sample of a group fades out the other voices of that group
+    #[serde(default)]
+    pub choke_group: Option<u8>,
+    // Loop points in frames of the file, the loop end defaults to the end of the sample
+    #[serde(default)]
+    pub loop_mode: LoopMode,
+    #[serde(default)]
+    pub loop_start: usize,
+    #[serde(default)]
+    pub loop_end: Option<usize>,
+    #[serde(default)]
+    pub loop_crossfade: usize,
+    // Tempo the sample was recorded at, it follows the tempo of the project when set
+    #[serde(default)]
+    pub original_bpm: Option<f32>,
+    #[serde(flatten)]
+    pub settings: SampleSettings,
+    // Set for the samples stored in a soundfont, the filepath being the sf2 file
+    #[serde(skip)]
+    pub sf2_sample: Option<Sf2Sample>,
+    // Unique in the sampler, the parameters of a pad keep their ids when the other pads change
+    #[serde(skip)]
+    pub pad_id: usize,
+}
+
+// Adjustments balancing the pads of a kit, they can be changed while playing
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SampleSettings {
+    pub gain: f32,
+    // -1 is full left, 1 is full right
+    pub pan: f32,
+    pub coarse: f32,
+    pub fine: f32,
+    // Start and end of the played part, relative to the sample length
+    pub start: f32,
+    pub end: f32,
+    pub reverse: bool,
+    // How the samples with an original tempo follow the tempo of the project
+    pub playback_mode: PlaybackMode,
+    // Fields of the kit envelope replaced for this sample, the others follow the kit
+    pub envelope: EnvelopeOverride,
+    pub filter_enabled: bool,
+    #[serde(with = "crate::preset::filter_type_serde")]
+    pub filter_type: Type<f32>,
+    pub filter_cutoff: f32,
+    #[serde(deserialize_with = "crate::preset::filter_q_serde::deserialize")]
+    pub filter_q: f32,
+    // Semitones added to the cutoff when the envelope is at its maximum
+    pub filter_envelope_amount: f32,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub struct SampleEnvelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct EnvelopeOverride {
+    pub attack: Option<f32>,
+    pub decay: Option<f32>,
+    pub sustain: Option<f32>,
+    pub release: Option<f32>,
+}
+
+impl EnvelopeOverride {
+    pub fn all(envelope: SampleEnvelope) -> EnvelopeOverride {
+        EnvelopeOverride {
+            attack: Some(envelope.attack),
+            decay: Some(envelope.decay),
+            sustain: Some(envelope.sustain),
+            release: Some(envelope.release),
+        }
+    }
+
+    pub fn apply(&self, kit_envelope: SampleEnvelope) -> SampleEnvelope {
+        SampleEnvelope {
+            attack: self.attack.unwrap_or(kit_envelope.attack),
+            decay: self.decay.unwrap_or(kit_envelope.decay),
+            sustain: self.sustain.unwrap_or(kit_envelope.sustain),
+            release: self.release.unwrap_or(kit_envelope.release),
+        }
+    }
+}
+
+impl Default for SampleSettings {
+    fn default() -> Self {
+        SampleSettings {
+            gain: 0.,
+            pan: 0.,
+            coarse: 0.,
+            fine: 0.,
+            start: 0.,
+            end: 1.,
+            reverse: false,
+            playback_mode: PlaybackMode::Repitch,
+            envelope: EnvelopeOverride::default(),
+            filter_enabled: false,
+            filter_type: Type::LowPass,
+            filter_cutoff: 20000.,
+            filter_q: biquad::Q_BUTTERWORTH_F32,
+            filter_envelope_amount: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    #[default]
+    None,
+    // Loops until the note is released, then plays on to the end of the sample
+    Forward,
+    PingPong,
+    // Forward loop that keeps looping through the release
+    Continuous,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    // Played faster or slower, the pitch changes with the tempo
+    #[default]
+    Repitch,
+    // Time-stretched, the pitch stays the same
+    Stretch,
+}
+
+fn default_velocity_max() -> u8 {
+    127
+}
+
+impl SampleInfo {
+    // One shot sample played by a single note
+    pub fn new(filepath: String, midi_note: u8) -> SampleInfo {
+        SampleInfo {
+            root_midi_note: midi_note,
+            note_midi_min: midi_note,
+            note_midi_max: midi_note,
+            filepath,
+            is_one_shot: true,
+            velocity_min: 0,
+            velocity_max: default_velocity_max(),
+            round_robin_group: None,
+            choke_group: None,
+            loop_mode: LoopMode::None,
+            loop_start: 0,
+            loop_end: None,
+            loop_crossfade: 0,
+            original_bpm: None,
+            settings: SampleSettings::default(),
+            sf2_sample: None,
+            pad_id: 0,
+        }
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -24,26 +193,126 @@ pub struct SamplerPreset {
     pub decay: f32,
     pub sustain: f32,
     pub release: f32,
+    // Set for the presets slicing a loop, their samples are made when they are selected
+    #[serde(skip)]
+    pub loop_filepath: Option<String>,
+    // preset.json of the kit, the recorded pads are saved next to it and added to it
+    #[serde(skip)]
+    pub filepath: Option<String>,
 }
 
+pub const PRESET_FILE_NAME : &str = "preset.json";
+// Name of the kit made for the pads recorded in the kits without a preset.json
+pub const RECORDINGS_NAME : &str = "Recordings";
+
 impl SamplerPreset {
-    pub fn new(filepath: String) -> Result<SamplerPreset, Box<dyn Error>>  {
+    pub fn empty() -> SamplerPreset {
+        SamplerPreset {
+            id: 0,
+            name: "Empty".to_string(),
+            samples: Vec::new(),
+            attack: 0.,
+            decay: 0.,
+            sustain: 1.,
+            release: 0.,
+            loop_filepath: None,
+            filepath: None,
+        }
+    }
+
+    // Every preset.json, sfz and sf2 file under the presets path sorted by name, the broken ones are reported and skipped
+    pub fn scan(presets_path: &str, errors: &mut Vec<LoadError>) -> Vec<SamplerPreset> {
+        let mut filepaths = Vec::new();
+        find_preset_files(Path::new(presets_path), &mut filepaths);
 
-        let parent_path = std::path::Path::new(&filepath).parent().unwrap().to_str().unwrap();
+        let mut presets = Vec::new();
+        for filepath in filepaths {
+            let file_presets = if sf2::is_sf2_file(&filepath) {
+                sf2::load_sf2(&filepath.to_string_lossy(), errors)
+            } else if sfz::is_sfz_file(&filepath) {
+                sfz::load_sfz(&filepath.to_string_lossy(), errors).map(|preset| vec![preset])
+            } else {
+                SamplerPreset::new(filepath.to_string_lossy().to_string()).map(|preset| vec![preset])
+            };
+            match file_presets {
+                Ok(file_presets) => presets.extend(file_presets),
+                Err(error) => errors.push(error),
+            }
+        }
 
-        let file = File::open(&filepath)?;
-        let reader = BufReader::new(file);
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        for (id, preset) in presets.iter_mut().enumerate() {
+            preset.id = id;
+        }
+        return presets;
+    }
 
-        let mut sampler_preset: SamplerPreset = serde_json::from_reader(reader)?;
+    pub fn new(filepath: String) -> Result<SamplerPreset, LoadError>  {
+
+        let parent_path = match Path::new(&filepath).parent() {
+            Some(parent_path) => parent_path.to_string_lossy().to_string(),
+            None => return Err(LoadError::InvalidPath(filepath)),
+        };
+
+        let mut sampler_preset: SamplerPreset = read_json_preset(Path::new(&filepath))?;
 
         for sample in sampler_preset.samples.iter_mut() {
-            sample.filepath = parent_path.to_string() + "/" + &sample.filepath.to_string();
+            sample.filepath = parent_path.clone() + "/" + &sample.filepath.to_string();
         }
+        sampler_preset.filepath = Some(filepath);
 
         Ok(sampler_preset)
     }
 }
 
+// Adds a recorded pad to a preset.json, which is made if it doesn't exist yet like the one of the recordings kit,
+// the pad goes on the note after the last one when its note is already played by the kit
+pub fn save_pad(preset_filepath: &str, sample_info: &SampleInfo) -> Result<(), LoadError> {
+    let path = Path::new(preset_filepath);
+    let mut preset: serde_json::Value = if path.exists() {
+        read_json_preset(path)?
+    } else {
+        serde_json::json!({ "name": RECORDINGS_NAME, "id": 0, "attack": 0., "decay": 0., "sustain": 1., "release": 0., "samples": [] })
+    };
+    let samples = match preset.get_mut("samples").and_then(|samples| samples.as_array_mut()) {
+        Some(samples) => samples,
+        None => return Err(LoadError::Write(preset_filepath.to_string(), std::io::Error::new(std::io::ErrorKind::InvalidData, "no samples in the preset"))),
+    };
+
+    let notes: Vec<u64> = samples.iter().filter_map(|sample| sample.get("note_midi_max").and_then(|note| note.as_u64())).collect();
+    let mut midi_note = sample_info.root_midi_note as u64;
+    if notes.contains(&midi_note) {
+        midi_note = (notes.iter().max().unwrap_or(&0) + 1).min(127);
+    }
+    let file_name = Path::new(&sample_info.filepath).file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_default();
+    samples.push(serde_json::json!({
+        "root_midi_note": midi_note,
+        "note_midi_min": midi_note,
+        "note_midi_max": midi_note,
+        "filepath": file_name,
+        "is_one_shot": sample_info.is_one_shot,
+    }));
+
+    let json = serde_json::to_vec_pretty(&preset).map_err(|error| LoadError::Json(preset_filepath.to_string(), error))?;
+    return write_atomically(preset_filepath, &json);
+}
+
+fn find_preset_files(directory: &Path, filepaths: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            find_preset_files(&path, filepaths);
+        } else if path.file_name().map_or(false, |file_name| file_name == PRESET_FILE_NAME) || sfz::is_sfz_file(&path) || sf2::is_sf2_file(&path) {
+            filepaths.push(path);
+        }
+    }
+}
+
 impl Preset for SamplerPreset {
     fn get_id(self) -> usize {
         return self.id;
@@ -0,0 +1,382 @@
+use crate::load_error::LoadError;
+use crate::sampler::sampler_preset::{SamplerPreset, SampleInfo, SampleSettings, SampleEnvelope, EnvelopeOverride, LoopMode};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Generators of the SoundFont 2.04 specification
+const START_ADDRS_OFFSET : u16 = 0;
+const END_ADDRS_OFFSET : u16 = 1;
+const STARTLOOP_ADDRS_OFFSET : u16 = 2;
+const ENDLOOP_ADDRS_OFFSET : u16 = 3;
+const START_ADDRS_COARSE_OFFSET : u16 = 4;
+const END_ADDRS_COARSE_OFFSET : u16 = 12;
+const PAN : u16 = 17;
+const ATTACK_VOL_ENV : u16 = 34;
+const DECAY_VOL_ENV : u16 = 36;
+const SUSTAIN_VOL_ENV : u16 = 37;
+const RELEASE_VOL_ENV : u16 = 38;
+const INSTRUMENT : u16 = 41;
+const KEY_RANGE : u16 = 43;
+const VEL_RANGE : u16 = 44;
+const STARTLOOP_ADDRS_COARSE_OFFSET : u16 = 45;
+const INITIAL_ATTENUATION : u16 = 48;
+const ENDLOOP_ADDRS_COARSE_OFFSET : u16 = 50;
+const COARSE_TUNE : u16 = 51;
+const FINE_TUNE : u16 = 52;
+const SAMPLE_ID : u16 = 53;
+const SAMPLE_MODES : u16 = 54;
+const EXCLUSIVE_CLASS : u16 = 57;
+const OVERRIDING_ROOT_KEY : u16 = 58;
+
+// Envelope times are in timecents, -12000 being about 1ms
+const DEFAULT_TIMECENTS : i16 = -12000;
+
+const RIGHT_SAMPLE : u16 = 2;
+const LEFT_SAMPLE : u16 = 4;
+const ROM_SAMPLE : u16 = 0x8000;
+
+const PHDR_SIZE : usize = 38;
+const INST_SIZE : usize = 22;
+const BAG_SIZE : usize = 4;
+const GEN_SIZE : usize = 4;
+const SHDR_SIZE : usize = 46;
+
+// Where the 16 bits frames of a sample are in the sf2 file
+#[derive(Clone, Copy)]
+pub struct Sf2Sample {
+    pub data_offset: u64,
+    pub right_data_offset: Option<u64>,
+    pub length: usize,
+    pub sample_rate: u32,
+}
+
+type Generators = HashMap<u16, i16>;
+
+struct SampleHeader {
+    start: u32,
+    end: u32,
+    start_loop: u32,
+    end_loop: u32,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+    sample_link: u16,
+    sample_type: u16,
+}
+
+pub fn is_sf2_file(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("sf2"))
+}
+
+// Every preset of the bank becomes a sampler preset, the samples stay in the file until the preset is selected
+pub fn load_sf2(filepath: &str, errors: &mut Vec<LoadError>) -> Result<Vec<SamplerPreset>, LoadError> {
+    let invalid = |message: &str| LoadError::Sf2(filepath.to_string(), message.to_string());
+    let io_error = |error: std::io::Error| LoadError::Io(filepath.to_string(), error);
+
+    let mut file = File::open(filepath).map_err(io_error)?;
+    let mut riff_header = [0u8; 12];
+    file.read_exact(&mut riff_header).map_err(io_error)?;
+    if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"sfbk" {
+        return Err(invalid("not a soundfont"));
+    }
+
+    let mut smpl_offset = None;
+    let mut pdta = None;
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let chunk_size = read_u32(&chunk_header, 4) as u64;
+        let chunk_start = file.stream_position().map_err(io_error)?;
+
+        if &chunk_header[0..4] == b"LIST" {
+            let mut list_type = [0u8; 4];
+            file.read_exact(&mut list_type).map_err(io_error)?;
+            if &list_type == b"sdta" {
+                smpl_offset = find_smpl_chunk(&mut file, chunk_start + chunk_size).map_err(io_error)?;
+            } else if &list_type == b"pdta" {
+                let mut data = vec![0u8; chunk_size.saturating_sub(4) as usize];
+                file.read_exact(&mut data).map_err(io_error)?;
+                pdta = Some(data);
+            }
+        }
+        // Chunks are padded to an even size
+        file.seek(SeekFrom::Start(chunk_start + chunk_size + chunk_size % 2)).map_err(io_error)?;
+    }
+
+    let smpl_offset = smpl_offset.ok_or_else(|| invalid("no sample data"))?;
+    let pdta = pdta.ok_or_else(|| invalid("no preset data"))?;
+    let chunks = sub_chunks(&pdta);
+    let chunk = |id: &[u8; 4], record_size: usize| -> Result<&[u8], LoadError> {
+        match chunks.get(id) {
+            Some(data) if data.len() >= record_size * 2 => Ok(data),
+            _ => Err(invalid(&format!("missing {} chunk", String::from_utf8_lossy(id)))),
+        }
+    };
+
+    let phdr = chunk(b"phdr", PHDR_SIZE)?;
+    let pbag = chunk(b"pbag", BAG_SIZE)?;
+    let pgen = chunk(b"pgen", GEN_SIZE)?;
+    let inst = chunk(b"inst", INST_SIZE)?;
+    let ibag = chunk(b"ibag", BAG_SIZE)?;
+    let igen = chunk(b"igen", GEN_SIZE)?;
+    let shdr = chunk(b"shdr", SHDR_SIZE)?;
+
+    let sample_headers: Vec<SampleHeader> = shdr.chunks_exact(SHDR_SIZE).map(|record| SampleHeader {
+        start: read_u32(record, 20),
+        end: read_u32(record, 24),
+        start_loop: read_u32(record, 28),
+        end_loop: read_u32(record, 32),
+        sample_rate: read_u32(record, 36),
+        original_pitch: record[40],
+        pitch_correction: record[41] as i8,
+        sample_link: read_u16(record, 42),
+        sample_type: read_u16(record, 44),
+    }).collect();
+
+    // The last record of each list only marks the end of the previous one
+    let nb_instruments = inst.len() / INST_SIZE - 1;
+    let instrument_zones: Vec<Vec<Generators>> = (0..nb_instruments).map(|i| {
+        zones(inst, INST_SIZE, 20, i, ibag, igen, SAMPLE_ID)
+    }).collect();
+
+    let name = Path::new(filepath).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let mut has_rom_samples = false;
+    let mut presets = Vec::new();
+
+    for p in 0..(phdr.len() / PHDR_SIZE - 1) {
+        let record = &phdr[p * PHDR_SIZE..(p + 1) * PHDR_SIZE];
+        let mut samples = Vec::new();
+
+        for preset_zone in zones(phdr, PHDR_SIZE, 24, p, pbag, pgen, INSTRUMENT) {
+            let instrument = match preset_zone.get(&INSTRUMENT) {
+                Some(instrument) => *instrument as u16 as usize,
+                None => continue,
+            };
+            for instrument_zone in instrument_zones.get(instrument).map(|zones| zones.as_slice()).unwrap_or(&[]) {
+                let sample_id = match instrument_zone.get(&SAMPLE_ID) {
+                    Some(sample_id) => *sample_id as u16 as usize,
+                    None => continue,
+                };
+                let header = match sample_headers.get(sample_id) {
+                    Some(header) => header,
+                    None => continue,
+                };
+                if header.sample_type & ROM_SAMPLE != 0 {
+                    has_rom_samples = true;
+                    continue;
+                }
+                // The right sample of a stereo pair is read with its left sample
+                let is_linked = |link: u16, sample_type: u16| sample_headers.get(link as usize).map_or(false, |linked| linked.sample_type & sample_type != 0);
+                if header.sample_type & RIGHT_SAMPLE != 0 && is_linked(header.sample_link, LEFT_SAMPLE) {
+                    continue;
+                }
+                let right_header = if header.sample_type & LEFT_SAMPLE != 0 && is_linked(header.sample_link, RIGHT_SAMPLE) {
+                    sample_headers.get(header.sample_link as usize)
+                } else {
+                    None
+                };
+
+                if let Some(sample_info) = zone_to_sample_info(filepath, smpl_offset, &preset_zone, instrument_zone, header, right_header) {
+                    samples.push(sample_info);
+                }
+            }
+        }
+
+        presets.push(SamplerPreset {
+            id: 0,
+            name: format!("{} - {}", name, read_name(&record[0..20])),
+            samples,
+            attack: 0.,
+            decay: 0.,
+            sustain: 1.,
+            release: timecents_to_seconds(DEFAULT_TIMECENTS),
+            loop_filepath: None,
+            filepath: None,
+        });
+    }
+
+    if has_rom_samples {
+        errors.push(invalid("ROM samples are not supported"));
+    }
+
+    return Ok(presets);
+}
+
+// Reads the frames of a sample, the mono ones are copied to both channels
+pub fn read_sample(filepath: &str, sample: &Sf2Sample) -> Result<(Vec<f32>, Vec<f32>), LoadError> {
+    let mut file = File::open(filepath).map_err(|error| LoadError::Io(filepath.to_string(), error))?;
+    let left_channel = read_frames(&mut file, sample.data_offset, sample.length)
+        .map_err(|error| LoadError::Io(filepath.to_string(), error))?;
+    let right_channel = match sample.right_data_offset {
+        Some(right_data_offset) => read_frames(&mut file, right_data_offset, sample.length)
+            .map_err(|error| LoadError::Io(filepath.to_string(), error))?,
+        None => left_channel.clone(),
+    };
+    return Ok((left_channel, right_channel));
+}
+
+fn read_frames(file: &mut File, offset: u64, length: usize) -> std::io::Result<Vec<f32>> {
+    let mut bytes = vec![0u8; length * 2];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes.chunks_exact(2).map(|frame| i16::from_le_bytes([frame[0], frame[1]]) as f32 / 32768.).collect())
+}
+
+fn find_smpl_chunk(file: &mut File, list_end: u64) -> std::io::Result<Option<u64>> {
+    let mut chunk_header = [0u8; 8];
+    while file.stream_position()? + 8 <= list_end {
+        file.read_exact(&mut chunk_header)?;
+        let chunk_size = read_u32(&chunk_header, 4) as u64;
+        let chunk_start = file.stream_position()?;
+        if &chunk_header[0..4] == b"smpl" {
+            return Ok(Some(chunk_start));
+        }
+        file.seek(SeekFrom::Start(chunk_start + chunk_size + chunk_size % 2))?;
+    }
+    Ok(None)
+}
+
+fn sub_chunks(data: &[u8]) -> HashMap<[u8; 4], &[u8]> {
+    let mut chunks = HashMap::new();
+    let mut position = 0;
+    while position + 8 <= data.len() {
+        let id = [data[position], data[position + 1], data[position + 2], data[position + 3]];
+        let size = read_u32(data, position + 4) as usize;
+        let end = (position + 8 + size).min(data.len());
+        chunks.insert(id, &data[position + 8..end]);
+        position = end + size % 2;
+    }
+    return chunks;
+}
+
+// Generators of each zone of a preset or an instrument, the global zone being merged into the others
+fn zones(headers: &[u8], header_size: usize, bag_index_offset: usize, index: usize, bags: &[u8], generators: &[u8], terminal: u16) -> Vec<Generators> {
+    let first_bag = read_u16(headers, index * header_size + bag_index_offset) as usize;
+    let last_bag = read_u16(headers, (index + 1) * header_size + bag_index_offset) as usize;
+
+    let mut global = Generators::new();
+    let mut zones = Vec::new();
+    for bag in first_bag..last_bag.min(bags.len() / BAG_SIZE - 1) {
+        let first_generator = read_u16(bags, bag * BAG_SIZE) as usize;
+        let last_generator = (read_u16(bags, (bag + 1) * BAG_SIZE) as usize).min(generators.len() / GEN_SIZE);
+
+        let mut zone = Generators::new();
+        for generator in first_generator..last_generator {
+            let operator = read_u16(generators, generator * GEN_SIZE);
+            zone.insert(operator, read_u16(generators, generator * GEN_SIZE + 2) as i16);
+        }
+
+        if zone.contains_key(&terminal) {
+            let mut merged = global.clone();
+            merged.extend(zone);
+            zones.push(merged);
+        } else if bag == first_bag {
+            global = zone;
+        }
+    }
+    return zones;
+}
+
+fn zone_to_sample_info(filepath: &str, smpl_offset: u64, preset_zone: &Generators, instrument_zone: &Generators, header: &SampleHeader, right_header: Option<&SampleHeader>) -> Option<SampleInfo> {
+    let instrument_value = |operator: u16, default: i16| *instrument_zone.get(&operator).unwrap_or(&default) as i32;
+    // Preset generators are added to the instrument ones
+    let value = |operator: u16, default: i16| instrument_value(operator, default) + *preset_zone.get(&operator).unwrap_or(&0) as i32;
+    let range = |zone: &Generators, operator: u16| zone.get(&operator).map_or((0, 127), |range| (*range as u16 as u8, (*range as u16 >> 8) as u8));
+    let offset = |fine: u16, coarse: u16| instrument_value(fine, 0) as i64 + instrument_value(coarse, 0) as i64 * 32768;
+
+    let (instrument_key_min, instrument_key_max) = range(instrument_zone, KEY_RANGE);
+    let (preset_key_min, preset_key_max) = range(preset_zone, KEY_RANGE);
+    let (instrument_velocity_min, instrument_velocity_max) = range(instrument_zone, VEL_RANGE);
+    let (preset_velocity_min, preset_velocity_max) = range(preset_zone, VEL_RANGE);
+    let note_midi_min = instrument_key_min.max(preset_key_min);
+    let note_midi_max = instrument_key_max.min(preset_key_max);
+    if note_midi_min > note_midi_max {
+        return None;
+    }
+
+    let start = header.start as i64 + offset(START_ADDRS_OFFSET, START_ADDRS_COARSE_OFFSET);
+    let end = header.end as i64 + offset(END_ADDRS_OFFSET, END_ADDRS_COARSE_OFFSET);
+    if start < 0 || end <= start {
+        return None;
+    }
+    let mut length = (end - start) as usize;
+    let right_data_offset = right_header.map(|right_header| {
+        let right_start = right_header.start as i64 + offset(START_ADDRS_OFFSET, START_ADDRS_COARSE_OFFSET);
+        length = length.min((right_header.end as i64 - right_start).max(0) as usize);
+        smpl_offset + right_start.max(0) as u64 * 2
+    });
+
+    let start_loop = header.start_loop as i64 + offset(STARTLOOP_ADDRS_OFFSET, STARTLOOP_ADDRS_COARSE_OFFSET) - start;
+    let end_loop = header.end_loop as i64 + offset(ENDLOOP_ADDRS_OFFSET, ENDLOOP_ADDRS_COARSE_OFFSET) - start;
+    // Mode 1 loops through the release, mode 3 until the release
+    let loop_mode = match instrument_value(SAMPLE_MODES, 0) & 3 {
+        1 if end_loop > start_loop && start_loop >= 0 => LoopMode::Continuous,
+        3 if end_loop > start_loop && start_loop >= 0 => LoopMode::Forward,
+        _ => LoopMode::None,
+    };
+
+    let root_midi_note = match instrument_value(OVERRIDING_ROOT_KEY, -1) {
+        root_key if (0..=127).contains(&root_key) => root_key as u8,
+        _ if header.original_pitch <= 127 => header.original_pitch,
+        _ => 60,
+    };
+
+    let mut settings = SampleSettings::default();
+    settings.coarse = value(COARSE_TUNE, 0) as f32;
+    settings.fine = (value(FINE_TUNE, 0) + header.pitch_correction as i32) as f32;
+    // Attenuation is in centibels and pan in 0.1%
+    settings.gain = -value(INITIAL_ATTENUATION, 0).clamp(0, 1440) as f32 / 10.;
+    settings.pan = (value(PAN, 0).clamp(-500, 500) as f32 / 500.).clamp(-1., 1.);
+    settings.envelope = EnvelopeOverride::all(SampleEnvelope {
+        attack: timecents_to_seconds(value(ATTACK_VOL_ENV, DEFAULT_TIMECENTS).clamp(-12000, 8000) as i16),
+        decay: timecents_to_seconds(value(DECAY_VOL_ENV, DEFAULT_TIMECENTS).clamp(-12000, 8000) as i16),
+        sustain: f32::powf(10., -value(SUSTAIN_VOL_ENV, 0).clamp(0, 1440) as f32 / 200.),
+        release: timecents_to_seconds(value(RELEASE_VOL_ENV, DEFAULT_TIMECENTS).clamp(-12000, 8000) as i16),
+    });
+
+    let exclusive_class = instrument_value(EXCLUSIVE_CLASS, 0);
+
+    Some(SampleInfo {
+        root_midi_note,
+        note_midi_min,
+        note_midi_max,
+        filepath: filepath.to_string(),
+        is_one_shot: false,
+        velocity_min: instrument_velocity_min.max(preset_velocity_min),
+        velocity_max: instrument_velocity_max.min(preset_velocity_max),
+        round_robin_group: None,
+        choke_group: if exclusive_class > 0 { Some(exclusive_class.min(255) as u8) } else { None },
+        loop_mode,
+        loop_start: start_loop.max(0) as usize,
+        loop_end: if loop_mode != LoopMode::None { Some(end_loop as usize) } else { None },
+        loop_crossfade: 0,
+        original_bpm: None,
+        settings,
+        sf2_sample: Some(Sf2Sample {
+            data_offset: smpl_offset + start as u64 * 2,
+            right_data_offset,
+            length,
+            sample_rate: header.sample_rate,
+        }),
+        pad_id: 0,
+    })
+}
+
+fn timecents_to_seconds(timecents: i16) -> f32 {
+    f32::powf(2., timecents as f32 / 1200.)
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+fn read_u16(bytes: &[u8], position: usize) -> u16 {
+    u16::from_le_bytes([bytes[position], bytes[position + 1]])
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]])
+}
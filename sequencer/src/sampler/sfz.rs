@@ -0,0 +1,294 @@
+use crate::load_error::LoadError;
+use crate::sampler::sampler_preset::{SamplerPreset, SampleInfo, SampleSettings, SampleEnvelope, EnvelopeOverride, LoopMode};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+// Opcodes of a header, the ones of <global>, <master> and <group> are inherited by the regions below them
+type Opcodes = HashMap<String, String>;
+
+const SUPPORTED_OPCODES : [&str; 21] = [
+    "sample", "default_path",
+    "key", "lokey", "hikey", "pitch_keycenter",
+    "lovel", "hivel",
+    "loop_mode", "loopmode", "loop_start", "loopstart", "loop_end", "loopend",
+    "ampeg_attack", "ampeg_decay", "ampeg_sustain", "ampeg_release",
+    "tune", "volume", "pan",
+];
+
+// Release used by sfz players when the file doesn't set one
+const DEFAULT_RELEASE : f32 = 0.001;
+
+pub fn is_sfz_file(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("sfz"))
+}
+
+// Regions become samples of the preset, the opcodes we can't play are reported in errors
+pub fn load_sfz(filepath: &str, errors: &mut Vec<LoadError>) -> Result<SamplerPreset, LoadError> {
+    let path = Path::new(filepath);
+    let text = std::fs::read_to_string(path).map_err(|error| LoadError::Io(filepath.to_string(), error))?;
+    let parent_path = match path.parent() {
+        Some(parent_path) => parent_path.to_string_lossy().to_string(),
+        None => return Err(LoadError::InvalidPath(filepath.to_string())),
+    };
+
+    let mut control = Opcodes::new();
+    let mut global = Opcodes::new();
+    let mut master = Opcodes::new();
+    let mut group = Opcodes::new();
+    let mut regions: Vec<Opcodes> = Vec::new();
+    let mut header = String::new();
+    let mut unsupported: Vec<String> = Vec::new();
+
+    let tokens = tokenize(&text).map_err(|message| LoadError::Sfz(filepath.to_string(), message))?;
+    for (header_name, opcode, value) in tokens {
+        if let Some(header_name) = header_name {
+            header = header_name;
+            match header.as_str() {
+                "global" => { global.clear(); master.clear(); group.clear(); },
+                "master" => { master.clear(); group.clear(); },
+                "group" => group.clear(),
+                "region" => {
+                    let mut region = global.clone();
+                    region.extend(master.clone());
+                    region.extend(group.clone());
+                    regions.push(region);
+                },
+                "control" => {},
+                _ => report(&mut unsupported, format!("<{}>", header)),
+            }
+            continue;
+        }
+
+        // The opcodes of an unknown header are skipped with it
+        let opcodes = match header.as_str() {
+            "control" => &mut control,
+            "global" => &mut global,
+            "master" => &mut master,
+            "group" => &mut group,
+            "region" => match regions.last_mut() {
+                Some(region) => region,
+                None => continue,
+            },
+            _ if opcode.starts_with('#') => {
+                report(&mut unsupported, opcode);
+                continue;
+            },
+            _ => continue,
+        };
+
+        if !SUPPORTED_OPCODES.contains(&opcode.as_str()) {
+            report(&mut unsupported, opcode);
+            continue;
+        }
+        opcodes.insert(opcode, value);
+    }
+
+    if !unsupported.is_empty() {
+        errors.push(LoadError::UnsupportedOpcodes(filepath.to_string(), unsupported));
+    }
+
+    let default_path = control.get("default_path").cloned().unwrap_or_default();
+    let mut samples = Vec::new();
+    for region in regions.iter() {
+        match region_to_sample_info(region, &parent_path, &default_path) {
+            Ok(Some(sample_info)) => samples.push(sample_info),
+            Ok(None) => {},
+            Err(message) => errors.push(LoadError::Sfz(filepath.to_string(), message)),
+        }
+    }
+
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+    Ok(SamplerPreset {
+        id: 0,
+        name,
+        samples,
+        attack: 0.,
+        decay: 0.,
+        sustain: 1.,
+        release: DEFAULT_RELEASE,
+        loop_filepath: None,
+        filepath: None,
+    })
+}
+
+fn report(unsupported: &mut Vec<String>, name: String) {
+    if !unsupported.contains(&name) {
+        unsupported.push(name);
+    }
+}
+
+// Headers and opcodes in the order of the file, a header comes as (Some(name), "", "")
+fn tokenize(text: &str) -> Result<Vec<(Option<String>, String, String)>, String> {
+    let mut tokens = Vec::new();
+
+    for line in text.lines() {
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        if line.trim_start().starts_with('#') {
+            tokens.push((None, line.trim().split_whitespace().next().unwrap_or_default().to_string(), String::new()));
+            continue;
+        }
+
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            if rest.starts_with('<') {
+                let end = match rest.find('>') {
+                    Some(end) => end,
+                    None => return Err(format!("unterminated header {}", rest.trim_end())),
+                };
+                tokens.push((Some(rest[1..end].trim().to_string()), String::new(), String::new()));
+                rest = rest[end + 1..].trim_start();
+                continue;
+            }
+
+            let equal = match rest.find('=') {
+                Some(equal) => equal,
+                None => break,
+            };
+            let opcode = rest[..equal].trim().to_string();
+            let value_start = &rest[equal + 1..];
+            // Values like sample paths may contain spaces, they end where the next opcode or header starts
+            let value_end = next_token_start(value_start);
+            tokens.push((None, opcode, value_start[..value_end].trim().to_string()));
+            rest = value_start[value_end..].trim_start();
+        }
+    }
+
+    return Ok(tokens);
+}
+
+fn next_token_start(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    for i in 0..bytes.len() {
+        if !bytes[i].is_ascii_whitespace() {
+            continue;
+        }
+        let next = text[i..].trim_start();
+        let is_opcode = next.find('=').map_or(false, |equal| {
+            equal > 0 && next[..equal].bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        });
+        if next.starts_with('<') || is_opcode {
+            return i;
+        }
+    }
+    return text.len();
+}
+
+fn region_to_sample_info(region: &Opcodes, parent_path: &str, default_path: &str) -> Result<Option<SampleInfo>, String> {
+    let sample = match region.get("sample") {
+        Some(sample) => sample.replace('\\', "/"),
+        None => return Ok(None),
+    };
+
+    let key = parse_optional(region, "key", parse_note)?;
+    let note_midi_min = parse_optional(region, "lokey", parse_note)?.or(key).unwrap_or(0);
+    let note_midi_max = parse_optional(region, "hikey", parse_note)?.or(key).unwrap_or(127);
+    let root_midi_note = parse_optional(region, "pitch_keycenter", parse_note)?.or(key).unwrap_or(60);
+
+    let loop_mode = match region.get("loop_mode").or(region.get("loopmode")).map(|mode| mode.as_str()) {
+        Some("loop_continuous") => LoopMode::Continuous,
+        Some("loop_sustain") => LoopMode::Forward,
+        Some("no_loop") | Some("one_shot") | None => LoopMode::None,
+        Some(mode) => return Err(format!("unknown loop mode {}", mode)),
+    };
+    let loop_start = parse_optional(region, "loop_start", parse_number::<usize>)?
+        .or(parse_optional(region, "loopstart", parse_number::<usize>)?)
+        .unwrap_or(0);
+    // The sfz loop end is the last frame of the loop
+    let loop_end = parse_optional(region, "loop_end", parse_number::<usize>)?
+        .or(parse_optional(region, "loopend", parse_number::<usize>)?)
+        .map(|loop_end| loop_end + 1);
+
+    let ampeg_attack = parse_optional(region, "ampeg_attack", parse_number::<f32>)?;
+    let ampeg_decay = parse_optional(region, "ampeg_decay", parse_number::<f32>)?;
+    let ampeg_sustain = parse_optional(region, "ampeg_sustain", parse_number::<f32>)?;
+    let ampeg_release = parse_optional(region, "ampeg_release", parse_number::<f32>)?;
+    // An sfz envelope replaces the whole kit envelope, its missing opcodes take their sfz default
+    let mut envelope = EnvelopeOverride::default();
+    if ampeg_attack.is_some() || ampeg_decay.is_some() || ampeg_sustain.is_some() || ampeg_release.is_some() {
+        envelope = EnvelopeOverride::all(SampleEnvelope {
+            attack: ampeg_attack.unwrap_or(0.),
+            decay: ampeg_decay.unwrap_or(0.),
+            sustain: ampeg_sustain.unwrap_or(100.).clamp(0., 100.) / 100.,
+            release: ampeg_release.unwrap_or(DEFAULT_RELEASE),
+        });
+    }
+
+    let mut settings = SampleSettings::default();
+    settings.fine = parse_optional(region, "tune", parse_number::<f32>)?.unwrap_or(0.);
+    settings.gain = parse_optional(region, "volume", parse_number::<f32>)?.unwrap_or(0.);
+    settings.pan = parse_optional(region, "pan", parse_number::<f32>)?.unwrap_or(0.).clamp(-100., 100.) / 100.;
+    settings.envelope = envelope;
+
+    Ok(Some(SampleInfo {
+        root_midi_note,
+        note_midi_min,
+        note_midi_max,
+        filepath: format!("{}/{}{}", parent_path, default_path.replace('\\', "/"), sample),
+        is_one_shot: region.get("loop_mode").or(region.get("loopmode")).map_or(false, |mode| mode == "one_shot"),
+        velocity_min: parse_optional(region, "lovel", parse_number::<u8>)?.unwrap_or(0),
+        velocity_max: parse_optional(region, "hivel", parse_number::<u8>)?.unwrap_or(127),
+        round_robin_group: None,
+        choke_group: None,
+        loop_mode,
+        loop_start,
+        loop_end,
+        loop_crossfade: 0,
+        original_bpm: None,
+        settings,
+        sf2_sample: None,
+        pad_id: 0,
+    }))
+}
+
+fn parse_optional<T>(region: &Opcodes, opcode: &str, parse: fn(&str) -> Option<T>) -> Result<Option<T>, String> {
+    match region.get(opcode) {
+        Some(value) => match parse(value) {
+            Some(parsed) => Ok(Some(parsed)),
+            None => Err(format!("invalid value {} for {}", value, opcode)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.parse::<T>().ok()
+}
+
+// Midi number or note name like c4, c#4 or db4, middle C being c4 = 60
+fn parse_note(value: &str) -> Option<u8> {
+    if let Ok(midi_note) = value.parse::<u8>() {
+        return Some(midi_note.min(127));
+    }
+
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+    let mut semitone: i32 = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let mut octave = chars.as_str();
+    if octave.starts_with('#') {
+        semitone += 1;
+        octave = &octave[1..];
+    } else if octave.starts_with('b') {
+        semitone -= 1;
+        octave = &octave[1..];
+    }
+
+    let midi_note = (octave.parse::<i32>().ok()? + 1) * 12 + semitone;
+    if (0..=127).contains(&midi_note) {
+        return Some(midi_note as u8);
+    }
+    return None;
+}
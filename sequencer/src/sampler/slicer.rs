@@ -0,0 +1,213 @@
+use crate::load_error::LoadError;
+use crate::sampler::sample::Sample;
+use crate::sampler::sampler_preset::{SamplerPreset, SampleInfo};
+
+use std::path::Path;
+
+pub const SLICE_MODE_NAMES : [&str; 2] = ["Transients", "Grid"];
+pub const SLICE_MODE_TRANSIENTS : usize = 0;
+pub const SLICE_MODE_GRID : usize = 1;
+
+pub const GRID_NAMES : [&str; 4] = ["1/4", "1/8", "1/16", "1/32"];
+const GRID_SLICES_PER_BEAT : [usize; 4] = [1, 2, 4, 8];
+
+pub const MAX_SLICES : usize = 64;
+// Note of the first slice, the next ones are on the notes above it
+pub const FIRST_SLICE_NOTE : u8 = 52;
+
+// Onset detection works on the energy of frames of HOP_SIZE samples
+const HOP_SIZE : usize = 512;
+const MIN_SLICE_DURATION : f32 = 0.06;
+// Frames quieter than this under the loudest one never start a slice
+const SILENCE_DB : f32 = -50.;
+
+// How a loop is cut, sent to the loader each time it changes
+#[derive(Clone, Copy)]
+pub struct SliceSettings {
+    pub mode: usize,
+    pub sensitivity: f32,
+    pub grid: usize,
+    pub tempo: f32,
+}
+
+// Where a loop is cut, the slicer itself and the frames stay in the loader thread
+#[derive(Clone)]
+pub struct SlicedLoop {
+    pub slice_points: Vec<usize>,
+    pub sample_rate: f32,
+    pub len: usize,
+}
+
+impl SlicedLoop {
+    pub fn duration(&self) -> f32 {
+        return self.len as f32 / self.sample_rate;
+    }
+
+    pub fn set_slice_point(&mut self, idx: usize, position: usize) {
+        move_slice_point(&mut self.slice_points, self.len, idx, position);
+    }
+}
+
+// A loop cut in slices played by consecutive notes
+pub struct Slicer {
+    pub filepath: String,
+    pub left_channel: Vec<f32>,
+    pub right_channel: Vec<f32>,
+    pub sample_rate: f32,
+    // First frame of each slice, a slice ends where the next one starts
+    pub slice_points: Vec<usize>,
+}
+
+impl Slicer {
+    pub fn load(filepath: &str, sample_rate: f32) -> Result<Slicer, LoadError> {
+        let sample = Sample::load_sample(&SampleInfo::new(filepath.to_string(), 0), sample_rate)?;
+        Ok(Slicer {
+            filepath: filepath.to_string(),
+            left_channel: sample.left_channel,
+            right_channel: sample.right_channel,
+            sample_rate,
+            slice_points: vec![0],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        return self.left_channel.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.left_channel.is_empty();
+    }
+
+    pub fn duration(&self) -> f32 {
+        return self.len() as f32 / self.sample_rate;
+    }
+
+    pub fn slice(&mut self, settings: &SliceSettings) {
+        if settings.mode == SLICE_MODE_GRID {
+            self.divide_grid(settings.tempo, settings.grid);
+        } else {
+            self.detect_transients(settings.sensitivity);
+        }
+    }
+
+    // sensitivity is in [0, 1], the higher the more slices
+    pub fn detect_transients(&mut self, sensitivity: f32) {
+        let nb_frames = self.len() / HOP_SIZE;
+        let mut energies_db = Vec::with_capacity(nb_frames);
+        let mut previous = 0.;
+        for frame in 0..nb_frames {
+            // The first difference favours the high frequencies of the attacks
+            let mut energy = 0.;
+            for i in frame * HOP_SIZE..(frame + 1) * HOP_SIZE {
+                let value = (self.left_channel[i] + self.right_channel[i]) * 0.5;
+                energy += (value - previous) * (value - previous);
+                previous = value;
+            }
+            energies_db.push(10. * (energy / HOP_SIZE as f32 + 1e-12).log10());
+        }
+
+        let loudest = energies_db.iter().cloned().fold(f32::MIN, f32::max);
+        let threshold = 12. - 9. * sensitivity.clamp(0., 1.);
+        let min_gap = (MIN_SLICE_DURATION * self.sample_rate) as usize;
+
+        let rise = |frame: usize| energies_db[frame] - energies_db[frame - 1];
+
+        self.slice_points = vec![0];
+        for frame in 1..nb_frames {
+            let is_peak = rise(frame) >= threshold
+                && (frame + 1 >= nb_frames || rise(frame) >= rise(frame + 1))
+                && energies_db[frame] > loudest + SILENCE_DB;
+            let position = frame * HOP_SIZE;
+            if is_peak && position - self.slice_points[self.slice_points.len() - 1] >= min_gap {
+                self.slice_points.push(position);
+                if self.slice_points.len() >= MAX_SLICES {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Equal slices at the tempo, grid being an index in GRID_NAMES
+    pub fn divide_grid(&mut self, bpm: f32, grid: usize) {
+        let slices_per_beat = GRID_SLICES_PER_BEAT[grid.min(GRID_SLICES_PER_BEAT.len() - 1)];
+        let step = ((60. / bpm.max(1.)) / slices_per_beat as f32 * self.sample_rate).max(1.);
+
+        self.slice_points = vec![0];
+        let mut position = step;
+        while (position as usize) < self.len() && self.slice_points.len() < MAX_SLICES {
+            self.slice_points.push(position as usize);
+            position += step;
+        }
+    }
+
+    pub fn set_slice_point(&mut self, idx: usize, position: usize) {
+        let len = self.len();
+        move_slice_point(&mut self.slice_points, len, idx, position);
+    }
+
+    pub fn sliced_loop(&self) -> SlicedLoop {
+        SlicedLoop {
+            slice_points: self.slice_points.clone(),
+            sample_rate: self.sample_rate,
+            len: self.len(),
+        }
+    }
+
+    pub fn slice_end(&self, idx: usize) -> usize {
+        return *self.slice_points.get(idx + 1).unwrap_or(&self.len());
+    }
+
+    pub fn slice_sample(&self, idx: usize, midi_note: u8) -> Sample {
+        let start = self.slice_points[idx];
+        let end = self.slice_end(idx);
+        let left_channel = self.left_channel[start..end].to_vec();
+        let right_channel = self.right_channel[start..end].to_vec();
+        Sample {
+            sample_rate: self.sample_rate,
+            size: left_channel.len(),
+            left_channel,
+            right_channel,
+            root_midi_note: midi_note,
+            note_midi_min: midi_note,
+            note_midi_max: midi_note,
+            ..Sample::empty()
+        }
+    }
+
+    // One pad for each slice, from FIRST_SLICE_NOTE
+    pub fn slice_samples(&self) -> Vec<Sample> {
+        return (0..self.slice_points.len()).map(|idx| self.slice_sample(idx, FIRST_SLICE_NOTE.saturating_add(idx as u8))).collect();
+    }
+}
+
+// The point stays between its neighbours
+fn move_slice_point(slice_points: &mut [usize], len: usize, idx: usize, position: usize) {
+    if idx >= slice_points.len() {
+        return;
+    }
+    let min = if idx > 0 { slice_points[idx - 1] + 1 } else { 0 };
+    let max = slice_points.get(idx + 1).unwrap_or(&len).saturating_sub(1).max(min);
+    slice_points[idx] = position.clamp(min, max);
+}
+
+// One preset for each wav file of the loops directory, sliced when it is selected
+pub fn scan_loops(loops_path: &str) -> Vec<SamplerPreset> {
+    let mut filepaths: Vec<_> = match std::fs::read_dir(loops_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("wav")))
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    filepaths.sort();
+
+    return filepaths.iter().map(|filepath| {
+        let name = Path::new(filepath).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        SamplerPreset {
+            name: format!("Slices - {}", name),
+            loop_filepath: Some(filepath.to_string_lossy().to_string()),
+            ..SamplerPreset::empty()
+        }
+    }).collect();
+}
@@ -0,0 +1,110 @@
+use crate::sampler::sample::Sample;
+
+// Grains are read at the pitch of the note while their start follows the tempo, so the duration changes without the pitch
+// https://en.wikipedia.org/wiki/Audio_time_stretching_and_pitch_scaling
+const GRAIN_DURATION : f32 = 0.04;
+// A new grain starts up to this far from its position to continue the waveform of the other one (WSOLA)
+const SEARCH_DURATION : f32 = 0.008;
+// Frames compared when searching, every COMPARE_STRIDE frames
+const COMPARE_LENGTH : usize = 256;
+const COMPARE_STRIDE : usize = 4;
+const SEARCH_STRIDE : usize = 2;
+
+// Two grains with Hann windows half a grain apart, their windows sum to 1
+#[derive(Clone)]
+pub struct GrainStretcher {
+    grain_size: usize,
+    search_size: usize,
+    positions: [f32; 2],
+    steps: [f32; 2],
+    ages: [usize; 2],
+}
+
+impl GrainStretcher {
+    pub fn new(sample_rate: f32) -> GrainStretcher {
+        let grain_size = ((GRAIN_DURATION * sample_rate) as usize).max(2);
+        GrainStretcher {
+            grain_size,
+            // Even so the search goes through the position itself
+            search_size: (SEARCH_DURATION * sample_rate) as usize / SEARCH_STRIDE * SEARCH_STRIDE,
+            positions: [0.; 2],
+            steps: [1.; 2],
+            ages: [0, grain_size / 2],
+        }
+    }
+
+    // Both grains start at the position, one at the middle of its window, so the attack isn't faded in
+    pub fn reset(&mut self, position: f32, step: f32) {
+        self.positions = [position; 2];
+        self.steps = [step; 2];
+        self.ages = [0, self.grain_size / 2];
+    }
+
+    // position is where the sample is on the timeline, step is the pitch ratio, negative to play backward
+    pub fn next_frame(&mut self, sample: &Sample, position: f32, step: f32) -> (f32, f32) {
+        let mut left = 0.;
+        let mut right = 0.;
+        for grain in 0..2 {
+            if self.ages[grain] >= self.grain_size {
+                let other = self.positions[1 - grain];
+                self.positions[grain] = self.find_grain_start(sample, position, other, step);
+                self.steps[grain] = step;
+                self.ages[grain] = 0;
+            }
+
+            let phase = self.ages[grain] as f32 / self.grain_size as f32;
+            let window = 0.5 - 0.5 * (2. * std::f32::consts::PI * phase).cos();
+            let (grain_left, grain_right) = read_frame(sample, self.positions[grain]);
+            left += grain_left * window;
+            right += grain_right * window;
+
+            self.positions[grain] += self.steps[grain];
+            self.ages[grain] += 1;
+        }
+        return (left, right);
+    }
+
+    // Start around the position looking the most like what the other grain is going to play
+    fn find_grain_start(&self, sample: &Sample, position: f32, other: f32, step: f32) -> f32 {
+        let mut best_position = position;
+        let mut best_score = f32::MIN;
+
+        let mut offset = -(self.search_size as isize);
+        while offset <= self.search_size as isize {
+            let candidate = position + offset as f32;
+            let mut correlation = 0.;
+            let mut energy = 1e-9;
+            let mut k = 0;
+            while k < COMPARE_LENGTH {
+                let value = read_mono(sample, candidate + k as f32 * step);
+                correlation += value * read_mono(sample, other + k as f32 * step);
+                energy += value * value;
+                k += COMPARE_STRIDE;
+            }
+            let score = correlation / energy.sqrt();
+            if score > best_score {
+                best_score = score;
+                best_position = candidate;
+            }
+            offset += SEARCH_STRIDE as isize;
+        }
+        return best_position;
+    }
+}
+
+// Silence outside of the sample
+fn read_frame(sample: &Sample, position: f32) -> (f32, f32) {
+    if position < 0. || position + 1. >= sample.size as f32 {
+        return (0., 0.);
+    }
+    let pos = position as usize;
+    let alpha = position - pos as f32;
+    let (left, right) = sample.frame(pos);
+    let (next_left, next_right) = sample.frame(pos + 1);
+    return (left * (1. - alpha) + next_left * alpha, right * (1. - alpha) + next_right * alpha);
+}
+
+fn read_mono(sample: &Sample, position: f32) -> f32 {
+    let (left, right) = read_frame(sample, position);
+    return left + right;
+}
@@ -0,0 +1,326 @@
+use crate::load_error::LoadError;
+use crate::preset::write_atomically;
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+// http://www-mmsp.ece.mcgill.ca/Documents/AudioFormats/WAVE/WAVE.html
+const WAVE_FORMAT_PCM : u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT : u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE : u16 = 0xFFFE;
+
+// Speaker positions of the channel mask, the channels are stored in this order
+const SPEAKER_FRONT_LEFT : u32 = 0x1;
+const SPEAKER_FRONT_RIGHT : u32 = 0x2;
+const SPEAKER_FRONT_CENTER : u32 = 0x4;
+const SPEAKER_LOW_FREQUENCY : u32 = 0x8;
+const SPEAKER_BACK_LEFT : u32 = 0x10;
+const SPEAKER_BACK_RIGHT : u32 = 0x20;
+const SPEAKER_FRONT_LEFT_OF_CENTER : u32 = 0x40;
+const SPEAKER_FRONT_RIGHT_OF_CENTER : u32 = 0x80;
+const SPEAKER_BACK_CENTER : u32 = 0x100;
+const SPEAKER_SIDE_LEFT : u32 = 0x200;
+const SPEAKER_SIDE_RIGHT : u32 = 0x400;
+const SPEAKER_TOP_FRONT_LEFT : u32 = 0x1000;
+const SPEAKER_TOP_FRONT_RIGHT : u32 = 0x4000;
+const SPEAKER_TOP_BACK_LEFT : u32 = 0x8000;
+const SPEAKER_TOP_BACK_RIGHT : u32 = 0x20000;
+
+const LEFT_SPEAKERS : u32 = SPEAKER_FRONT_LEFT | SPEAKER_BACK_LEFT | SPEAKER_FRONT_LEFT_OF_CENTER | SPEAKER_SIDE_LEFT | SPEAKER_TOP_FRONT_LEFT | SPEAKER_TOP_BACK_LEFT;
+const RIGHT_SPEAKERS : u32 = SPEAKER_FRONT_RIGHT | SPEAKER_BACK_RIGHT | SPEAKER_FRONT_RIGHT_OF_CENTER | SPEAKER_SIDE_RIGHT | SPEAKER_TOP_FRONT_RIGHT | SPEAKER_TOP_BACK_RIGHT;
+
+// Centered channels go to both sides at -3dB
+const CENTER_GAIN : f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SampleFormat {
+    Pcm,
+    Float,
+}
+
+#[derive(Clone, Copy)]
+struct WavFormat {
+    sample_format: SampleFormat,
+    channels: usize,
+    sample_rate: u32,
+    // Bytes used by a sample of a channel, 24 bits samples may be stored in 4 bytes
+    container_size: usize,
+    channel_mask: u32,
+}
+
+// Left and right channels of the file as floats between -1 and 1, with the sample rate
+pub fn read_wav(filepath: &str) -> Result<(Vec<f32>, Vec<f32>, f32), LoadError> {
+    let bytes = std::fs::read(filepath).map_err(|error| LoadError::Io(filepath.to_string(), error))?;
+    let (left_channel, right_channel, sample_rate) = decode_wav(&bytes)
+        .map_err(|message| LoadError::Wav(filepath.to_string(), message))?;
+    return Ok((left_channel, right_channel, sample_rate as f32));
+}
+
+pub fn decode_wav(bytes: &[u8]) -> Result<(Vec<f32>, Vec<f32>, u32), String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("not a wav file".to_string());
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let chunk_size = read_u32(bytes, position + 4) as usize;
+        let chunk_start = position + 8;
+        // Files written while recording may have a wrong size for the last chunk
+        let chunk_end = chunk_start.saturating_add(chunk_size).min(bytes.len());
+        match &bytes[position..position + 4] {
+            b"fmt " => format = Some(read_format(&bytes[chunk_start..chunk_end])?),
+            b"data" => data = Some(&bytes[chunk_start..chunk_end]),
+            _ => {},
+        }
+        // Chunks are padded to an even size
+        position = chunk_end + chunk_size % 2;
+    }
+
+    let format = format.ok_or("no fmt chunk")?;
+    let data = data.ok_or("no data chunk")?;
+    let (left_channel, right_channel) = decode_frames(&format, data);
+    return Ok((left_channel, right_channel, format.sample_rate));
+}
+
+// Stereo 32 bits float file
+pub fn write_wav(filepath: &str, left_channel: &[f32], right_channel: &[f32], sample_rate: u32) -> Result<(), LoadError> {
+    let channels = 2;
+    let container_size = 4;
+    let data_size = left_channel.len() * channels * container_size;
+
+    let mut bytes = Vec::with_capacity(44 + data_size);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size as u32).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+    bytes.extend_from_slice(&(channels as u16).to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * (channels * container_size) as u32).to_le_bytes());
+    bytes.extend_from_slice(&((channels * container_size) as u16).to_le_bytes());
+    bytes.extend_from_slice(&(container_size as u16 * 8).to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&(data_size as u32).to_le_bytes());
+    for (left, right) in left_channel.iter().zip(right_channel.iter()) {
+        bytes.extend_from_slice(&left.to_le_bytes());
+        bytes.extend_from_slice(&right.to_le_bytes());
+    }
+
+    return write_atomically(filepath, &bytes);
+}
+
+// Reads the frames of a wav file a block at a time, for the samples streamed from the disk
+pub struct WavReader {
+    filepath: String,
+    file: File,
+    format: WavFormat,
+    data_offset: u64,
+    pub nb_frames: usize,
+    pub sample_rate: u32,
+}
+
+impl WavReader {
+    // Only the headers are read
+    pub fn open(filepath: &str) -> Result<WavReader, LoadError> {
+        let io_error = |error: std::io::Error| LoadError::Io(filepath.to_string(), error);
+        let invalid = |message: String| LoadError::Wav(filepath.to_string(), message);
+
+        let mut file = File::open(filepath).map_err(io_error)?;
+        let file_size = file.metadata().map_err(io_error)?.len();
+        let mut riff_header = [0u8; 12];
+        file.read_exact(&mut riff_header).map_err(|_| invalid("not a wav file".to_string()))?;
+        if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"WAVE" {
+            return Err(invalid("not a wav file".to_string()));
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut chunk_header = [0u8; 8];
+        while data.is_none() && file.read_exact(&mut chunk_header).is_ok() {
+            let chunk_size = read_u32(&chunk_header, 4) as u64;
+            let chunk_start = file.stream_position().map_err(io_error)?;
+            match &chunk_header[0..4] {
+                b"fmt " => {
+                    let mut chunk = vec![0u8; chunk_size.min(file_size - chunk_start) as usize];
+                    file.read_exact(&mut chunk).map_err(io_error)?;
+                    format = Some(read_format(&chunk).map_err(invalid)?);
+                },
+                b"data" => data = Some((chunk_start, chunk_size.min(file_size - chunk_start))),
+                _ => {},
+            }
+            file.seek(SeekFrom::Start(chunk_start + chunk_size + chunk_size % 2)).map_err(io_error)?;
+        }
+
+        let format = format.ok_or_else(|| invalid("no fmt chunk".to_string()))?;
+        let (data_offset, data_size) = data.ok_or_else(|| invalid("no data chunk".to_string()))?;
+
+        Ok(WavReader {
+            filepath: filepath.to_string(),
+            file,
+            format,
+            data_offset,
+            nb_frames: data_size as usize / (format.container_size * format.channels),
+            sample_rate: format.sample_rate,
+        })
+    }
+
+    // Left and right channels of up to count frames from the start frame
+    pub fn read_frames(&mut self, start: usize, count: usize) -> Result<(Vec<f32>, Vec<f32>), LoadError> {
+        let io_error = |error: std::io::Error| LoadError::Io(self.filepath.clone(), error);
+        let count = count.min(self.nb_frames.saturating_sub(start));
+        let frame_size = self.format.container_size * self.format.channels;
+
+        let mut data = vec![0u8; count * frame_size];
+        self.file.seek(SeekFrom::Start(self.data_offset + (start * frame_size) as u64)).map_err(io_error)?;
+        self.file.read_exact(&mut data).map_err(io_error)?;
+        return Ok(decode_frames(&self.format, &data));
+    }
+}
+
+fn decode_frames(format: &WavFormat, data: &[u8]) -> (Vec<f32>, Vec<f32>) {
+    let frame_size = format.container_size * format.channels;
+    let nb_frames = data.len() / frame_size;
+    let (left_gains, right_gains) = downmix_gains(format);
+
+    let mut left_channel = Vec::with_capacity(nb_frames);
+    let mut right_channel = Vec::with_capacity(nb_frames);
+    for frame in 0..nb_frames {
+        let mut left = 0.;
+        let mut right = 0.;
+        for channel in 0..format.channels {
+            let value = read_sample(format, &data[frame * frame_size + channel * format.container_size..]);
+            left += value * left_gains[channel];
+            right += value * right_gains[channel];
+        }
+        left_channel.push(left);
+        right_channel.push(right);
+    }
+
+    return (left_channel, right_channel);
+}
+
+fn read_format(chunk: &[u8]) -> Result<WavFormat, String> {
+    if chunk.len() < 16 {
+        return Err("fmt chunk too short".to_string());
+    }
+    let mut format_tag = read_u16(chunk, 0);
+    let channels = read_u16(chunk, 2) as usize;
+    let sample_rate = read_u32(chunk, 4);
+    let block_align = read_u16(chunk, 12) as usize;
+    let bits_per_sample = read_u16(chunk, 14) as usize;
+    let mut channel_mask = 0;
+
+    // The valid bits of an extensible format are left aligned in the container, read as the container they keep their scale
+    if format_tag == WAVE_FORMAT_EXTENSIBLE {
+        if chunk.len() < 40 {
+            return Err("extensible fmt chunk too short".to_string());
+        }
+        channel_mask = read_u32(chunk, 20);
+        // First two bytes of the sub format GUID
+        format_tag = read_u16(chunk, 24);
+    }
+
+    if channels == 0 {
+        return Err("no channels".to_string());
+    }
+    if sample_rate == 0 {
+        return Err("sample rate of 0".to_string());
+    }
+    let container_size = block_align / channels;
+    if container_size == 0 || container_size * 8 < bits_per_sample {
+        return Err(format!("block align of {} for {} channels of {} bits", block_align, channels, bits_per_sample));
+    }
+
+    let sample_format = match (format_tag, container_size) {
+        (WAVE_FORMAT_PCM, 1..=4) => SampleFormat::Pcm,
+        (WAVE_FORMAT_IEEE_FLOAT, 4) | (WAVE_FORMAT_IEEE_FLOAT, 8) => SampleFormat::Float,
+        (WAVE_FORMAT_PCM, _) | (WAVE_FORMAT_IEEE_FLOAT, _) => return Err(format!("{} bits samples not supported", bits_per_sample)),
+        _ => return Err(format!("format {:#x} not supported", format_tag)),
+    };
+
+    if channel_mask == 0 || channel_mask.count_ones() as usize > channels {
+        channel_mask = default_channel_mask(channels);
+    }
+
+    Ok(WavFormat {
+        sample_format,
+        channels,
+        sample_rate,
+        container_size,
+        channel_mask,
+    })
+}
+
+fn read_sample(format: &WavFormat, bytes: &[u8]) -> f32 {
+    match (format.sample_format, format.container_size) {
+        // 8 bits samples are the only unsigned ones
+        (SampleFormat::Pcm, 1) => (bytes[0] as f32 - 128.) / 128.,
+        (SampleFormat::Pcm, 2) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.,
+        (SampleFormat::Pcm, 3) => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.,
+        (SampleFormat::Pcm, _) => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.,
+        (SampleFormat::Float, 4) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        (SampleFormat::Float, _) => f64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]) as f32,
+    }
+}
+
+// Layouts Windows assumes when a file doesn't have a channel mask
+fn default_channel_mask(channels: usize) -> u32 {
+    match channels {
+        1 => SPEAKER_FRONT_CENTER,
+        2 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT,
+        3 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_FRONT_CENTER,
+        4 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT,
+        5 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_FRONT_CENTER | SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT,
+        6 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_FRONT_CENTER | SPEAKER_LOW_FREQUENCY | SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT,
+        7 => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_FRONT_CENTER | SPEAKER_LOW_FREQUENCY | SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT | SPEAKER_BACK_CENTER,
+        _ => SPEAKER_FRONT_LEFT | SPEAKER_FRONT_RIGHT | SPEAKER_FRONT_CENTER | SPEAKER_LOW_FREQUENCY | SPEAKER_BACK_LEFT | SPEAKER_BACK_RIGHT | SPEAKER_SIDE_LEFT | SPEAKER_SIDE_RIGHT,
+    }
+}
+
+// Gain of each channel in the left and the right outputs
+// Every left speaker is mixed in the left output, the centered ones in both and the LFE is dropped
+// More than two channels are scaled down so the downmix can't clip
+fn downmix_gains(format: &WavFormat) -> (Vec<f32>, Vec<f32>) {
+    let mut left_gains = vec![0.; format.channels];
+    let mut right_gains = vec![0.; format.channels];
+
+    if format.channels == 1 {
+        return (vec![1.], vec![1.]);
+    }
+
+    // Speaker bits are given to the channels in order, the channels after the mask are centered
+    let mut speakers = (0..32).map(|bit| 1u32 << bit).filter(|speaker| format.channel_mask & speaker != 0);
+    for channel in 0..format.channels {
+        match speakers.next().unwrap_or(SPEAKER_FRONT_CENTER) {
+            SPEAKER_LOW_FREQUENCY => {},
+            speaker if speaker & LEFT_SPEAKERS != 0 => left_gains[channel] = 1.,
+            speaker if speaker & RIGHT_SPEAKERS != 0 => right_gains[channel] = 1.,
+            _ => {
+                left_gains[channel] = CENTER_GAIN;
+                right_gains[channel] = CENTER_GAIN;
+            },
+        }
+    }
+
+    if format.channels > 2 {
+        for gains in [&mut left_gains, &mut right_gains] {
+            let total : f32 = gains.iter().sum();
+            if total > 1. {
+                gains.iter_mut().for_each(|gain| *gain /= total);
+            }
+        }
+    }
+
+    return (left_gains, right_gains);
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
@@ -2,7 +2,8 @@ use std::sync::mpsc;
 use std::sync::mpsc::Sender;
 use std::sync::mpsc::Receiver;
 
-use crate::midimessage::NoteEvent;
+use crate::midimessage::{NoteEvent, AutomationPoint};
+use crate::parameter::Parameter;
 
 const QUANTIZE_VALUE: [i32; 8] = [-1, 2, 4, 8, 16, 32, 64, 128];
 
@@ -14,8 +15,19 @@ pub enum Message {
     SetMetronomeActive(bool),
     SetBpmHasBiped(bool),
     SetMidiMessagesInstrument(Vec<NoteEvent>),
+    SetAutomationPointsInstrument(Vec<AutomationPoint>),
     SetWaveFormData(Vec<f32>),
     SetRMSInstrument(usize, f32, f32),
+    SetVolumeInstrument(usize, f32),
+    SetParameter(usize, usize, f32),
+    SetParameterValuesInstrument(usize, Vec<f32>),
+    SetParametersInstrument(usize, Vec<Parameter>),
+    SaveCurrentPreset,
+    // Renders the loop of an instrument into a sampler, sliced or as a new pad
+    BounceInstrument(usize, bool),
+    SetInstrumentFrozen(usize, bool),
+    SetPresetsInstrument(usize, Vec<String>, usize),
+    AddErrorMessages(Vec<String>),
     NextInstrument,
     PreviousInstrument,
     PlayStop,
@@ -24,6 +36,7 @@ pub enum Message {
     NextQuantize,
     PreviousQuantize,
     SetIsRecording(bool),
+    SetIsArmed(bool),
     SetCurrentInstrumentSelected(usize),
     UndoLastSession,
 }
@@ -48,8 +61,19 @@ pub struct InstrumentData {
     pub current_preset_id: usize,
     pub presets: Vec<String>,
     pub paired_notes: Vec<NoteEvent>,
+    pub automation_points: Vec<AutomationPoint>,
+    pub parameters: Vec<Parameter>,
+    pub parameter_values: Vec<f32>,
     pub rms_left: f32,
     pub rms_right: f32,
+    // Played from a render of its loop instead of its processor
+    pub is_frozen: bool,
+}
+
+impl InstrumentData {
+    pub fn parameter_index(&self, id: usize) -> Option<usize> {
+        self.parameters.iter().position(|parameter| parameter.id == id)
+    }
 }
 
 pub struct SequencerData {
@@ -64,13 +88,18 @@ pub struct SequencerData {
     pub metronome_active: bool,
     pub ticks_per_quarter_note: i32,
     pub is_recording: bool,
+    // The selected instrument records the audio input while the transport plays
+    pub is_armed: bool,
     pub instrument_selected_id: usize,
     pub instruments: Vec<InstrumentData>,
     pub receiver: Receiver<Message>,
     pub record_session: i32,
     pub undo_last_session: bool,
+    pub save_current_preset: bool,
+    pub bounce_instrument: Option<(usize, bool)>,
     pub kill_all_notes: bool,
     pub audio_wave_form: Vec<f32>,
+    pub error_messages: Vec<String>,
 }
 
 impl SequencerData {
@@ -87,6 +116,7 @@ impl SequencerData {
             volume: 1.,
             metronome_active: true,
             is_recording: true,
+            is_armed: false,
             ticks_per_quarter_note: 960,
             instrument_selected_id: 0,
             tick_time: 0.0,
@@ -94,8 +124,11 @@ impl SequencerData {
             receiver,
             record_session: 0,
             undo_last_session: false,
+            save_current_preset: false,
+            bounce_instrument: None,
             kill_all_notes: false,
-            audio_wave_form: Vec::new()
+            audio_wave_form: Vec::new(),
+            error_messages: Vec::new(),
         };
         data.compute_tick_time();
         (data, sender)
@@ -126,6 +159,9 @@ impl SequencerData {
                     self.is_recording = x;
                     self.record_session += 1;
                 },
+                Message::SetIsArmed(x) => {
+                    self.is_armed = x;
+                },
                 Message::SetBpmHasBiped(x) => {
                     self.bpm_has_biped = x;
                 },
@@ -171,6 +207,9 @@ impl SequencerData {
                 Message::SetMidiMessagesInstrument(note_events) => {
                     self.instruments[self.instrument_selected_id].paired_notes = note_events;
                 },
+                Message::SetAutomationPointsInstrument(automation_points) => {
+                    self.instruments[self.instrument_selected_id].automation_points = automation_points;
+                },
                 Message::UndoLastSession => {
                     self.undo_last_session = true;
                 },
@@ -181,6 +220,37 @@ impl SequencerData {
                     self.instruments[idx].rms_left = rms_left;
                     self.instruments[idx].rms_right = rms_right;
                 },
+                Message::SetVolumeInstrument(idx, volume) => {
+                    self.instruments[idx].volume = volume;
+                },
+                Message::SetParameter(idx, id, value) => {
+                    if let Some(parameter_idx) = self.instruments[idx].parameter_index(id) {
+                        self.instruments[idx].parameter_values[parameter_idx] = value;
+                    }
+                },
+                Message::SetParameterValuesInstrument(idx, parameter_values) => {
+                    self.instruments[idx].parameter_values = parameter_values;
+                },
+                Message::SetParametersInstrument(idx, parameters) => {
+                    self.instruments[idx].parameter_values = parameters.iter().map(|parameter| parameter.default).collect();
+                    self.instruments[idx].parameters = parameters;
+                },
+                Message::SaveCurrentPreset => {
+                    self.save_current_preset = true;
+                },
+                Message::BounceInstrument(idx, sliced) => {
+                    self.bounce_instrument = Some((idx, sliced));
+                },
+                Message::SetInstrumentFrozen(idx, is_frozen) => {
+                    self.instruments[idx].is_frozen = is_frozen;
+                },
+                Message::AddErrorMessages(error_messages) => {
+                    self.error_messages.extend(error_messages);
+                },
+                Message::SetPresetsInstrument(idx, presets, current_preset_id) => {
+                    self.instruments[idx].presets = presets;
+                    self.instruments[idx].current_preset_id = current_preset_id;
+                },
                 _ => (),
             }
         }
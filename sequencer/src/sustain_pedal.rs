@@ -0,0 +1,47 @@
+const PEDAL_THRESHOLD: f32 = 0.5;
+
+pub struct SustainPedal {
+    pub is_down: bool,
+    sustained_notes: Vec<u8>,
+}
+
+impl SustainPedal {
+    pub fn new() -> SustainPedal {
+        SustainPedal {
+            is_down: false,
+            sustained_notes: Vec::with_capacity(128),
+        }
+    }
+
+    pub fn note_on(&mut self, midi_note: u8) {
+        self.sustained_notes.retain(|note| *note != midi_note);
+    }
+
+    // Returns true when the note off has to wait for the pedal to be released
+    pub fn hold_note_off(&mut self, midi_note: u8) -> bool {
+        if !self.is_down {
+            return false;
+        }
+        if !self.sustained_notes.contains(&midi_note) {
+            self.sustained_notes.push(midi_note);
+        }
+        return true;
+    }
+
+    pub fn set_value(&mut self, value: f32) {
+        self.is_down = value >= PEDAL_THRESHOLD;
+    }
+
+    // Next note to release once the pedal is up, the notes are taken from the storage reserved in new
+    pub fn next_released_note(&mut self) -> Option<u8> {
+        if self.is_down {
+            return None;
+        }
+        return self.sustained_notes.pop();
+    }
+
+    pub fn reset(&mut self) {
+        self.is_down = false;
+        self.sustained_notes.clear();
+    }
+}
@@ -0,0 +1,106 @@
+use crate::synthesizer::synthesizer_preset::NB_OPERATORS;
+
+// The operators are numbered like on the DX and OPM chips, op 4 is D and op 1 is A,
+// a modulator always comes before the operators it modulates in the index order
+pub const NB_ALGORITHMS : usize = 8;
+pub const ALGORITHM_NAMES : [&str; NB_ALGORITHMS] = ["1", "2", "3", "4", "5", "6", "7", "8"];
+
+// modulations[op][modulator] is the amount of the modulator output added to the phase of op,
+// outputs[op] is the amount of op sent to the voice output
+pub struct Algorithm {
+    pub modulations: [[f32; NB_OPERATORS]; NB_OPERATORS],
+    pub outputs: [f32; NB_OPERATORS],
+}
+
+// Rows and columns are in the operator index order D, C, B, A
+pub const ALGORITHMS : [Algorithm; NB_ALGORITHMS] = [
+    // 1: D -> C -> B -> A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+        ],
+        outputs: [0., 0., 0., 1.],
+    },
+    // 2: (D + C) -> B -> A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [1., 1., 0., 0.],
+            [0., 0., 1., 0.],
+        ],
+        outputs: [0., 0., 0., 1.],
+    },
+    // 3: (D + (C -> B)) -> A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [1., 0., 1., 0.],
+        ],
+        outputs: [0., 0., 0., 1.],
+    },
+    // 4: ((D -> C) + B) -> A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 1., 1., 0.],
+        ],
+        outputs: [0., 0., 0., 1.],
+    },
+    // 5: (D -> C) + (B -> A)
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 0., 1., 0.],
+        ],
+        outputs: [0., 1., 0., 1.],
+    },
+    // 6: D -> C + D -> B + D -> A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [1., 0., 0., 0.],
+        ],
+        outputs: [0., 1., 1., 1.],
+    },
+    // 7: (D -> C) + B + A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [1., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+        ],
+        outputs: [0., 1., 1., 1.],
+    },
+    // 8: D + C + B + A
+    Algorithm {
+        modulations: [
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 0., 0., 0.],
+        ],
+        outputs: [1., 1., 1., 1.],
+    },
+];
+
+// Algorithms are numbered from 1
+pub fn is_known_algorithm(algorithm: u8) -> bool {
+    return algorithm >= 1 && algorithm as usize <= NB_ALGORITHMS;
+}
+
+pub fn algorithm(algorithm: u8) -> &'static Algorithm {
+    return &ALGORITHMS[(algorithm as usize).clamp(1, NB_ALGORITHMS) - 1];
+}
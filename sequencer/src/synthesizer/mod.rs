@@ -1,4 +1,5 @@
 pub mod operator;
+pub mod algorithm;
 pub mod synthesizervoice;
 pub mod synthesizer_preset;
-pub mod synthesizer;
\ No newline at end of file
+pub mod synthesizer;
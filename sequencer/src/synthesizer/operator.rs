@@ -13,9 +13,12 @@ pub const SAW_DIGITAL : u8 = 7;
 pub const NOISE : u8 = 8;
 pub const OSC_OFF : u8 = 9;
 
+pub const OSC_TYPE_NAMES : [&str; 10] = ["Sine", "Triangle", "Square", "Square analog", "Saw 4", "Saw 64", "Saw 256", "Saw", "Noise", "Off"];
+
 #[derive(Copy, Clone)]
 pub struct Operator {
     frequency: f32,
+    base_frequency: f32,
     angular_speed: f32,
     current_angle: f32,
     pub volume: f32,
@@ -32,6 +35,7 @@ impl Operator {
     pub fn new(sample_rate: f32, osc_type: u8) -> Operator {
         return Operator {
             frequency: 0.0,
+            base_frequency: 0.0,
             angular_speed: 0.0,
             current_angle: 0.0,
             volume: 1.0,
@@ -46,23 +50,12 @@ impl Operator {
     }
 
     pub fn tick(&mut self) -> f32 {
-        self.value = self.oscillate(self.current_angle) * self.adsr.tick() * self.volume;
-        self.current_angle += self.angular_speed;
-
-        if self.current_angle > 2.0 * std::f32::consts::PI {
-            self.current_angle -= 2.0 * std::f32::consts::PI;
-        }
-
-        if self.feedback == 0.0 {
-            return self.value;
-        } else {
-            self.current_angle -= self.angular_speed;
-            return self.tick_modulated(self.value * self.feedback);
-        }
+        return self.tick_modulated(0.);
     }
 
+    // The last output is fed back to the phase whatever the operator is modulated by
     pub fn tick_modulated(&mut self, modulation: f32) -> f32 {
-        self.value = self.oscillate(self.current_angle + modulation) * self.adsr.tick() * self.volume;
+        self.value = self.oscillate(self.current_angle + modulation + self.value * self.feedback) * self.adsr.tick() * self.volume;
         self.current_angle += self.angular_speed;
         
         if self.current_angle > 2.0 * std::f32::consts::PI {
@@ -74,6 +67,7 @@ impl Operator {
 
     pub fn init(&mut self, sample_rate: f32, frequency: f32, volume: f32, osc_type: u8, phase_offset: f32, feedback: f32) {
        self.frequency = frequency;
+       self.base_frequency = frequency;
        self.angular_speed = (frequency / sample_rate) * 2.0 * std::f32::consts::PI;
        self.volume = volume;
        self.current_angle = self.angular_speed * (phase_offset * (1. / (frequency / sample_rate)));
@@ -85,6 +79,11 @@ impl Operator {
        self.time_step = 1. / sample_rate;
     }
 
+    pub fn set_bend_ratio(&mut self, bend_ratio: f32) {
+        self.frequency = self.base_frequency * bend_ratio;
+        self.angular_speed = self.frequency * self.time_step * 2.0 * std::f32::consts::PI;
+    }
+
     pub fn oscillate(&mut self, phase: f32) -> f32 {
 
         if self.osc_type == SINE {
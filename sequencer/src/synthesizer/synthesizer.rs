@@ -1,33 +1,102 @@
-use crate::midimessage::NoteEvent;
-use crate::processor::Processor;
+use crate::midimessage::{NoteEvent, AutomationPoint, CC_FILTER_CUTOFF, CC_SUSTAIN_PEDAL};
+use crate::sustain_pedal::SustainPedal;
+use crate::utils::{pitch_bend_ratio, DEFAULT_PITCH_BEND_RANGE};
+use crate::processor::{Processor, ProcessorSnapshot};
 use crate::synthesizer::synthesizervoice::SynthesizerVoice;
 
-use crate::synthesizer::operator::SINE;
-use crate::synthesizer::operator::SAW_ANALOGIC_4;
-use crate::synthesizer::operator::SAW_DIGITAL;
-use crate::synthesizer::operator::OSC_OFF;
-use crate::synthesizer::synthesizer_preset::SynthesizerPreset;
-use crate::preset::Preset;
+use crate::synthesizer::operator::OSC_TYPE_NAMES;
+use crate::synthesizer::synthesizer_preset::{SynthesizerPreset, NB_OPERATORS};
+use crate::synthesizer::algorithm::{ALGORITHM_NAMES, NB_ALGORITHMS};
+use crate::preset::{Preset, load_user_presets, PresetWriter, new_preset_name, MIN_FILTER_Q, MAX_FILTER_Q};
+use crate::load_error::LoadError;
+use crate::parameter::{Parameter, ParameterUnit, FILTER_TYPE_NAMES, filter_type_from_index, filter_type_index};
 
-use crate::decibels::db_to_gain;
+use crate::decibels::{db_to_gain, gain_to_db};
 
 use biquad::*;
-use biquad::Type;
+
+use std::error::Error;
+use std::path::Path;
 
 const MAX_VOICES : usize = 8;
+// The factory presets come first, their file names start with their position, then the user presets
+const PRESETS_DIRECTORY : &str = "synthesizer-presets";
+
+const ALGORITHM : usize = 0;
+const FILTER_TYPE : usize = 1;
+const FILTER_CUTOFF : usize = 2;
+const FILTER_Q : usize = 3;
+
+// Each operator exposes the same block of parameters, the operator parameter id is
+// OPERATORS_PARAMETERS + the operator index * NB_OPERATOR_PARAMETERS + the parameter index in the block
+const OPERATORS_PARAMETERS : usize = 4;
+const OP_OSC_TYPE : usize = 0;
+const OP_COARSE : usize = 1;
+const OP_LEVEL : usize = 2;
+const OP_PHASE_OFFSET : usize = 3;
+const OP_FEEDBACK : usize = 4;
+const OP_ATTACK : usize = 5;
+const OP_DECAY : usize = 6;
+const OP_SUSTAIN : usize = 7;
+const OP_RELEASE : usize = 8;
+const NB_OPERATOR_PARAMETERS : usize = 9;
+
+const OPERATOR_NAMES : [&str; NB_OPERATORS] = ["D", "C", "B", "A"];
 
 pub struct Synthesizer {
     voices: Vec<SynthesizerVoice>,
     nb_actives_notes: usize,
     pub id: usize,
     pub note_events: Vec<NoteEvent>,
+    pub automation_points: Vec<AutomationPoint>,
     sample_rate: f32,
     presets: Vec<SynthesizerPreset>,
     preset_id: usize,
+    bend_ratio: f32,
+    filter_cutoff_ratio: f32,
+    sustain_pedal: SustainPedal,
+    preset_writer: PresetWriter<SynthesizerPreset>,
+    errors: Vec<LoadError>,
+}
+
+pub struct SynthesizerSnapshot {
+    id: usize,
+    note_events: Vec<NoteEvent>,
+    automation_points: Vec<AutomationPoint>,
+    sample_rate: f32,
+    preset: SynthesizerPreset,
+    bend_ratio: f32,
+    filter_cutoff_ratio: f32,
+}
+
+impl ProcessorSnapshot for SynthesizerSnapshot {
+    fn into_processor(self: Box<Self>) -> Box<dyn Processor> {
+        let mut voices : Vec<SynthesizerVoice> = Vec::new();
+        for _i in 0..MAX_VOICES {
+            voices.push(SynthesizerVoice::new(self.sample_rate))
+        }
+
+        Box::new(Synthesizer {
+            voices,
+            nb_actives_notes: 0,
+            id: self.id,
+            note_events: self.note_events,
+            automation_points: self.automation_points,
+            sample_rate: self.sample_rate,
+            presets: vec![self.preset],
+            preset_id: 0,
+            bend_ratio: self.bend_ratio,
+            filter_cutoff_ratio: self.filter_cutoff_ratio,
+            sustain_pedal: SustainPedal::new(),
+            preset_writer: PresetWriter::detached(),
+            errors: Vec::new(),
+        })
+    }
 }
 
 impl Synthesizer {
-    pub fn new(sample_rate: f32, id: usize, preset_id: usize) -> Synthesizer {
+    pub fn new(sample_rate: f32, id: usize, preset_id: usize, data_path: &str) -> Synthesizer {
+        let presets_path = Path::new(data_path).join(PRESETS_DIRECTORY).to_string_lossy().to_string();
 
         let mut voices : Vec<SynthesizerVoice> = Vec::new();
 
@@ -40,73 +109,36 @@ impl Synthesizer {
             nb_actives_notes: 0,
             id: id,
             note_events: Vec::with_capacity(100),
+            automation_points: Vec::new(),
             sample_rate: sample_rate,
             presets: Vec::new(),
-            preset_id: preset_id
+            preset_id: preset_id,
+            bend_ratio: 1.,
+            filter_cutoff_ratio: 1.,
+            sustain_pedal: SustainPedal::new(),
+            preset_writer: PresetWriter::new(&presets_path),
+            errors: Vec::new(),
         };
 
-        synth.presets.push(SynthesizerPreset {
-            id: 0,
-            name: "Guitar bass".to_string(),
-            algorithm: 5,
-            nb_voices: 1,
-            filter_type: Type::LowPass,
-            filter_f0: 880.hz() as biquad::Hertz<f32>,
-            filter_q_value: biquad::Q_BUTTERWORTH_F32,
-            
-            oscx_coarse: [0.5, 0.5, 1.0, 0.5],
-            oscx_level: [db_to_gain(-25.), db_to_gain(-20.), db_to_gain(-12.0), db_to_gain(0.0)],
-            oscx_osc_type: [SINE, SINE, SINE, SAW_ANALOGIC_4],
-            oscx_phase_offset: [0.0, 0.7, 0., 0.],
-            oscx_feedback: [0.4, 0., 0., 0.],
-            oscx_adsr_attack: [0.0128, 0.00092, 0.00423, 0.00243],
-            oscx_adsr_decay: [3.38, 0.969, 60.0, 2.33],
-            oscx_adsr_sustain: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-33.), db_to_gain(-11.)],
-            oscx_adsr_release: [0.05, 6.26, 0.145, 0.05],
-        });
-
-        synth.presets.push(SynthesizerPreset {
-            id: 1,
-            name: "G-FUNK bass".to_string(),
-            algorithm: 11,
-            nb_voices: 1,
-            filter_type: Type::LowPass,
-            filter_f0: (880 * 2).hz() as biquad::Hertz<f32>,
-            filter_q_value: biquad::Q_BUTTERWORTH_F32,
-            
-            oscx_coarse: [0.5, 0.5, 1.0, 1.0],
-            oscx_level: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-100.0), db_to_gain(0.0)],
-            oscx_osc_type: [OSC_OFF, OSC_OFF, OSC_OFF, SAW_DIGITAL],
-            oscx_phase_offset: [0.0, 0.7, 0., 0.],
-            oscx_feedback: [0.4, 0., 0., 0.],
-            oscx_adsr_attack: [0.0128, 0.00092, 0.00423, 0.00243],
-            oscx_adsr_decay: [3.38, 0.969, 60.0, 0.],
-            oscx_adsr_sustain: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-33.), db_to_gain(-11.)],
-            oscx_adsr_release: [0.05, 0.2, 0.145, 0.05],
-        });
-
-        synth.presets.push(SynthesizerPreset {
-            id: 2,
-            name: "G-FUNK lead".to_string(),
-            algorithm: 11,
-            nb_voices: 1,
-            filter_type: Type::LowPass,
-            filter_f0: 10.khz(),
-            filter_q_value: biquad::Q_BUTTERWORTH_F32,
-
-            oscx_coarse: [0.5, 0.5, 3.98, 2.],
-            oscx_level: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-1.0), db_to_gain(-1.0)],
-            oscx_osc_type: [OSC_OFF, OSC_OFF, SAW_DIGITAL, SAW_DIGITAL],
-            oscx_phase_offset: [0.0, 0.0, 0., 0.],
-            oscx_feedback: [0.0, 0., 0., 0.],
-            oscx_adsr_attack: [0.0128, 0.00092, 0.00243, 0.00243],
-            oscx_adsr_decay: [3.38, 0.969, 0., 0.],
-            oscx_adsr_sustain: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-10.), db_to_gain(-2.)],
-            oscx_adsr_release: [0.05, 0.2, 0.05, 0.05],
-        });
+        synth.presets = load_user_presets::<SynthesizerPreset>(&presets_path, &mut synth.errors);
+        if synth.presets.is_empty() {
+            synth.presets.push(SynthesizerPreset::empty());
+        }
+        for (id, preset) in synth.presets.iter_mut().enumerate() {
+            preset.id = id;
+        }
+        synth.preset_id = preset_id.min(synth.presets.len() - 1);
         
         return synth;
     }
+
+    fn release_note(&mut self, midi_note: u8) {
+        for i in 0..self.voices.len() {
+            if self.voices[i].note_id == midi_note && self.voices[i].active {
+                self.voices[i].stop_note();
+            }
+        }
+    }
 }
 
 
@@ -115,6 +147,7 @@ impl Processor for Synthesizer {
     fn get_name(&self) -> String { "Synthesizer".to_string() }
 
     fn note_on(&mut self, midi_note: u8, velocity: f32) {
+        self.sustain_pedal.note_on(midi_note);
         if self.nb_actives_notes < MAX_VOICES - 1 {
             let note_to_active = self.nb_actives_notes as usize;
 
@@ -127,6 +160,7 @@ impl Processor for Synthesizer {
             self.voices[note_to_active].filter_type = preset.filter_type;
             self.voices[note_to_active].filter_f0 = preset.filter_f0;
             self.voices[note_to_active].filter_q_value = preset.filter_q_value;
+            self.voices[note_to_active].filter_cutoff_ratio = self.filter_cutoff_ratio;
 
             for operator_idx in 0..self.voices[note_to_active].operators.len() {
                 self.voices[note_to_active].operators[operator_idx].init(
@@ -144,6 +178,7 @@ impl Processor for Synthesizer {
                     preset.oscx_adsr_release[operator_idx],
                 );
             }
+            self.voices[note_to_active].set_pitch_bend_ratio(self.bend_ratio);
             
             self.voices[note_to_active].start_note(midi_note, velocity);
             self.nb_actives_notes += 1;
@@ -151,19 +186,40 @@ impl Processor for Synthesizer {
     }
 
     fn note_off(&mut self, midi_note: u8) {
-        for i in 0..self.voices.len() {
-            if self.voices[i].note_id == midi_note && self.voices[i].active {
-                self.voices[i].stop_note();
-            }
+        if !self.sustain_pedal.hold_note_off(midi_note) {
+            self.release_note(midi_note);
         }
     }
 
     fn all_note_off(&mut self) {
+        self.sustain_pedal.reset();
         for i in 0..self.voices.len() {
             self.voices[i].stop_note();
         }
     }
 
+    fn pitch_bend(&mut self, value: f32) {
+        self.bend_ratio = pitch_bend_ratio(value, DEFAULT_PITCH_BEND_RANGE);
+        for i in 0..self.nb_actives_notes {
+            self.voices[i].set_pitch_bend_ratio(self.bend_ratio);
+        }
+    }
+
+    fn control_change(&mut self, controller: u8, value: f32) {
+        if controller == CC_FILTER_CUTOFF {
+            // The cutoff controller moves the preset cutoff up to 4 octaves up or down
+            self.filter_cutoff_ratio = f32::powf(2., (value - 0.5) * 8.);
+            for i in 0..self.nb_actives_notes {
+                self.voices[i].set_filter_cutoff_ratio(self.filter_cutoff_ratio);
+            }
+        } else if controller == CC_SUSTAIN_PEDAL {
+            self.sustain_pedal.set_value(value);
+            while let Some(midi_note) = self.sustain_pedal.next_released_note() {
+                self.release_note(midi_note);
+            }
+        }
+    }
+
     fn process(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize) {
         for i in 0..self.nb_actives_notes {
             let i: usize = i as usize;
@@ -189,6 +245,19 @@ impl Processor for Synthesizer {
         self.note_events.push(midi_message);
     }
 
+    fn get_errors(&mut self) -> &mut Vec<LoadError> {
+        self.preset_writer.receive_errors(&mut self.errors);
+        return &mut self.errors;
+    }
+
+    fn get_automation_points(&mut self) -> &mut Vec<AutomationPoint> {
+        return &mut self.automation_points;
+    }
+
+    fn add_automation_point(&mut self, automation_point: AutomationPoint) {
+        self.automation_points.push(automation_point);
+    }
+
     fn get_current_preset_id(&self) -> usize {
         self.preset_id
     }
@@ -205,6 +274,141 @@ impl Processor for Synthesizer {
         return presets;
     }
 
+    fn get_parameters(&self) -> Vec<Parameter> {
+        let default = SynthesizerPreset::empty();
+        let mut parameters = Vec::new();
+
+        parameters.push(Parameter::choice(ALGORITHM, "Algorithm", &ALGORITHM_NAMES, default.algorithm as usize - 1));
+        parameters.push(Parameter::choice(FILTER_TYPE, "Filter type", &FILTER_TYPE_NAMES, filter_type_index(default.filter_type)));
+        parameters.push(Parameter::new(FILTER_CUTOFF, "Filter cutoff", 20., 20000., default.filter_f0.hz(), 10., ParameterUnit::Hertz));
+        parameters.push(Parameter::new(FILTER_Q, "Filter Q", MIN_FILTER_Q, MAX_FILTER_Q, default.filter_q_value, 0.01, ParameterUnit::None));
+
+        for op in 0..NB_OPERATORS {
+            let id = OPERATORS_PARAMETERS + op * NB_OPERATOR_PARAMETERS;
+            let name = |parameter_name: &str| format!("Op {} {}", OPERATOR_NAMES[op], parameter_name);
+
+            parameters.push(Parameter::choice(id + OP_OSC_TYPE, &name("wave"), &OSC_TYPE_NAMES, default.oscx_osc_type[op] as usize));
+            parameters.push(Parameter::new(id + OP_COARSE, &name("coarse"), 0.5, 16., default.oscx_coarse[op], 0.01, ParameterUnit::None));
+            parameters.push(Parameter::new(id + OP_LEVEL, &name("level"), -100., 0., gain_to_db(default.oscx_level[op]), 0.5, ParameterUnit::Decibels));
+            parameters.push(Parameter::new(id + OP_PHASE_OFFSET, &name("phase"), 0., 1., default.oscx_phase_offset[op], 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + OP_FEEDBACK, &name("feedback"), 0., 1., default.oscx_feedback[op], 0.01, ParameterUnit::Percent));
+            parameters.push(Parameter::new(id + OP_ATTACK, &name("attack"), 0., 10., default.oscx_adsr_attack[op], 0.001, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + OP_DECAY, &name("decay"), 0., 60., default.oscx_adsr_decay[op], 0.01, ParameterUnit::Seconds));
+            parameters.push(Parameter::new(id + OP_SUSTAIN, &name("sustain"), -100., 0., gain_to_db(default.oscx_adsr_sustain[op]), 0.5, ParameterUnit::Decibels));
+            parameters.push(Parameter::new(id + OP_RELEASE, &name("release"), 0., 10., default.oscx_adsr_release[op], 0.001, ParameterUnit::Seconds));
+        }
+
+        return parameters;
+    }
+
+    fn get_parameter(&self, id: usize) -> f32 {
+        let preset = &self.presets[self.preset_id];
+
+        if id >= OPERATORS_PARAMETERS {
+            let op = ((id - OPERATORS_PARAMETERS) / NB_OPERATOR_PARAMETERS).min(NB_OPERATORS - 1);
+            return match (id - OPERATORS_PARAMETERS) % NB_OPERATOR_PARAMETERS {
+                OP_OSC_TYPE => preset.oscx_osc_type[op] as f32,
+                OP_COARSE => preset.oscx_coarse[op],
+                OP_LEVEL => gain_to_db(preset.oscx_level[op]),
+                OP_PHASE_OFFSET => preset.oscx_phase_offset[op],
+                OP_FEEDBACK => preset.oscx_feedback[op],
+                OP_ATTACK => preset.oscx_adsr_attack[op],
+                OP_DECAY => preset.oscx_adsr_decay[op],
+                OP_SUSTAIN => gain_to_db(preset.oscx_adsr_sustain[op]),
+                _ => preset.oscx_adsr_release[op],
+            };
+        }
+
+        match id {
+            ALGORITHM => (preset.algorithm - 1) as f32,
+            FILTER_TYPE => filter_type_index(preset.filter_type) as f32,
+            FILTER_CUTOFF => preset.filter_f0.hz(),
+            _ => preset.filter_q_value,
+        }
+    }
+
+    // Operator settings are applied on the next note, filter settings are applied to the playing voices
+    fn set_parameter(&mut self, id: usize, value: f32) {
+        let preset = &mut self.presets[self.preset_id];
+
+        if id >= OPERATORS_PARAMETERS {
+            let op = ((id - OPERATORS_PARAMETERS) / NB_OPERATOR_PARAMETERS).min(NB_OPERATORS - 1);
+            match (id - OPERATORS_PARAMETERS) % NB_OPERATOR_PARAMETERS {
+                OP_OSC_TYPE => preset.oscx_osc_type[op] = (value.round() as usize).min(OSC_TYPE_NAMES.len() - 1) as u8,
+                OP_COARSE => preset.oscx_coarse[op] = value,
+                OP_LEVEL => preset.oscx_level[op] = db_to_gain(value),
+                OP_PHASE_OFFSET => preset.oscx_phase_offset[op] = value,
+                OP_FEEDBACK => preset.oscx_feedback[op] = value,
+                OP_ATTACK => preset.oscx_adsr_attack[op] = value,
+                OP_DECAY => preset.oscx_adsr_decay[op] = value,
+                OP_SUSTAIN => preset.oscx_adsr_sustain[op] = db_to_gain(value),
+                _ => preset.oscx_adsr_release[op] = value,
+            }
+            return;
+        }
+
+        match id {
+            ALGORITHM => preset.algorithm = (value.round() as usize).min(NB_ALGORITHMS - 1) as u8 + 1,
+            FILTER_TYPE => preset.filter_type = filter_type_from_index(value.round() as usize),
+            FILTER_CUTOFF => preset.filter_f0 = value.hz(),
+            _ => preset.filter_q_value = value,
+        }
+
+        let (filter_type, filter_f0, filter_q_value) = (preset.filter_type, preset.filter_f0, preset.filter_q_value);
+        for i in 0..self.nb_actives_notes {
+            self.voices[i].set_filter(filter_type, filter_f0, filter_q_value);
+        }
+    }
+
+    fn save_current_preset(&mut self) -> Result<(), Box<dyn Error>> {
+        let names = self.presets.iter().map(|preset| preset.name.clone()).collect();
+        let mut preset = self.presets[self.preset_id].clone();
+        preset.name = new_preset_name(&preset.name, &names);
+        preset.id = self.presets.len();
+
+        self.preset_writer.save(preset.clone());
+
+        self.presets.push(preset);
+        self.set_current_preset_id(self.presets.len() - 1);
+        Ok(())
+    }
+
+    fn have_parameters_changed(&mut self) -> bool {
+        return false;
+    }
+
+    fn add_audio(&mut self, _name: &str, _left_channel: &mut Vec<f32>, _right_channel: &mut Vec<f32>, _sliced: bool) -> Result<(), Box<dyn Error>> {
+        Err("The Synthesizer doesn't play audio".into())
+    }
+
+    fn snapshot(&self) -> Box<dyn ProcessorSnapshot> {
+        Box::new(SynthesizerSnapshot {
+            id: self.id,
+            note_events: self.note_events.clone(),
+            automation_points: self.automation_points.clone(),
+            sample_rate: self.sample_rate,
+            preset: self.presets[self.preset_id].clone(),
+            bend_ratio: self.bend_ratio,
+            filter_cutoff_ratio: self.filter_cutoff_ratio,
+        })
+    }
+
+    fn set_armed(&mut self, _armed: bool) {
+
+    }
+
+    fn reserve_take(&mut self, _reserved: bool) {
+
+    }
+
+    fn record_input(&mut self, _inputs: &[f32], _num_samples: usize, _nb_channels: usize) {
+
+    }
+
+    fn set_tempo(&mut self, _bpm: f32) {
+        
+    }
+
     fn prepare(&mut self, _sample_rate: f32, _num_samples: usize, _nb_channels: usize) {
         
     }
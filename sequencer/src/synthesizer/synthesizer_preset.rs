@@ -1,18 +1,27 @@
 
-const NB_OPERATORS : usize = 4;
+pub const NB_OPERATORS : usize = 4;
 
 use crate::preset::Preset;
+use crate::synthesizer::operator::{SINE, OSC_OFF};
+use crate::decibels::db_to_gain;
 
-// use biquad;
+use biquad::*;
+use biquad::Type;
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SynthesizerPreset {
     pub id: usize,
     pub name: String,
+    #[serde(deserialize_with = "algorithm_serde::deserialize")]
     pub algorithm: u8,
     pub nb_voices: usize, 
+    #[serde(with = "crate::preset::filter_type_serde")]
     pub filter_type: biquad::Type<f32>,
+    #[serde(with = "crate::preset::hertz_serde")]
     pub filter_f0: biquad::Hertz<f32>,
+    #[serde(deserialize_with = "crate::preset::filter_q_serde::deserialize")]
     pub filter_q_value: f32,
 
     pub oscx_coarse: [f32 ;NB_OPERATORS],
@@ -26,6 +35,29 @@ pub struct SynthesizerPreset {
     pub oscx_adsr_sustain: [f32; NB_OPERATORS],
 }
 
+impl SynthesizerPreset {
+    pub fn empty() -> SynthesizerPreset {
+        SynthesizerPreset {
+            id: 0,
+            name: "Empty".to_string(),
+            algorithm: 1,
+            nb_voices: 1,
+            filter_type: Type::LowPass,
+            filter_f0: 10.khz(),
+            filter_q_value: biquad::Q_BUTTERWORTH_F32,
+
+            oscx_coarse: [1.0, 1.0, 1.0, 1.0],
+            oscx_level: [db_to_gain(-100.), db_to_gain(-100.), db_to_gain(-100.), db_to_gain(0.)],
+            oscx_osc_type: [OSC_OFF, OSC_OFF, OSC_OFF, SINE],
+            oscx_phase_offset: [0., 0., 0., 0.],
+            oscx_feedback: [0., 0., 0., 0.],
+            oscx_adsr_attack: [0.00423, 0.00423, 0.00423, 0.00423],
+            oscx_adsr_decay: [0.38, 0.38, 0.38, 0.38],
+            oscx_adsr_sustain: [db_to_gain(0.), db_to_gain(0.), db_to_gain(0.), db_to_gain(0.)],
+            oscx_adsr_release: [0.1, 0.1, 0.1, 0.1],
+        }
+    }
+}
 
 impl Preset for SynthesizerPreset {
     fn get_id(self) -> usize {
@@ -35,4 +67,17 @@ impl Preset for SynthesizerPreset {
     fn get_name(&self) -> String {
         return self.name.clone();
     }
-}
\ No newline at end of file
+}
+mod algorithm_serde {
+    use crate::synthesizer::algorithm::is_known_algorithm;
+    use serde::{Deserialize, Deserializer};
+    use serde::de::Error;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+        let algorithm = u8::deserialize(deserializer)?;
+        if !is_known_algorithm(algorithm) {
+            return Err(D::Error::custom(format!("unknown algorithm {}", algorithm)));
+        }
+        return Ok(algorithm);
+    }
+}
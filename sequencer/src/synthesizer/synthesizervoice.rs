@@ -1,12 +1,12 @@
 use crate::synthesizer::operator::Operator;
 use crate::synthesizer::operator::SINE;
+use crate::synthesizer::algorithm::algorithm;
+use crate::synthesizer::synthesizer_preset::NB_OPERATORS;
 
 use biquad::*;
 use biquad::Type;
 use biquad::DirectForm1;
 
-const NB_OPERATORS : usize = 4;
-
 pub const OP_A : usize = 3;
 pub const OP_B : usize = 2;
 pub const OP_C : usize = 1;
@@ -22,7 +22,8 @@ pub struct SynthesizerVoice {
     pub biquad_filter: DirectForm1::<f32>,
     pub filter_type: biquad::Type<f32>,
     pub filter_f0: biquad::Hertz<f32>,
-    pub filter_q_value: f32
+    pub filter_q_value: f32,
+    pub filter_cutoff_ratio: f32,
 }
 
 impl SynthesizerVoice {
@@ -44,18 +45,17 @@ impl SynthesizerVoice {
                 Operator::new(sample_rate, SINE),
             ],
             sample_rate: sample_rate,
-            algorithm: 5,
+            algorithm: 1,
             biquad_filter: biquad_filter,
             filter_type: Type::AllPass,
             filter_f0: f0,
-            filter_q_value: biquad::Q_BUTTERWORTH_F32
+            filter_q_value: biquad::Q_BUTTERWORTH_F32,
+            filter_cutoff_ratio: 1.,
         };
     }
 
     pub fn start_note(&mut self, midi_note :u8, _velocity: f32) {
-        let coeffs = Coefficients::<f32>::from_params(self.filter_type, self.sample_rate.hz(), self.filter_f0, biquad::Q_BUTTERWORTH_F32).unwrap();
-        self.biquad_filter = DirectForm1::<f32>::new(coeffs);
-        
+        self.update_filter();
         self.biquad_filter.reset_state();
         self.note_id = midi_note;
         for i in 0..NB_OPERATORS {
@@ -65,6 +65,36 @@ impl SynthesizerVoice {
         self.active = true;
     }
 
+    // The filter keeps its coefficients when the new ones can't be computed
+    fn update_filter(&mut self) {
+        let cutoff = (self.filter_f0.hz() * self.filter_cutoff_ratio).clamp(20., self.sample_rate * 0.45);
+        if let Ok(coefficients) = Coefficients::<f32>::from_params(self.filter_type, self.sample_rate.hz(), cutoff.hz(), self.filter_q_value) {
+            self.biquad_filter.update_coefficients(coefficients);
+        }
+    }
+
+    pub fn set_filter_cutoff_ratio(&mut self, filter_cutoff_ratio: f32) {
+        self.filter_cutoff_ratio = filter_cutoff_ratio;
+        if self.active {
+            self.update_filter();
+        }
+    }
+
+    pub fn set_filter(&mut self, filter_type: biquad::Type<f32>, filter_f0: biquad::Hertz<f32>, filter_q_value: f32) {
+        self.filter_type = filter_type;
+        self.filter_f0 = filter_f0;
+        self.filter_q_value = filter_q_value;
+        if self.active {
+            self.update_filter();
+        }
+    }
+
+    pub fn set_pitch_bend_ratio(&mut self, bend_ratio: f32) {
+        for i in 0..NB_OPERATORS {
+            self.operators[i].set_bend_ratio(bend_ratio);
+        }
+    }
+
     pub fn stop_note(&mut self) {
         for i in 0..NB_OPERATORS {
             self.operators[i].adsr.note_off();
@@ -74,46 +104,19 @@ impl SynthesizerVoice {
     pub fn render_next_block(&mut self, outputs: &mut [f32], num_samples: usize, nb_channels: usize) { 
         let mut out = 0.0;
         let mut idx = 0;
+        let algorithm = algorithm(self.algorithm);
         while idx < nb_channels * num_samples {
 
-            if self.algorithm == 1 {
-                self.operators[OP_D].tick();
-                self.operators[OP_C].tick_modulated(self.operators[OP_D].value);
-                self.operators[OP_B].tick_modulated(self.operators[OP_C].value);
-                let op0 = self.operators[OP_A].tick_modulated(self.operators[OP_B].value);
-                out = op0;
-            }
-
-            if self.algorithm == 5 {
-                self.operators[OP_D].tick();
-                self.operators[OP_C].tick_modulated(self.operators[OP_D].value);
-                let op1 = self.operators[OP_B].tick_modulated(self.operators[OP_C].value);
-                let op0 = self.operators[OP_A].tick_modulated(self.operators[OP_C].value);
-                out = op1 + op0;
-            }
-
-            if self.algorithm == 6 {
-                self.operators[OP_D].tick();
-                self.operators[OP_C].tick_modulated(self.operators[OP_D].value);
-                let op1 = self.operators[OP_B].tick_modulated(self.operators[OP_C].value);
-                let op0 = self.operators[OP_A].tick();
-                out = op0 + op1;
-            }
-
-            if self.algorithm == 8 {
-                self.operators[OP_D].tick();
-                let op_c = self.operators[OP_C].tick_modulated(self.operators[OP_D].value);
-                self.operators[OP_B].tick();
-                let op_a = self.operators[OP_A].tick_modulated(self.operators[OP_B].value);
-                out = op_a + op_c;
-            }
-
-            if self.algorithm == 11 {
-                let op3 = self.operators[OP_D].tick();
-                let op2 = self.operators[OP_C].tick();
-                let op1 = self.operators[OP_B].tick();
-                let op0 = self.operators[OP_A].tick();
-                out = op3 + op2 + op1 + op0;
+            // D is ticked first, each operator is modulated by the operators before it
+            let mut values = [0.; NB_OPERATORS];
+            out = 0.;
+            for op in 0..NB_OPERATORS {
+                let mut modulation = 0.;
+                for modulator in 0..op {
+                    modulation += algorithm.modulations[op][modulator] * values[modulator];
+                }
+                values[op] = self.operators[op].tick_modulated(modulation);
+                out += algorithm.outputs[op] * values[op];
             }
 
             out = self.biquad_filter.run(out);
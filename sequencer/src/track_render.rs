@@ -0,0 +1,247 @@
+use crate::processor::{Processor, ProcessorSnapshot};
+use crate::midimessage::{AutomationPlayer, AutomationTarget, CC_VOLUME};
+use crate::decibels::db_to_gain;
+
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::thread;
+
+// The notes still ringing after a rendered loop are rendered until they fade under this level
+const RENDER_SILENCE_DB : f32 = -80.;
+const MAX_RENDER_TAIL_DURATION : f32 = 4.;
+
+// Timing of the sequencer when the render is asked, the render plays its ticks and blocks like process does
+#[derive(Clone, Copy)]
+pub struct RenderTiming {
+    pub nb_ticks: i32,
+    pub tick_time: f32,
+    pub elapsed_time_each_render: f32,
+    pub sample_rate: f32,
+    pub buffer_size: usize,
+    pub nb_channels: usize,
+}
+
+impl RenderTiming {
+    pub fn loop_size(&self) -> usize {
+        return (self.nb_ticks as f32 * self.tick_time * self.sample_rate).round() as usize;
+    }
+}
+
+// Audio of one track rendered offline, one loop of its pattern followed by the tail of the last notes
+pub struct TrackRender {
+    pub left_channel: Vec<f32>,
+    pub right_channel: Vec<f32>,
+    pub loop_size: usize,
+}
+
+impl TrackRender {
+    // The tail is mixed over the start of the loop like when the pattern plays again, so it loops without a gap
+    pub fn into_loop(self) -> (Vec<f32>, Vec<f32>) {
+        let TrackRender { mut left_channel, mut right_channel, loop_size } = self;
+        if loop_size == 0 {
+            return (Vec::new(), Vec::new());
+        }
+        for i in loop_size..left_channel.len() {
+            left_channel[i % loop_size] += left_channel[i];
+            right_channel[i % loop_size] += right_channel[i];
+        }
+        left_channel.truncate(loop_size);
+        right_channel.truncate(loop_size);
+        return (left_channel, right_channel);
+    }
+
+    pub fn into_one_shot(self) -> (Vec<f32>, Vec<f32>) {
+        return (self.left_channel, self.right_channel);
+    }
+}
+
+// Loop of a frozen track, played in place of its processor while the sequencer plays
+pub struct FrozenTrack {
+    left_channel: Vec<f32>,
+    right_channel: Vec<f32>,
+}
+
+impl FrozenTrack {
+    pub fn new(render: TrackRender) -> FrozenTrack {
+        let (left_channel, right_channel) = render.into_loop();
+        FrozenTrack {
+            left_channel,
+            right_channel,
+        }
+    }
+
+    // position is the frame of the loop at the start of the block
+    pub fn play(&self, outputs: &mut [f32], position: usize, num_samples: usize, nb_channels: usize) {
+        if self.left_channel.is_empty() {
+            return;
+        }
+        for frame in 0..num_samples {
+            let idx = (position + frame) % self.left_channel.len();
+            outputs[frame * nb_channels] = self.left_channel[idx];
+            outputs[frame * nb_channels + 1] = self.right_channel[idx];
+        }
+    }
+}
+
+// Plays the notes and the automation of a copy of a track for one loop, then its tail until it's silent
+// The blocks and the ticks follow each other like in process so the render sounds like the live track, before its volume
+pub fn render_track(processor: &mut dyn Processor, timing: &RenderTiming) -> TrackRender {
+    let nb_ticks = timing.nb_ticks;
+    let loop_size = timing.loop_size();
+    let max_size = loop_size + (MAX_RENDER_TAIL_DURATION * timing.sample_rate) as usize;
+    let silence = db_to_gain(RENDER_SILENCE_DB);
+    let note_events = processor.get_notes_events().clone();
+    let automation_points = processor.get_automation_points().clone();
+    let mut automation_player = AutomationPlayer::new();
+
+    // Notes held over the end of the loop are released in the tail
+    let last_wrapped_tick = note_events.iter()
+        .filter(|note_event| note_event.tick_off != -1 && note_event.tick_off < note_event.tick_on)
+        .map(|note_event| note_event.tick_off)
+        .max()
+        .unwrap_or(0);
+
+    let mut render = TrackRender {
+        left_channel: Vec::with_capacity(max_size),
+        right_channel: Vec::with_capacity(max_size),
+        loop_size,
+    };
+    let mut outputs = vec![0.; timing.buffer_size * timing.nb_channels];
+    let mut time_accumulated = 0.;
+    let mut tick = 0;
+    while render.left_channel.len() < max_size {
+        time_accumulated += timing.elapsed_time_each_render;
+        while time_accumulated >= timing.tick_time {
+            time_accumulated -= timing.tick_time;
+            let is_in_loop = tick < nb_ticks;
+            for note_event in note_events.iter() {
+                if note_event.tick_off != -1 {
+                    if is_in_loop && note_event.tick_on == tick {
+                        processor.note_on(note_event.note_id, note_event.velocity);
+                    }
+                    if note_event.tick_off == tick % nb_ticks {
+                        processor.note_off(note_event.note_id);
+                    }
+                }
+            }
+            if is_in_loop {
+                automation_player.play(&automation_points, tick, |_| true);
+                for k in 0..automation_player.values.len() {
+                    let (target, value) = automation_player.values[k];
+                    match target {
+                        AutomationTarget::PitchBend => processor.pitch_bend(value),
+                        // The volume is applied when the render is played
+                        AutomationTarget::ControlChange(CC_VOLUME) => {},
+                        AutomationTarget::ControlChange(controller) => processor.control_change(controller, value),
+                        AutomationTarget::Parameter(id) => processor.set_parameter(id, value),
+                    }
+                }
+            }
+            tick += 1;
+        }
+
+        for s in 0..outputs.len() {
+            outputs[s] = 0.;
+        }
+        processor.process(&mut outputs, timing.buffer_size, timing.nb_channels);
+
+        let mut peak: f32 = 0.;
+        for frame in 0..timing.buffer_size {
+            let left = outputs[frame * timing.nb_channels];
+            let right = outputs[frame * timing.nb_channels + 1];
+            render.left_channel.push(left);
+            render.right_channel.push(right);
+            peak = peak.max(left.abs()).max(right.abs());
+        }
+
+        if render.left_channel.len() >= loop_size && tick > nb_ticks + last_wrapped_tick && peak < silence {
+            break;
+        }
+    }
+    render.left_channel.truncate(max_size);
+    render.right_channel.truncate(max_size);
+    return render;
+}
+
+pub enum RenderResult {
+    // A new loop for a frozen track
+    Frozen { processor_idx: usize, frozen_track: FrozenTrack },
+    // The loop of the track or the loop followed by its tail, for the first next track taking audio
+    Bounce { processor_idx: usize, sliced: bool, left_channel: Vec<f32>, right_channel: Vec<f32> },
+}
+
+enum RenderRequest {
+    Freeze { processor_idx: usize, snapshot: Box<dyn ProcessorSnapshot>, timing: RenderTiming },
+    Bounce { processor_idx: usize, sliced: bool, snapshot: Box<dyn ProcessorSnapshot>, timing: RenderTiming },
+    // Audio the audio thread is done with, freed here so it doesn't deallocate
+    Drop(Vec<f32>, Vec<f32>),
+    DropFrozenTrack(FrozenTrack),
+}
+
+// Renders the tracks in a thread on copies of their processors built from snapshots, the live ones keep playing meanwhile
+pub struct TrackRenderer {
+    requests: Sender<RenderRequest>,
+    results: Receiver<RenderResult>,
+}
+
+impl TrackRenderer {
+    pub fn new() -> TrackRenderer {
+        let (requests, request_receiver) = mpsc::channel::<RenderRequest>();
+        let (result_sender, results) = mpsc::channel::<RenderResult>();
+        thread::spawn(move || run(request_receiver, result_sender));
+        TrackRenderer {
+            requests,
+            results,
+        }
+    }
+
+    pub fn freeze(&self, processor_idx: usize, snapshot: Box<dyn ProcessorSnapshot>, timing: RenderTiming) {
+        // The thread only stops when the renderer is dropped
+        let _ = self.requests.send(RenderRequest::Freeze { processor_idx, snapshot, timing });
+    }
+
+    pub fn bounce(&self, processor_idx: usize, sliced: bool, snapshot: Box<dyn ProcessorSnapshot>, timing: RenderTiming) {
+        let _ = self.requests.send(RenderRequest::Bounce { processor_idx, sliced, snapshot, timing });
+    }
+
+    pub fn drop_audio(&self, left_channel: Vec<f32>, right_channel: Vec<f32>) {
+        let _ = self.requests.send(RenderRequest::Drop(left_channel, right_channel));
+    }
+
+    pub fn drop_frozen_track(&self, frozen_track: FrozenTrack) {
+        let _ = self.requests.send(RenderRequest::DropFrozenTrack(frozen_track));
+    }
+
+    pub fn try_receive(&self) -> Option<RenderResult> {
+        return self.results.try_recv().ok();
+    }
+}
+
+fn run(requests: Receiver<RenderRequest>, results: Sender<RenderResult>) {
+    for request in requests.iter() {
+        let result = match request {
+            RenderRequest::Freeze { processor_idx, snapshot, timing } => {
+                let mut processor = snapshot.into_processor();
+                let frozen_track = FrozenTrack::new(render_track(processor.as_mut(), &timing));
+                RenderResult::Frozen { processor_idx, frozen_track }
+            },
+            RenderRequest::Bounce { processor_idx, sliced, snapshot, timing } => {
+                let mut processor = snapshot.into_processor();
+                let render = render_track(processor.as_mut(), &timing);
+                let (left_channel, right_channel) = if sliced { render.into_loop() } else { render.into_one_shot() };
+                RenderResult::Bounce { processor_idx, sliced, left_channel, right_channel }
+            },
+            RenderRequest::Drop(left_channel, right_channel) => {
+                drop(left_channel);
+                drop(right_channel);
+                continue;
+            },
+            RenderRequest::DropFrozenTrack(frozen_track) => {
+                drop(frozen_track);
+                continue;
+            },
+        };
+        if results.send(result).is_err() {
+            return;
+        }
+    }
+}
@@ -13,4 +13,10 @@ pub fn set_panic_hook() {
 pub fn midi_note_fo_hertz(midi_note: u8) -> f32 {
     let a = 440.;
     (a / 32.) * f32::powf(2., (midi_note as f32 - 9.) / 12.0)
-}
\ No newline at end of file
+}
+pub const DEFAULT_PITCH_BEND_RANGE: f32 = 2.;
+
+// bend is in [-1, 1], range in semitones
+pub fn pitch_bend_ratio(bend: f32, range: f32) -> f32 {
+    f32::powf(2., bend * range / 12.)
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{data_path, process, loop_size, SAMPLE_RATE, BUFFER_SIZE, MOOD_IDX};
+
+use sequencer::Sequencer;
+use sequencer::sequencer_data::SequencerData;
+use sequencer::midimessage::CC_VOLUME;
+
+#[test]
+fn automated_volumes_are_sent_to_the_ui() {
+    let path = data_path("automation-volume");
+    let (mut sequencer, _sender) = Sequencer::new(SAMPLE_RATE, BUFFER_SIZE, &path.to_string_lossy());
+    let (mut data_ui, ui_sender) = SequencerData::new();
+    data_ui.instruments = sequencer.data.instruments.clone();
+    sequencer.audio_state_senders.push(ui_sender);
+    sequencer.data.metronome_active = false;
+    sequencer.data.instrument_selected_id = MOOD_IDX;
+
+    sequencer.data.is_recording = true;
+    sequencer.data.is_playing = true;
+    process(&mut sequencer, 2);
+    sequencer.control_change(CC_VOLUME, 0.25);
+    sequencer.data.is_recording = false;
+
+    // Turned up by hand, the recorded point turns it down again on the next loops
+    sequencer.control_change(CC_VOLUME, 1.);
+    process(&mut sequencer, 1);
+    data_ui.process_messages();
+    assert_eq!(data_ui.instruments[MOOD_IDX].volume, 1.);
+    let nb_blocks = 3 * loop_size(&sequencer) / BUFFER_SIZE;
+    process(&mut sequencer, nb_blocks);
+    data_ui.process_messages();
+    assert_eq!(sequencer.data.instruments[MOOD_IDX].volume, 0.25);
+    assert_eq!(data_ui.instruments[MOOD_IDX].volume, 0.25);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
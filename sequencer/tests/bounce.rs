@@ -0,0 +1,87 @@
+mod common;
+
+use common::{data_path, process, sequencer_with_chord, loop_size, BUFFER_SIZE, MOOD_IDX, SAMPLER_IDX};
+
+use sequencer::Sequencer;
+use sequencer::sampler::wav::read_wav;
+
+use std::path::Path;
+use std::time::Duration;
+use std::thread;
+
+// The wav is renamed into place once written, it is complete as soon as it's there
+fn wait_for_file(directory: &Path, sequencer: &mut Sequencer) -> (Vec<f32>, Vec<f32>) {
+    for _ in 0..200 {
+        process(sequencer, 1);
+        let wav = std::fs::read_dir(directory).ok()
+            .and_then(|entries| entries.filter_map(|entry| entry.ok()).find(|entry| entry.path().extension().map_or(false, |extension| extension == "wav")));
+        if let Some(entry) = wav {
+            let (left_channel, right_channel, _) = read_wav(&entry.path().to_string_lossy()).unwrap();
+            return (left_channel, right_channel);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("no bounce in {:?}", directory);
+}
+
+#[test]
+fn bounces_a_track_into_a_new_pad() {
+    let path = data_path("bounce-pad");
+    let mut sequencer = sequencer_with_chord(&path);
+    let nb_parameters = sequencer.data.instruments[SAMPLER_IDX].parameters.len();
+
+    sequencer.data.bounce_instrument = Some((MOOD_IDX, false));
+    // The empty kit has no preset.json, its pads go to the recordings kit
+    let (left_channel, _) = wait_for_file(&path.join("sampler-presets").join("Recordings"), &mut sequencer);
+
+    // The loop and the release of the chord
+    assert!(left_channel.len() >= loop_size(&sequencer));
+    assert!(left_channel.iter().any(|value| value.abs() > 0.01));
+    for _ in 0..200 {
+        if sequencer.data.instruments[SAMPLER_IDX].parameters.len() > nb_parameters {
+            break;
+        }
+        process(&mut sequencer, 1);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(sequencer.data.instruments[SAMPLER_IDX].parameters.len() > nb_parameters);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn bounces_a_track_into_a_sliced_loop() {
+    let path = data_path("bounce-loop");
+    let mut sequencer = sequencer_with_chord(&path);
+    let nb_presets = sequencer.data.instruments[SAMPLER_IDX].presets.len();
+
+    sequencer.data.bounce_instrument = Some((MOOD_IDX, true));
+    let (left_channel, _) = wait_for_file(&path.join("sampler-loops"), &mut sequencer);
+
+    assert_eq!(left_channel.len(), loop_size(&sequencer));
+    let instrument = &sequencer.data.instruments[SAMPLER_IDX];
+    assert_eq!(instrument.presets.len(), nb_presets + 1);
+    assert_eq!(instrument.current_preset_id, nb_presets);
+    assert!(instrument.presets[nb_presets].starts_with("Slices - bounce-mood-"));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn bouncing_leaves_the_live_track_playing() {
+    let path = data_path("bounce-live");
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.note_on(72, 127);
+    let outputs = process(&mut sequencer, 8);
+    let peak_before = outputs[7 * BUFFER_SIZE * 2..].iter().fold(0., |peak: f32, value| peak.max(value.abs()));
+
+    sequencer.data.bounce_instrument = Some((MOOD_IDX, false));
+    let outputs = process(&mut sequencer, 8);
+    let peak_after = outputs[7 * BUFFER_SIZE * 2..].iter().fold(0., |peak: f32, value| peak.max(value.abs()));
+
+    // The held note isn't released by the render
+    assert!(peak_before > 0.01);
+    assert!(peak_after > 0.5 * peak_before, "{} after {}", peak_after, peak_before);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
@@ -0,0 +1,81 @@
+// Helpers shared by the integration tests, each test file uses only some of them
+#![allow(dead_code)]
+
+use sequencer::Sequencer;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::thread;
+
+pub const SAMPLE_RATE : f32 = 8000.;
+pub const BUFFER_SIZE : usize = 512;
+pub const MOOD_IDX : usize = 3;
+pub const SAMPLER_IDX : usize = 1;
+
+pub fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// Data folder with the factory presets of the instruments and no kit, the samplers have an empty one
+pub fn data_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("daz-studio-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    for directory in ["epiano-presets", "mood-presets", "synthesizer-presets"] {
+        let factory_presets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../data").join(directory);
+        std::fs::create_dir_all(path.join(directory)).unwrap();
+        for entry in std::fs::read_dir(factory_presets).unwrap() {
+            let filepath = entry.unwrap().path();
+            std::fs::copy(&filepath, path.join(directory).join(filepath.file_name().unwrap())).unwrap();
+        }
+    }
+    return path;
+}
+
+// The interleaved outputs of the blocks, one after the other
+pub fn process(sequencer: &mut Sequencer, nb_blocks: usize) -> Vec<f32> {
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    let mut recorded = Vec::new();
+    for _ in 0..nb_blocks {
+        sequencer.process(&mut outputs, BUFFER_SIZE, 2);
+        recorded.extend_from_slice(&outputs);
+    }
+    return recorded;
+}
+
+// A chord held over the end of the loop on the Mood, the sequencer is stopped and silent
+pub fn sequencer_with_chord(path: &Path) -> Sequencer {
+    let (mut sequencer, _sender) = Sequencer::new(SAMPLE_RATE, BUFFER_SIZE, &path.to_string_lossy());
+    sequencer.data.metronome_active = false;
+    sequencer.data.instrument_selected_id = MOOD_IDX;
+    sequencer.data.is_playing = true;
+    let nb_blocks_per_loop = loop_size(&sequencer) / BUFFER_SIZE;
+    process(&mut sequencer, nb_blocks_per_loop - 4);
+    for note in [60, 64, 67] {
+        sequencer.note_on(note, 127);
+    }
+    process(&mut sequencer, 8);
+    for note in [60, 64, 67] {
+        sequencer.note_off(note);
+    }
+    sequencer.data.is_playing = false;
+    process(&mut sequencer, 40);
+    sequencer.data.tick = 0;
+    return sequencer;
+}
+
+pub fn loop_size(sequencer: &Sequencer) -> usize {
+    return (sequencer.data.nb_ticks() as f32 * sequencer.data.tick_time * SAMPLE_RATE).round() as usize;
+}
+
+// The renders of the frozen tracks come back from the renderer thread in process
+pub fn wait_for_renders(sequencer: &mut Sequencer) {
+    for _ in 0..200 {
+        process(sequencer, 1);
+        if !sequencer.is_rendering_frozen_tracks() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("frozen tracks still rendering");
+}
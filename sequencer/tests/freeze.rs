@@ -0,0 +1,108 @@
+mod common;
+
+use common::{data_path, process, sequencer_with_chord, loop_size, wait_for_renders, BUFFER_SIZE, MOOD_IDX};
+
+use sequencer::Sequencer;
+
+// Two loops, the second one has the tail of the first one
+fn play_two_loops(sequencer: &mut Sequencer) -> Vec<f32> {
+    sequencer.data.is_playing = true;
+    let nb_blocks = 2 * loop_size(sequencer) / BUFFER_SIZE;
+    return process(sequencer, nb_blocks);
+}
+
+#[test]
+fn frozen_tracks_sound_like_the_live_ones() {
+    let path = data_path("freeze");
+    let live = play_two_loops(&mut sequencer_with_chord(&path));
+
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.data.instruments[MOOD_IDX].is_frozen = true;
+    wait_for_renders(&mut sequencer);
+    let frozen = play_two_loops(&mut sequencer);
+
+    assert!(live.iter().any(|value| value.abs() > 0.01));
+    // Live ticks are played at the start of the block they fall in, so each live note can move by up to a block from the render
+    let nb_blocks_per_loop = loop_size(&sequencer) / BUFFER_SIZE;
+    for block in (nb_blocks_per_loop + 1..2 * nb_blocks_per_loop - 1).step_by(4) {
+        let window = block * BUFFER_SIZE..(block + 4).min(2 * nb_blocks_per_loop - 1) * BUFFER_SIZE;
+        let distance = |lag: usize| window.clone()
+            .map(|frame| (live[(frame + lag - BUFFER_SIZE) * 2] - frozen[frame * 2]).abs())
+            .fold(0., f32::max);
+        assert!((0..2 * BUFFER_SIZE).any(|lag| distance(lag) < 1e-4), "no match at block {}", block);
+    }
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn frozen_tracks_ignore_the_keyboard() {
+    let path = data_path("freeze-keyboard");
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.data.instruments[MOOD_IDX].is_frozen = true;
+    wait_for_renders(&mut sequencer);
+
+    sequencer.note_on(72, 127);
+    let outputs = process(&mut sequencer, 4);
+    assert!(outputs.iter().all(|value| *value == 0.));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn edits_render_frozen_tracks_again() {
+    let path = data_path("freeze-edit");
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.data.instruments[MOOD_IDX].is_frozen = true;
+    wait_for_renders(&mut sequencer);
+    let before = play_two_loops(&mut sequencer);
+
+    // A new preset while the track stays frozen
+    sequencer.data.is_playing = false;
+    sequencer.data.tick = 0;
+    sequencer.data.instruments[MOOD_IDX].current_preset_id += 1;
+    wait_for_renders(&mut sequencer);
+    sequencer.data.tick = 0;
+    let after = play_two_loops(&mut sequencer);
+
+    assert!(after.iter().any(|value| value.abs() > 0.01));
+    assert!(before.iter().zip(after.iter()).any(|(before, after)| (before - after).abs() > 0.01));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn the_last_render_plays_while_an_edit_is_rendered() {
+    let path = data_path("freeze-pending");
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.data.instruments[MOOD_IDX].is_frozen = true;
+    wait_for_renders(&mut sequencer);
+
+    // The frozen loop starts with the tail of the chord held over its end, the live track is silent
+    sequencer.data.instruments[MOOD_IDX].current_preset_id += 1;
+    sequencer.data.is_playing = true;
+    let outputs = process(&mut sequencer, 1);
+    assert!(sequencer.is_rendering_frozen_tracks());
+    assert!(outputs.iter().any(|value| value.abs() > 0.01));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn frozen_tracks_have_no_voices_left_once_unfrozen() {
+    let path = data_path("freeze-voices");
+    let mut sequencer = sequencer_with_chord(&path);
+    sequencer.data.instruments[MOOD_IDX].is_frozen = true;
+    wait_for_renders(&mut sequencer);
+
+    // Stopped while the chord held over the end of the loop is on
+    sequencer.data.is_playing = true;
+    let nb_blocks = 3 * loop_size(&sequencer) / BUFFER_SIZE - 2;
+    process(&mut sequencer, nb_blocks);
+    sequencer.data.is_playing = false;
+    sequencer.data.instruments[MOOD_IDX].is_frozen = false;
+    let outputs = process(&mut sequencer, 1);
+    assert!(outputs.iter().all(|value| *value == 0.));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
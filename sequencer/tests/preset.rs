@@ -0,0 +1,90 @@
+mod common;
+
+use common::{data_path, process, SAMPLE_RATE, BUFFER_SIZE};
+
+use sequencer::Sequencer;
+use sequencer::preset::{load_user_presets, save_user_preset};
+use sequencer::synthesizer::synthesizer_preset::SynthesizerPreset;
+use sequencer::mood::mood_preset::MoodPreset;
+use sequencer::load_error::LoadError;
+use sequencer::synthesizer::synthesizer::Synthesizer;
+use sequencer::mood::mood::Mood;
+use sequencer::processor::Processor;
+
+use std::time::Duration;
+use std::thread;
+
+fn preset(name: &str) -> SynthesizerPreset {
+    let mut preset = SynthesizerPreset::empty();
+    preset.name = name.to_string();
+    return preset;
+}
+
+#[test]
+fn presets_with_the_same_file_name_are_both_kept() {
+    let path = data_path("preset-file-names");
+    let directory = path.to_string_lossy();
+    for name in ["Bass 1", "Bass-1", "bass 1"] {
+        save_user_preset(&directory, name, &preset(name)).unwrap();
+    }
+
+    let mut errors = Vec::new();
+    let mut names : Vec<String> = load_user_presets::<SynthesizerPreset>(&directory, &mut errors).into_iter().map(|preset| preset.name).collect();
+    assert!(errors.is_empty());
+    names.sort();
+    assert_eq!(names, ["Bass 1", "Bass-1", "bass 1"]);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn presets_with_a_filter_q_out_of_range_are_not_loaded() {
+    let path = data_path("preset-filter-q");
+    let directory = path.to_string_lossy();
+    let mut synthesizer_preset = serde_json::to_value(&preset("Bass")).unwrap();
+    synthesizer_preset["filter_q_value"] = (-1.).into();
+    std::fs::write(path.join("bass.json"), synthesizer_preset.to_string()).unwrap();
+    let mut mood_preset = serde_json::to_value(&MoodPreset::empty()).unwrap();
+    mood_preset["filter_q_value"] = 20.into();
+    std::fs::write(path.join("mood.json"), mood_preset.to_string()).unwrap();
+
+    let mut errors = Vec::new();
+    assert!(load_user_presets::<SynthesizerPreset>(&directory, &mut errors).is_empty());
+    assert!(load_user_presets::<MoodPreset>(&directory, &mut errors).is_empty());
+    assert_eq!(errors.len(), 4);
+    assert!(errors.iter().all(|error| matches!(error, LoadError::Json(..))));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn instruments_without_presets_start_with_an_empty_one() {
+    let path = std::env::temp_dir().join(format!("daz-studio-no-presets-{}", std::process::id()));
+    let (mut sequencer, _sender) = Sequencer::new(SAMPLE_RATE, BUFFER_SIZE, &path.to_string_lossy());
+    assert!(sequencer.data.instruments.iter().all(|instrument| !instrument.presets.is_empty()));
+    process(&mut sequencer, 1);
+}
+
+#[test]
+fn the_presets_saved_by_the_instruments_are_read_back() {
+    let path = data_path("preset-save");
+    let directory = path.to_string_lossy();
+    let mut synthesizer = Synthesizer::new(SAMPLE_RATE, 0, 0, &directory);
+    let mut mood = Mood::new(SAMPLE_RATE, 0, &directory);
+    let nb_presets = (synthesizer.get_presets().len(), mood.get_presets().len());
+    synthesizer.save_current_preset().unwrap();
+    mood.save_current_preset().unwrap();
+
+    // Both are written by the preset thread
+    let are_read_back = || Synthesizer::new(SAMPLE_RATE, 0, 0, &directory).get_presets().len() == nb_presets.0 + 1
+        && Mood::new(SAMPLE_RATE, 0, &directory).get_presets().len() == nb_presets.1 + 1;
+    let mut nb_tries = 0;
+    while !are_read_back() {
+        assert!(nb_tries < 200, "presets not saved");
+        nb_tries += 1;
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(synthesizer.get_errors().is_empty() && mood.get_errors().is_empty());
+
+    let _ = std::fs::remove_dir_all(&path);
+}
@@ -0,0 +1,263 @@
+mod common;
+
+use common::{fixture, data_path, SAMPLE_RATE, BUFFER_SIZE};
+
+use sequencer::sampler::wav::read_wav;
+use sequencer::sampler::sampler::Sampler;
+use sequencer::processor::Processor;
+use sequencer::load_error::LoadError;
+use sequencer::decibels::db_to_gain;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::thread;
+
+// Kit of the pads recorded in the kits without a preset.json
+fn recordings_path(path: &Path) -> PathBuf {
+    return path.join("sampler-presets").join("Recordings");
+}
+
+// The track is armed first, the loader gives the buffers of the take
+fn arm(sampler: &mut Sampler) {
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    sampler.reserve_take(true);
+    for _ in 0..200 {
+        sampler.process(&mut outputs, BUFFER_SIZE, 2);
+        if sampler.is_take_ready() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("no buffers for the take");
+}
+
+// Plays the inputs through the sampler like the audio callback does, one block at a time
+fn record(sampler: &mut Sampler, inputs: &[f32]) {
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    arm(sampler);
+    sampler.set_armed(true);
+    for block in inputs.chunks(BUFFER_SIZE) {
+        sampler.record_input(block, block.len(), 1);
+        sampler.process(&mut outputs, block.len(), 2);
+    }
+    sampler.set_armed(false);
+}
+
+// The loader trims and saves the takes, the pads come back from it, true if the parameters changed meanwhile
+fn wait_for_pads(sampler: &mut Sampler, nb_pads: usize) -> bool {
+    let mut have_changed = false;
+    for _ in 0..200 {
+        have_changed |= sampler.have_parameters_changed();
+        if sampler.samples.len() >= nb_pads {
+            return have_changed;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("{} pads instead of {}", sampler.samples.len(), nb_pads);
+}
+
+// The wav is renamed into place once written, it is complete as soon as it's there
+fn wait_for_file(directory: &Path) -> PathBuf {
+    for _ in 0..200 {
+        let wav = std::fs::read_dir(directory).ok()
+            .and_then(|entries| entries.filter_map(|entry| entry.ok()).find(|entry| entry.path().extension().map_or(false, |extension| extension == "wav")));
+        if let Some(entry) = wav {
+            return entry.path();
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("no recording in {:?}", directory);
+}
+
+fn preset_samples(preset_filepath: &Path) -> Vec<serde_json::Value> {
+    let preset: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(preset_filepath).unwrap()).unwrap();
+    return preset["samples"].as_array().unwrap().clone();
+}
+
+#[test]
+fn records_a_trimmed_and_normalized_pad() {
+    let path = data_path("recording");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    // 200 ms of silence, a 500 ms tone at -12 dB, then 300 ms of silence
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+
+    assert!(sampler.get_errors().is_empty());
+    assert_eq!(sampler.samples.len(), 1);
+    let sample = &sampler.samples[0];
+    assert_eq!(sample.root_midi_note, 60);
+    // 5 ms kept before the tone and 50 ms after it
+    assert!((sample.size as i32 - (40 + 4000 + 400)).abs() <= 2, "{} frames", sample.size);
+    let peak = sample.left_channel.iter().fold(0., |peak: f32, value| peak.max(value.abs()));
+    assert!((peak - db_to_gain(-1.)).abs() < 1e-4);
+    assert_eq!(sample.left_channel, sample.right_channel);
+
+    let filepath = wait_for_file(&recordings_path(&path));
+    let (left_channel, right_channel, sample_rate) = read_wav(&filepath.to_string_lossy()).unwrap();
+    assert_eq!(sample_rate, SAMPLE_RATE);
+    assert_eq!(left_channel, sample.left_channel);
+    assert_eq!(right_channel, sample.right_channel);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn adds_the_next_take_after_the_last_pad() {
+    let path = data_path("recording-next");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+    record(&mut sampler, &inputs);
+
+    assert!(wait_for_pads(&mut sampler, 2));
+    assert_eq!(sampler.samples.len(), 2);
+    assert_eq!(sampler.samples[1].note_midi_min, 61);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn keeps_the_takes_recorded_while_the_kit_loads() {
+    let path = data_path("recording-loading");
+    let kit_path = path.join("sampler-presets").join("kit");
+    std::fs::create_dir_all(&kit_path).unwrap();
+    std::fs::copy(fixture("input_take_mono.wav"), kit_path.join("tone.wav")).unwrap();
+    std::fs::write(kit_path.join("preset.json"), r#"{
+        "name": "Kit", "id": 0, "attack": 0.0, "decay": 0.0, "sustain": 1.0, "release": 0.1,
+        "samples": [{ "root_midi_note": 60, "note_midi_min": 60, "note_midi_max": 60, "filepath": "tone.wav", "is_one_shot": true }]
+    }"#).unwrap();
+
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 2);
+
+    assert_eq!(sampler.samples.len(), 2);
+    assert_eq!(sampler.samples[0].root_midi_note, 60);
+    assert_eq!(sampler.samples[0].size, inputs.len());
+    assert_eq!(sampler.samples[1].root_midi_note, 61);
+
+    // The take is saved in the kit folder and added to its preset.json
+    let samples = preset_samples(&kit_path.join("preset.json"));
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[1]["root_midi_note"], 61);
+    let filepath = samples[1]["filepath"].as_str().unwrap();
+    assert!(filepath.starts_with("recording-") && filepath.ends_with("-61.wav"), "{}", filepath);
+    assert!(kit_path.join(filepath).exists());
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn takes_of_a_kit_without_preset_file_are_kept_in_the_recordings_kit() {
+    let path = data_path("recording-kit");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+    assert_eq!(preset_samples(&recordings_path(&path).join("preset.json")).len(), 1);
+
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let presets : Vec<String> = sampler.get_presets().iter().map(|preset| preset.get_name()).collect();
+    let recordings_id = presets.iter().position(|name| name == "Recordings").unwrap();
+    sampler.set_current_preset_id(recordings_id);
+    wait_for_pads(&mut sampler, 1);
+    assert_eq!(sampler.samples[0].root_midi_note, 60);
+    assert!(sampler.get_errors().is_empty());
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn pads_keep_their_parameter_ids_when_a_take_is_added() {
+    let path = data_path("recording-ids");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+    let gain = sampler.get_parameters().into_iter().find(|parameter| parameter.name.ends_with(" gain")).unwrap();
+    sampler.set_parameter(gain.id, -6.);
+
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 2);
+    let parameters = sampler.get_parameters();
+    let gains : Vec<_> = parameters.iter().filter(|parameter| parameter.name.ends_with(" gain")).collect();
+    assert_eq!(gains.len(), 2);
+    assert_eq!(gains[0].name, gain.name);
+    assert_eq!(gains[0].id, gain.id);
+    assert_eq!(sampler.get_parameter(gain.id), -6.);
+    assert_eq!(sampler.get_parameter(gains[1].id), 0.);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn pad_envelope_stages_left_alone_follow_the_kit() {
+    let path = data_path("recording-envelope");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (inputs, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+    let parameters = sampler.get_parameters();
+    let id = |suffix: &str| parameters.iter().find(|parameter| parameter.name.ends_with(suffix)).unwrap().id;
+    let kit_release = parameters.iter().find(|parameter| parameter.name == "Release").unwrap().id;
+
+    sampler.set_parameter(id(" attack"), 0.5);
+    sampler.set_parameter(kit_release, 2.);
+    assert_eq!(sampler.get_parameter(id(" attack")), 0.5);
+    assert_eq!(sampler.get_parameter(id(" release")), 2.);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn ignores_silent_takes() {
+    let path = data_path("recording-silent");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    record(&mut sampler, &vec![0.0001; 4000]);
+    for _ in 0..200 {
+        sampler.have_parameters_changed();
+        if !sampler.get_errors().is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(sampler.samples.len(), 0);
+    assert!(matches!(sampler.get_errors()[..], [LoadError::SilentRecording]));
+    assert!(!recordings_path(&path).exists());
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn takes_longer_than_a_minute_are_cut() {
+    let path = data_path("recording-truncated");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let (tone, _, _) = read_wav(&fixture("input_take_mono.wav")).unwrap();
+    let mut inputs = Vec::new();
+    while inputs.len() < 61 * SAMPLE_RATE as usize {
+        inputs.extend_from_slice(&tone);
+    }
+    record(&mut sampler, &inputs);
+    wait_for_pads(&mut sampler, 1);
+
+    assert!(sampler.samples[0].size <= 60 * SAMPLE_RATE as usize);
+    assert!(matches!(sampler.get_errors()[..], [LoadError::TruncatedRecording(duration)] if duration == 60.));
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn disarmed_tracks_give_back_the_memory_of_their_takes() {
+    let path = data_path("recording-disarmed");
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    assert!(!sampler.is_take_ready());
+    arm(&mut sampler);
+    sampler.reserve_take(false);
+    assert!(!sampler.is_take_ready());
+
+    let _ = std::fs::remove_dir_all(&path);
+}
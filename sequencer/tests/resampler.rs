@@ -0,0 +1,25 @@
+use sequencer::sampler::resampler::Resampler;
+
+// The edges are faded by the missing frames of the sinc, the middle keeps the level of the input
+fn assert_unit_gain(output: &[f32]) {
+    let margin = output.len() / 8;
+    for value in output[margin..output.len() - margin].iter() {
+        assert!((value - 1.).abs() < 1e-3, "{}", value);
+    }
+}
+
+#[test]
+fn resampling_keeps_the_duration_and_the_level() {
+    let resampler = Resampler::new();
+    let input = vec![1.; 1000];
+
+    let upsampled = resampler.resample(&input, 44100., 48000.);
+    assert_eq!(upsampled.len(), 1088);
+    assert_unit_gain(&upsampled);
+
+    let downsampled = resampler.resample(&input, 48000., 8000.);
+    assert_eq!(downsampled.len(), 167);
+    assert_unit_gain(&downsampled);
+
+    assert_eq!(resampler.resample(&input, 8000., 8000.), input);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::{data_path, SAMPLE_RATE, BUFFER_SIZE};
+
+use sequencer::sampler::sampler::Sampler;
+use sequencer::sampler::wav::write_wav;
+use sequencer::processor::Processor;
+
+use std::path::Path;
+use std::time::Duration;
+use std::thread;
+
+// Kit of constant samples, each one is told apart by its level
+fn write_kit(path: &Path, pads: &[(&str, u8, f32, usize, &str)]) {
+    let kit_path = path.join("sampler-presets").join("Test");
+    std::fs::create_dir_all(&kit_path).unwrap();
+    let mut samples = Vec::new();
+    for (name, midi_note, level, nb_frames, group) in pads {
+        let channel = vec![*level; *nb_frames];
+        write_wav(&kit_path.join(format!("{}.wav", name)).to_string_lossy(), &channel, &channel, SAMPLE_RATE as u32).unwrap();
+        let mut sample = serde_json::json!({
+            "root_midi_note": midi_note,
+            "note_midi_min": midi_note,
+            "note_midi_max": midi_note,
+            "filepath": format!("{}.wav", name),
+            "is_one_shot": true,
+        });
+        sample[*group] = 1.into();
+        samples.push(sample);
+    }
+    let preset = serde_json::json!({
+        "attack": 0.0,
+        "decay": 1.0,
+        "sustain": 1.0,
+        "release": 0.1,
+        "name": "Test",
+        "id": 0,
+        "samples": samples,
+    });
+    std::fs::write(kit_path.join("preset.json"), preset.to_string()).unwrap();
+}
+
+fn sampler_with_kit(path: &Path, nb_pads: usize) -> Sampler {
+    let mut sampler = Sampler::new(SAMPLE_RATE, 0, &path.to_string_lossy());
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    for _ in 0..200 {
+        sampler.process(&mut outputs, BUFFER_SIZE, 2);
+        if sampler.samples.len() == nb_pads {
+            return sampler;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("{} pads instead of {}", sampler.samples.len(), nb_pads);
+}
+
+// Sum of the left channel over the blocks
+fn play(sampler: &mut Sampler, nb_blocks: usize) -> f32 {
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    let mut sum = 0.;
+    for _ in 0..nb_blocks {
+        sampler.process(&mut outputs, BUFFER_SIZE, 2);
+        sum += outputs.iter().step_by(2).sum::<f32>();
+    }
+    return sum;
+}
+
+#[test]
+fn round_robin_groups_cycle_through_their_samples() {
+    let path = data_path("sampler-round-robin");
+    write_kit(&path, &[
+        ("first", 60, 0.1, 100, "round_robin_group"),
+        ("second", 60, 0.2, 100, "round_robin_group"),
+        ("third", 60, 0.3, 100, "round_robin_group"),
+    ]);
+    let mut sampler = sampler_with_kit(&path, 3);
+
+    let mut hits = Vec::new();
+    for _ in 0..4 {
+        sampler.note_on(60, 1.);
+        hits.push(play(&mut sampler, 1));
+        sampler.note_off(60);
+    }
+    assert!(hits[0] > 0.);
+    let levels : Vec<f32> = hits.iter().map(|hit| hit / hits[0]).collect();
+    for (level, expected) in levels.iter().zip([1., 2., 3., 1.]) {
+        assert!((level - expected).abs() < 0.01, "{:?}", levels);
+    }
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn a_pad_chokes_the_other_pads_of_its_group() {
+    let path = data_path("sampler-choke");
+    write_kit(&path, &[
+        ("closed", 60, 0.5, 8000, "choke_group"),
+        ("open", 62, -0.25, 8000, "choke_group"),
+    ]);
+    let mut sampler = sampler_with_kit(&path, 2);
+    let mut open_only = sampler_with_kit(&path, 2);
+
+    sampler.note_on(60, 1.);
+    play(&mut sampler, 2);
+    sampler.note_on(62, 1.);
+    open_only.note_on(62, 1.);
+    let nb_blocks = 4;
+    play(&mut sampler, nb_blocks);
+    play(&mut open_only, nb_blocks);
+
+    // Once faded out the closed pad isn't heard anymore
+    let choked = play(&mut sampler, 1);
+    let expected = play(&mut open_only, 1);
+    assert!(expected < 0.);
+    assert!((choked - expected).abs() < 1e-3 * expected.abs(), "{} instead of {}", choked, expected);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{fixture, SAMPLE_RATE};
+
+use sequencer::sampler::sf2::{load_sf2, read_sample};
+use sequencer::sampler::sampler_preset::{SampleInfo, SampleSettings, LoopMode};
+use sequencer::sampler::sample::Sample;
+use sequencer::sampler::sample_voice::SamplerVoice;
+use sequencer::adsr::ADSR;
+
+use std::sync::Arc;
+
+// One preset with an instrument zone looping through the release, one looping until the release
+// and a stereo pair, the envelope of the instrument global zone being extended by the preset
+fn samples() -> Vec<SampleInfo> {
+    let mut errors = Vec::new();
+    let mut presets = load_sf2(&fixture("piano.sf2"), &mut errors).unwrap();
+    assert!(errors.is_empty());
+    assert_eq!(presets.len(), 1);
+    assert_eq!(presets[0].name, "piano - Piano");
+    return presets.remove(0).samples;
+}
+
+fn assert_close(value: Option<f32>, expected: f32) {
+    let value = value.unwrap();
+    assert!((value - expected).abs() < 1e-3, "{} instead of {}", value, expected);
+}
+
+// Whether the voice still plays long after the end of the sample once the note is released
+fn plays_after_release(sample_info: &SampleInfo) -> bool {
+    let sample = Sample::load_sample(sample_info, SAMPLE_RATE).unwrap();
+    let mut voice = SamplerVoice::new(SAMPLE_RATE);
+    voice.adsr = ADSR::new(0.001, 0., 1., 10., SAMPLE_RATE);
+    voice.start_note(sample_info.root_midi_note, 1., Arc::new(sample), SampleSettings::default());
+    let mut outputs = vec![0.; 1024 * 2];
+    voice.render_next_block(&mut outputs, 256, 2);
+    voice.stop_note();
+    voice.render_next_block(&mut outputs, 1024, 2);
+    return !voice.is_ended();
+}
+
+#[test]
+fn zones_keep_the_ranges_of_the_instrument_within_the_preset() {
+    let samples = samples();
+    assert_eq!(samples.len(), 3);
+
+    assert_eq!((samples[0].note_midi_min, samples[0].note_midi_max, samples[0].root_midi_note), (36, 59, 60));
+    assert_eq!(samples[0].settings.fine, 10.);
+
+    assert_eq!((samples[1].note_midi_min, samples[1].note_midi_max, samples[1].root_midi_note), (60, 72, 64));
+    assert_eq!((samples[1].velocity_min, samples[1].velocity_max), (0, 100));
+    assert_eq!(samples[1].settings.gain, -6.);
+    assert_eq!(samples[1].settings.pan, 0.5);
+
+    // The preset key range cuts the instrument one
+    assert_eq!((samples[2].note_midi_min, samples[2].note_midi_max), (73, 100));
+}
+
+#[test]
+fn stereo_links_are_read_as_one_sample() {
+    let samples = samples();
+    let stereo = samples[2].sf2_sample.unwrap();
+    assert!(stereo.right_data_offset.is_some());
+    assert_eq!(stereo.length, 32);
+
+    let (left_channel, right_channel) = read_sample(&samples[2].filepath, &stereo).unwrap();
+    assert_eq!(left_channel, vec![1000. / 32768.; 32]);
+    assert_eq!(right_channel, vec![-1000. / 32768.; 32]);
+
+    // A mono sample is on both channels
+    let (left_channel, right_channel) = read_sample(&samples[0].filepath, &samples[0].sf2_sample.unwrap()).unwrap();
+    assert_eq!(left_channel.len(), 64);
+    assert_eq!(left_channel, right_channel);
+}
+
+#[test]
+fn loop_points_are_relative_to_the_sample() {
+    let samples = samples();
+    assert!(samples[0].loop_mode == LoopMode::Continuous);
+    assert_eq!((samples[0].loop_start, samples[0].loop_end), (8, Some(40)));
+    assert!(samples[1].loop_mode == LoopMode::Forward);
+    assert_eq!((samples[1].loop_start, samples[1].loop_end), (16, Some(32)));
+    assert!(samples[2].loop_mode == LoopMode::None);
+    assert_eq!(samples[2].loop_end, None);
+}
+
+#[test]
+fn only_the_continuous_loop_plays_through_the_release() {
+    let samples = samples();
+    assert!(plays_after_release(&samples[0]));
+    assert!(!plays_after_release(&samples[1]));
+}
+
+#[test]
+fn envelope_adds_the_preset_generators_to_the_instrument_ones() {
+    let samples = samples();
+    let envelope = samples[0].settings.envelope;
+    assert_close(envelope.attack, 0.5);
+    assert_close(envelope.decay, 1.);
+    // 6 dB of attenuation
+    assert_close(envelope.sustain, 0.501);
+    // 1200 timecents in the instrument and 1200 more in the preset
+    assert_close(envelope.release, 4.);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::fixture;
+
+use sequencer::sampler::sfz::load_sfz;
+use sequencer::sampler::sampler_preset::LoopMode;
+use sequencer::load_error::LoadError;
+
+#[test]
+fn regions_inherit_the_opcodes_of_their_headers() {
+    let mut errors = Vec::new();
+    let preset = load_sfz(&fixture("kit.sfz"), &mut errors).unwrap();
+    assert_eq!(preset.name, "kit");
+    assert_eq!(preset.samples.len(), 5);
+
+    let soft = &preset.samples[0];
+    assert_eq!(soft.filepath, fixture("samples/kick soft.wav"));
+    assert_eq!((soft.note_midi_min, soft.note_midi_max, soft.root_midi_note), (36, 38, 36));
+    assert_eq!((soft.velocity_min, soft.velocity_max), (0, 63));
+    assert_eq!(soft.settings.gain, -3.);
+
+    let hard = &preset.samples[1];
+    assert_eq!(hard.filepath, fixture("samples/kick hard.wav"));
+    assert_eq!((hard.velocity_min, hard.velocity_max), (64, 127));
+
+    let pad = &preset.samples[2];
+    assert_eq!((pad.note_midi_min, pad.note_midi_max, pad.root_midi_note), (60, 60, 60));
+    assert!(pad.loop_mode == LoopMode::Continuous);
+    // The sfz loop end is the last frame of the loop, ours is the frame after it
+    assert_eq!((pad.loop_start, pad.loop_end), (100, Some(200)));
+    assert_eq!(pad.settings.pan, -0.5);
+    assert_eq!(pad.settings.fine, 12.);
+
+    let short = &preset.samples[3];
+    assert!(short.loop_mode == LoopMode::None);
+    assert!(short.is_one_shot);
+    // Only loop_continuous keeps looping in the release
+    assert!(preset.samples[4].loop_mode == LoopMode::Forward);
+}
+
+#[test]
+fn an_envelope_opcode_sets_the_whole_envelope() {
+    let mut errors = Vec::new();
+    let preset = load_sfz(&fixture("kit.sfz"), &mut errors).unwrap();
+
+    let envelope = preset.samples[0].settings.envelope;
+    assert_eq!(envelope.attack, Some(0.));
+    assert_eq!(envelope.decay, Some(0.));
+    assert_eq!(envelope.sustain, Some(1.));
+    assert_eq!(envelope.release, Some(0.5));
+    let envelope = preset.samples[1].settings.envelope;
+    assert_eq!((envelope.attack, envelope.decay, envelope.sustain, envelope.release), (None, None, None, None));
+}
+
+#[test]
+fn reports_the_headers_and_opcodes_it_cant_play() {
+    let mut errors = Vec::new();
+    load_sfz(&fixture("kit.sfz"), &mut errors).unwrap();
+    match &errors[..] {
+        [LoadError::UnsupportedOpcodes(_, opcodes)] => assert_eq!(opcodes, &["fil_type", "<curve>"]),
+        _ => panic!("{:?}", errors.iter().map(|error| error.to_string()).collect::<Vec<_>>()),
+    }
+}
+
+#[test]
+fn skips_the_regions_with_an_invalid_value() {
+    let mut errors = Vec::new();
+    let preset = load_sfz(&fixture("invalid_note.sfz"), &mut errors).unwrap();
+    assert_eq!(preset.samples.len(), 1);
+    assert_eq!(preset.samples[0].root_midi_note, 38);
+    assert!(matches!(&errors[..], [LoadError::Sfz(_, message)] if message.contains("h2")));
+}
+
+#[test]
+fn rejects_an_unterminated_header() {
+    let mut errors = Vec::new();
+    let result = load_sfz(&fixture("unterminated_header.sfz"), &mut errors);
+    assert!(matches!(result, Err(LoadError::Sfz(..))));
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::SAMPLE_RATE;
+
+use sequencer::sampler::slicer::Slicer;
+
+fn slicer(channel: Vec<f32>) -> Slicer {
+    Slicer {
+        filepath: "loop.wav".to_string(),
+        left_channel: channel.clone(),
+        right_channel: channel,
+        sample_rate: SAMPLE_RATE,
+        slice_points: vec![0],
+    }
+}
+
+#[test]
+fn transients_are_found_at_the_impulses() {
+    let mut channel = vec![0.; 16000];
+    for position in [0, 4096, 8192, 12288] {
+        channel[position] = 1.;
+    }
+    let mut slicer = slicer(channel);
+    slicer.detect_transients(0.5);
+    assert_eq!(slicer.slice_points, [0, 4096, 8192, 12288]);
+}
+
+#[test]
+fn the_grid_follows_the_tempo() {
+    // Two seconds at 120 bpm are four beats, eight slices of 1/8
+    let mut slicer = slicer(vec![0.; 2 * SAMPLE_RATE as usize]);
+    slicer.divide_grid(120., 1);
+    assert_eq!(slicer.slice_points, (0..8).map(|k| k * 2000).collect::<Vec<usize>>());
+
+    slicer.divide_grid(60., 0);
+    assert_eq!(slicer.slice_points, [0, 8000]);
+}
+
+#[test]
+fn slice_points_stay_between_their_neighbours() {
+    let mut slicer = slicer(vec![0.; 3000]);
+    slicer.slice_points = vec![0, 1000, 2000];
+    slicer.set_slice_point(1, 5000);
+    assert_eq!(slicer.slice_points, [0, 1999, 2000]);
+    slicer.set_slice_point(1, 0);
+    assert_eq!(slicer.slice_points, [0, 1, 2000]);
+    slicer.set_slice_point(2, 10000);
+    assert_eq!(slicer.slice_points, [0, 1, 2999]);
+
+    let mut sliced_loop = slicer.sliced_loop();
+    sliced_loop.set_slice_point(1, 3500);
+    assert_eq!(sliced_loop.slice_points, [0, 2998, 2999]);
+}
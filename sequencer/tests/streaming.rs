@@ -0,0 +1,111 @@
+mod common;
+
+use common::{data_path, SAMPLE_RATE};
+
+use sequencer::sampler::sample::Sample;
+use sequencer::sampler::sample_loader::{SampleLoader, LoadRequest, LoadResult, STREAM_BLOCK_SIZE, STREAM_WINDOW};
+use sequencer::sampler::sampler_preset::SampleInfo;
+use sequencer::sampler::wav::write_wav;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::thread;
+
+// Half the engine rate, the stream is resampled
+const FILE_SAMPLE_RATE : f32 = SAMPLE_RATE / 2.;
+const DURATION : f32 = 50.;
+
+// A slow sweep so every frame differs from its neighbours
+fn write_long_wav(path: &Path) -> String {
+    std::fs::create_dir_all(path).unwrap();
+    let filepath = path.join("long.wav").to_string_lossy().to_string();
+    let nb_frames = (DURATION * FILE_SAMPLE_RATE) as usize;
+    let left_channel : Vec<f32> = (0..nb_frames).map(|i| (i as f32 * (0.01 + i as f32 * 1e-8)).sin() * 0.5).collect();
+    let right_channel : Vec<f32> = left_channel.iter().map(|value| -value).collect();
+    write_wav(&filepath, &left_channel, &right_channel, FILE_SAMPLE_RATE as u32).unwrap();
+    return filepath;
+}
+
+// The stream is read while the loader lives
+fn load(sample_info: SampleInfo) -> (SampleLoader, Arc<Sample>) {
+    let loader = SampleLoader::new(SAMPLE_RATE);
+    loader.request(LoadRequest::Kit { generation: 0, samples: vec![sample_info] });
+    for _ in 0..500 {
+        if let Some(LoadResult::Kit { mut samples, errors, .. }) = loader.try_receive() {
+            assert!(errors.is_empty());
+            return (loader, samples.remove(0));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the kit isn't loaded");
+}
+
+fn assert_same_frames(sample: &Sample, expected: &Sample, frames: std::ops::Range<usize>) {
+    for idx in frames {
+        let (left, right) = sample.frame(idx);
+        let (expected_left, expected_right) = expected.frame(idx);
+        assert!((left - expected_left).abs() < 1e-5 && (right - expected_right).abs() < 1e-5,
+            "frame {}: {} {} instead of {} {}", idx, left, right, expected_left, expected_right);
+    }
+}
+
+#[test]
+fn files_at_another_rate_are_streamed_resampled() {
+    let path = data_path("streaming-rate");
+    let sample_info = SampleInfo::new(write_long_wav(&path), 60);
+    let expected = Sample::load_sample(&sample_info, SAMPLE_RATE).unwrap();
+    let (_loader, sample) = load(sample_info);
+
+    assert!(sample.stream.is_some());
+    assert_eq!(sample.size, expected.size);
+    // The head and the blocks after it are read before the sample is played
+    let head_size = sample.left_channel.len();
+    assert_same_frames(&sample, &expected, 0..head_size + STREAM_BLOCK_SIZE);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn reversed_pads_start_with_the_end_of_the_sample_read() {
+    let path = data_path("streaming-reverse");
+    let mut sample_info = SampleInfo::new(write_long_wav(&path), 60);
+    sample_info.settings.reverse = true;
+    sample_info.settings.end = 0.75;
+    let settings = sample_info.settings;
+    let expected = Sample::load_sample(&sample_info, SAMPLE_RATE).unwrap();
+    let (_loader, sample) = load(sample_info);
+
+    let first_frame = sample.first_played_frame(&settings) as usize;
+    assert!(first_frame > sample.left_channel.len() + (STREAM_WINDOW + 1) * STREAM_BLOCK_SIZE);
+    assert_same_frames(&sample, &expected, first_frame - STREAM_BLOCK_SIZE..first_frame + 1);
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn only_the_blocks_around_the_played_frames_stay_in_memory() {
+    let path = data_path("streaming-window");
+    let sample_info = SampleInfo::new(write_long_wav(&path), 60);
+    let expected = Sample::load_sample(&sample_info, SAMPLE_RATE).unwrap();
+    let (_loader, sample) = load(sample_info);
+    let stream = sample.stream.as_ref().unwrap();
+    let head_size = sample.left_channel.len();
+    let nb_blocks = stream.len().div_ceil(STREAM_BLOCK_SIZE);
+    // A voice playing through the sample, the blocks around the start stay for the next notes
+    let max_blocks_read = 2 * (2 * STREAM_WINDOW + 1);
+    assert!(nb_blocks > max_blocks_read);
+    for block_idx in 0..nb_blocks {
+        let frame = head_size + block_idx * STREAM_BLOCK_SIZE;
+        for _ in 0..200 {
+            if sample.frame(frame) == expected.frame(frame) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_same_frames(&sample, &expected, frame..frame + 100);
+        assert!(stream.nb_blocks_read() <= max_blocks_read, "{} blocks read", stream.nb_blocks_read());
+    }
+
+    let _ = std::fs::remove_dir_all(&path);
+}
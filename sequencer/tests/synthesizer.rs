@@ -0,0 +1,92 @@
+mod common;
+
+use common::{SAMPLE_RATE, BUFFER_SIZE};
+
+use sequencer::synthesizer::synthesizer::Synthesizer;
+use sequencer::synthesizer::synthesizer_preset::SynthesizerPreset;
+use sequencer::synthesizer::operator::SINE;
+use sequencer::synthesizer::algorithm::NB_ALGORITHMS;
+use sequencer::processor::Processor;
+use sequencer::load_error::LoadError;
+
+use std::path::{Path, PathBuf};
+
+const ALGORITHM : usize = 0;
+const NB_FACTORY_PRESETS : usize = 3;
+
+// Data folder with the factory presets and the given user presets
+fn data_path(name: &str, presets: &[(&str, String)]) -> PathBuf {
+    let path = common::data_path(name);
+    for (file_name, json) in presets {
+        std::fs::write(path.join("synthesizer-presets").join(file_name), json).unwrap();
+    }
+    return path;
+}
+
+// Four sine operators at full level, every routing can be heard
+// The algorithm is set in the JSON since the unknown ones can't be loaded in a preset
+fn preset_json(name: &str, algorithm: u8) -> String {
+    let mut preset = SynthesizerPreset::empty();
+    preset.name = name.to_string();
+    preset.oscx_osc_type = [SINE; 4];
+    preset.oscx_level = [1.; 4];
+    preset.oscx_coarse = [1., 2., 3., 1.];
+    let mut json = serde_json::to_value(&preset).unwrap();
+    json["algorithm"] = algorithm.into();
+    return serde_json::to_string_pretty(&json).unwrap();
+}
+
+fn play(synthesizer: &mut Synthesizer) -> Vec<f32> {
+    let mut outputs = vec![0.; BUFFER_SIZE * 2];
+    synthesizer.note_on(57, 1.);
+    synthesizer.process(&mut outputs, BUFFER_SIZE, 2);
+    synthesizer.all_note_off();
+    return outputs;
+}
+
+fn synthesizer(path: &Path) -> Synthesizer {
+    return Synthesizer::new(SAMPLE_RATE, 0, NB_FACTORY_PRESETS, &path.to_string_lossy());
+}
+
+#[test]
+fn every_algorithm_has_its_own_routing() {
+    let path = data_path("synthesizer-algorithms", &[("fm.json", preset_json("FM", 1))]);
+    let mut renders : Vec<Vec<f32>> = Vec::new();
+    for algorithm in 0..NB_ALGORITHMS {
+        let mut synthesizer = synthesizer(&path);
+        assert!(synthesizer.get_errors().is_empty());
+        synthesizer.set_parameter(ALGORITHM, algorithm as f32);
+        assert_eq!(synthesizer.get_parameter(ALGORITHM), algorithm as f32);
+
+        let outputs = play(&mut synthesizer);
+        assert!(outputs.iter().any(|value| value.abs() > 0.01), "algorithm {} is silent", algorithm + 1);
+        for (other, render) in renders.iter().enumerate() {
+            let difference = outputs.iter().zip(render.iter()).fold(0., |difference: f32, (a, b)| difference.max((a - b).abs()));
+            assert!(difference > 0.01, "algorithms {} and {} sound the same", other + 1, algorithm + 1);
+        }
+        renders.push(outputs);
+    }
+
+    let _ = std::fs::remove_dir_all(&path);
+}
+
+#[test]
+fn rejects_presets_with_an_unknown_algorithm() {
+    let path = data_path("synthesizer-unknown-algorithm", &[
+        ("eight.json", preset_json("Eight", 8)),
+        ("eleven.json", preset_json("Eleven", 11)),
+        ("zero.json", preset_json("Zero", 0)),
+    ]);
+    let mut synthesizer = synthesizer(&path);
+
+    let presets : Vec<String> = synthesizer.get_presets().iter().map(|preset| preset.get_name()).collect();
+    assert_eq!(presets[NB_FACTORY_PRESETS..], ["Eight".to_string()]);
+    let errors = synthesizer.get_errors();
+    assert_eq!(errors.len(), 2);
+    for error in errors.iter() {
+        assert!(matches!(error, LoadError::Json(..)));
+        assert!(error.to_string().contains("unknown algorithm"), "{}", error);
+    }
+
+    let _ = std::fs::remove_dir_all(&path);
+}
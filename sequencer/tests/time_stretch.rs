@@ -0,0 +1,42 @@
+mod common;
+
+use common::SAMPLE_RATE;
+
+use sequencer::sampler::sample::Sample;
+use sequencer::sampler::time_stretch::GrainStretcher;
+
+fn sample(channel: Vec<f32>) -> Sample {
+    Sample {
+        sample_rate: SAMPLE_RATE,
+        size: channel.len(),
+        left_channel: channel.clone(),
+        right_channel: channel,
+        ..Sample::empty()
+    }
+}
+
+// The timeline moves at half the speed of the grains, the sample lasts twice as long
+fn stretch(sample: &Sample, nb_frames: usize) -> Vec<f32> {
+    let mut stretcher = GrainStretcher::new(SAMPLE_RATE);
+    stretcher.reset(0., 1.);
+    return (0..nb_frames).map(|n| stretcher.next_frame(sample, n as f32 * 0.5, 1.).0).collect();
+}
+
+#[test]
+fn the_grain_windows_sum_to_one() {
+    let output = stretch(&sample(vec![1.; 8000]), 8000);
+    for value in output.iter() {
+        assert!((value - 1.).abs() < 1e-3, "{}", value);
+    }
+}
+
+#[test]
+fn stretching_keeps_the_pitch() {
+    let frequency = 400.;
+    let channel = (0..8000).map(|n| (2. * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE).sin()).collect();
+    let output = stretch(&sample(channel), 8000);
+
+    // Two zero crossings per period, over the second of output
+    let nb_crossings = output.windows(2).filter(|pair| (pair[0] < 0.) != (pair[1] < 0.)).count();
+    assert!((nb_crossings as f32 - 2. * frequency).abs() < 0.05 * 2. * frequency, "{} zero crossings", nb_crossings);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::fixture;
+
+use sequencer::sampler::wav::read_wav;
+use sequencer::sampler::sample::Sample;
+use sequencer::sampler::sampler_preset::SampleInfo;
+use sequencer::load_error::LoadError;
+
+// Every fixture holds these frames, written in its format
+const LEFT : [f32; 4] = [0., 0.5, -0.5, -1.];
+const RIGHT : [f32; 4] = [0.25, -0.25, 0.75, 0.];
+
+fn assert_frames(channel: &[f32], expected: &[f32], tolerance: f32) {
+    assert_eq!(channel.len(), expected.len());
+    for (value, expected) in channel.iter().zip(expected.iter()) {
+        assert!((value - expected).abs() <= tolerance, "{:?} instead of {:?}", channel, expected);
+    }
+}
+
+fn assert_stereo(name: &str, sample_rate: f32, tolerance: f32) {
+    let (left_channel, right_channel, file_sample_rate) = read_wav(&fixture(name)).unwrap();
+    assert_eq!(file_sample_rate, sample_rate);
+    assert_frames(&left_channel, &LEFT, tolerance);
+    assert_frames(&right_channel, &RIGHT, tolerance);
+}
+
+#[test]
+fn reads_8_bits_mono_on_both_channels() {
+    let (left_channel, right_channel, sample_rate) = read_wav(&fixture("pcm8_mono.wav")).unwrap();
+    assert_eq!(sample_rate, 22050.);
+    assert_frames(&left_channel, &LEFT, 1. / 128.);
+    assert_frames(&right_channel, &LEFT, 1. / 128.);
+}
+
+#[test]
+fn reads_16_bits() {
+    assert_stereo("pcm16_stereo.wav", 44100., 1. / 32768.);
+}
+
+#[test]
+fn reads_24_bits() {
+    assert_stereo("pcm24_stereo.wav", 48000., 1. / 8388608.);
+}
+
+#[test]
+fn reads_32_bits() {
+    assert_stereo("pcm32_stereo.wav", 96000., 1e-6);
+}
+
+#[test]
+fn reads_32_bits_float() {
+    assert_stereo("float32_stereo.wav", 44100., 0.);
+}
+
+#[test]
+fn reads_64_bits_float() {
+    assert_stereo("float64_stereo.wav", 44100., 0.);
+}
+
+#[test]
+fn reads_extensible_headers() {
+    assert_stereo("extensible_pcm24_stereo.wav", 44100., 1. / 8388608.);
+    assert_stereo("extensible_pcm24_in_32_stereo.wav", 44100., 1. / 8388608.);
+}
+
+#[test]
+fn skips_unknown_chunks() {
+    assert_stereo("pcm16_stereo_list_chunk.wav", 44100., 1. / 32768.);
+}
+
+#[test]
+fn downmixes_5_1_without_the_lfe() {
+    // Front left and right, a constant center at 0.5, a full LFE and silent back channels
+    let (left_channel, right_channel, _) = read_wav(&fixture("extensible_float32_5_1.wav")).unwrap();
+    let center = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+    let total = 2. + std::f32::consts::FRAC_1_SQRT_2;
+    let left: Vec<f32> = LEFT.iter().map(|value| (value + center) / total).collect();
+    let right: Vec<f32> = RIGHT.iter().map(|value| (value + center) / total).collect();
+    assert_frames(&left_channel, &left, 1e-6);
+    assert_frames(&right_channel, &right, 1e-6);
+}
+
+#[test]
+fn downmixes_quad_without_channel_mask() {
+    // The back channels repeat the front ones
+    let (left_channel, right_channel, _) = read_wav(&fixture("pcm16_quad.wav")).unwrap();
+    assert_frames(&left_channel, &LEFT, 1. / 32768.);
+    assert_frames(&right_channel, &RIGHT, 1. / 32768.);
+}
+
+#[test]
+fn rejects_unsupported_files() {
+    assert!(matches!(read_wav(&fixture("adpcm_mono.wav")), Err(LoadError::Wav(_, _))));
+    assert!(matches!(read_wav(&fixture("not_a_wav.wav")), Err(LoadError::Wav(_, _))));
+    assert!(matches!(read_wav(&fixture("missing.wav")), Err(LoadError::Io(_, _))));
+}
+
+#[test]
+fn loads_samples_at_the_engine_rate() {
+    let sample = Sample::load_sample(&SampleInfo::new(fixture("float32_stereo.wav"), 60), 44100.).unwrap();
+    assert_eq!(sample.size, 4);
+    assert_frames(&sample.left_channel, &LEFT, 0.);
+
+    let sample = Sample::load_sample(&SampleInfo::new(fixture("pcm24_stereo.wav"), 60), 96000.).unwrap();
+    assert_eq!(sample.size, 8);
+}